@@ -0,0 +1,433 @@
+use color_eyre::Result;
+use mongodb::bson::DateTime;
+use poise::{serenity::model::prelude::*, serenity_prelude::Http};
+use tracing::info;
+
+use crate::{
+    lawsuit::Lawsuit,
+    model::{PrisonEntry, SnowflakeId},
+    record::stay_hours,
+    time::saturating_after_hours,
+    Mongo, WrapErr,
+};
+
+/// Something a member can be measured by, derived from the court and prison data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    LawsuitsFiled,
+    Acquittals,
+    CasesJudged,
+    SuccessfulDefenses,
+    LongestPrisonHours,
+    AppealEscapes,
+}
+
+/// An achievement is unlocked as soon as its counter reaches the threshold.
+#[derive(Debug)]
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub counter: Counter,
+    pub threshold: f64,
+}
+
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        id: "first_lawsuit",
+        name: "Querulant",
+        description: "Die erste Klage eingereicht",
+        counter: Counter::LawsuitsFiled,
+        threshold: 1.0,
+    },
+    Achievement {
+        id: "first_acquittal",
+        name: "Unschuldslamm",
+        description: "Zum ersten Mal freigesprochen",
+        counter: Counter::Acquittals,
+        threshold: 1.0,
+    },
+    Achievement {
+        id: "judged_10",
+        name: "Richter Gnadenlos",
+        description: "10 Prozesse geleitet",
+        counter: Counter::CasesJudged,
+        threshold: 10.0,
+    },
+    Achievement {
+        id: "defended_5",
+        name: "Staranwalt",
+        description: "5 Angeklagte erfolgreich verteidigt",
+        counter: Counter::SuccessfulDefenses,
+        threshold: 5.0,
+    },
+    Achievement {
+        id: "prison_24h",
+        name: "Stammgast",
+        description: "24 Stunden am Stück im Gefängnis verbracht",
+        counter: Counter::LongestPrisonHours,
+        threshold: 24.0,
+    },
+    Achievement {
+        id: "appeal_escape",
+        name: "Ausbrecher",
+        description: "Aus dem Gefängnis heraus freigesprochen",
+        counter: Counter::AppealEscapes,
+        threshold: 1.0,
+    },
+];
+
+/// An event in the court or the prison which might unlock achievements.
+#[derive(Debug)]
+pub enum CourtEvent<'a> {
+    LawsuitFiled(&'a Lawsuit),
+    LawsuitClosed(&'a Lawsuit),
+    PrisonReleased(SnowflakeId),
+}
+
+impl CourtEvent<'_> {
+    fn users(&self) -> Vec<SnowflakeId> {
+        match self {
+            Self::LawsuitFiled(lawsuit) => vec![lawsuit.plaintiff],
+            Self::LawsuitClosed(lawsuit) => [
+                Some(lawsuit.accused),
+                Some(lawsuit.judge),
+                lawsuit.accused_lawyer,
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            Self::PrisonReleased(user_id) => vec![*user_id],
+        }
+    }
+
+    fn lawsuit(&self) -> Option<&Lawsuit> {
+        match self {
+            Self::LawsuitFiled(lawsuit) | Self::LawsuitClosed(lawsuit) => Some(lawsuit),
+            Self::PrisonReleased(_) => None,
+        }
+    }
+}
+
+/// The counters of a single member.
+#[derive(Debug, Default)]
+pub struct Progress {
+    lawsuits_filed: f64,
+    acquittals: f64,
+    cases_judged: f64,
+    successful_defenses: f64,
+    longest_prison_hours: f64,
+    appeal_escapes: f64,
+}
+
+impl Progress {
+    /// The counters of the member as of `now`, ongoing prison stays count until then.
+    pub fn compute(
+        user_id: SnowflakeId,
+        lawsuits: &[Lawsuit],
+        prison: &[PrisonEntry],
+        now: DateTime,
+    ) -> Self {
+        let mut progress = Self::default();
+
+        for lawsuit in lawsuits.iter().filter(|lawsuit| !lawsuit.is_deleted()) {
+            if lawsuit.plaintiff == user_id {
+                progress.lawsuits_filed += 1.0;
+            }
+
+            let acquitted = lawsuit.guilty == Some(false);
+
+            if lawsuit.judge == user_id && lawsuit.guilty.is_some() {
+                progress.cases_judged += 1.0;
+            }
+            if lawsuit.accused_lawyer == Some(user_id) && acquitted {
+                progress.successful_defenses += 1.0;
+            }
+            if lawsuit.accused == user_id && acquitted {
+                progress.acquittals += 1.0;
+
+                let imprisoned = lawsuit.closed_at.is_some_and(|closed_at| {
+                    prison
+                        .iter()
+                        .any(|entry| is_imprisoned_at(entry, closed_at))
+                });
+                if imprisoned {
+                    progress.appeal_escapes += 1.0;
+                }
+            }
+        }
+
+        progress.longest_prison_hours = prison
+            .iter()
+            .filter_map(|entry| stay_hours(entry, now))
+            .fold(0.0, f64::max);
+
+        progress
+    }
+
+    pub fn get(&self, counter: Counter) -> f64 {
+        match counter {
+            Counter::LawsuitsFiled => self.lawsuits_filed,
+            Counter::Acquittals => self.acquittals,
+            Counter::CasesJudged => self.cases_judged,
+            Counter::SuccessfulDefenses => self.successful_defenses,
+            Counter::LongestPrisonHours => self.longest_prison_hours,
+            Counter::AppealEscapes => self.appeal_escapes,
+        }
+    }
+}
+
+fn is_imprisoned_at(entry: &PrisonEntry, at: DateTime) -> bool {
+    let arrested = entry
+        .arrested_at
+        .is_some_and(|arrested_at| arrested_at <= at);
+    let released = entry
+        .released_at
+        .is_some_and(|released_at| released_at < at);
+    arrested && !released
+}
+
+pub async fn load_progress(
+    mongo: &Mongo,
+    guild_id: SnowflakeId,
+    user_id: SnowflakeId,
+) -> Result<Progress> {
+    let state = mongo.find_or_insert_state(guild_id).await?;
+    let prison = mongo.find_prison_history(guild_id, user_id).await?;
+    Ok(Progress::compute(
+        user_id,
+        &state.lawsuits,
+        &prison,
+        DateTime::now(),
+    ))
+}
+
+/// Unlocks all achievements the users involved in the event have earned and announces them
+/// in the channel.
+#[tracing::instrument(skip(mongo, http))]
+pub async fn process_event(
+    mongo: &Mongo,
+    http: &Http,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    event: CourtEvent<'_>,
+) -> Result<()> {
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+
+    // the event's lawsuit might not be written to the database yet
+    let mut lawsuits = state.lawsuits;
+    if let Some(lawsuit) = event.lawsuit() {
+        lawsuits.retain(|l| l.id != lawsuit.id);
+        lawsuits.push(lawsuit.clone());
+    }
+
+    for user_id in event.users() {
+        for achievement in unlock_earned(mongo, guild_id.into(), user_id, &lawsuits).await? {
+            channel_id
+                .send_message(http, |msg| {
+                    msg.embed(|embed| {
+                        embed
+                            .title(format!("🏆 {}", achievement.name))
+                            .description(format!(
+                                "<@{}> hat eine Errungenschaft freigeschaltet: {}",
+                                user_id, achievement.description
+                            ))
+                    })
+                })
+                .await
+                .wrap_err("announce achievement")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Unlocks the prison achievements of everyone who has been in prison long enough, in every
+/// guild. Long stays don't end with an event, so the scheduler checks them. The achievements are
+/// not announced, there is no channel they would belong to.
+#[tracing::instrument(skip_all)]
+pub async fn unlock_prison_achievements(mongo: &Mongo) -> Result<()> {
+    let shortest = ACHIEVEMENTS
+        .iter()
+        .filter(|achievement| achievement.counter == Counter::LongestPrisonHours)
+        .map(|achievement| achievement.threshold)
+        .fold(f64::INFINITY, f64::min);
+    if shortest.is_infinite() {
+        return Ok(());
+    }
+
+    let now = DateTime::now();
+    let arrested_before = saturating_after_hours(now, -shortest);
+
+    for entry in mongo.find_prisoners_since(arrested_before).await? {
+        let state = mongo.find_or_insert_state(entry.guild_id).await?;
+        unlock_earned(mongo, entry.guild_id, entry.user_id, &state.lawsuits).await?;
+    }
+
+    Ok(())
+}
+
+/// Unlocks all achievements the user has earned and not unlocked yet. Returns the newly unlocked
+/// ones.
+async fn unlock_earned(
+    mongo: &Mongo,
+    guild_id: SnowflakeId,
+    user_id: SnowflakeId,
+    lawsuits: &[Lawsuit],
+) -> Result<Vec<&'static Achievement>> {
+    let prison = mongo.find_prison_history(guild_id, user_id).await?;
+    let progress = Progress::compute(user_id, lawsuits, &prison, DateTime::now());
+
+    let mut unlocked = Vec::new();
+    for achievement in ACHIEVEMENTS {
+        if progress.get(achievement.counter) < achievement.threshold {
+            continue;
+        }
+
+        let newly_unlocked = mongo
+            .unlock_achievement(guild_id, user_id, achievement.id)
+            .await?;
+
+        if newly_unlocked {
+            info!(%user_id, achievement = achievement.id, "Unlocked achievement");
+            unlocked.push(achievement);
+        }
+    }
+
+    Ok(unlocked)
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::Uuid;
+
+    use super::*;
+    use crate::{gallery::Visibility, time::MILLIS_PER_HOUR};
+
+    const NOW: DateTime = DateTime::from_millis(1_650_000_000_000);
+    const ACCUSED: SnowflakeId = SnowflakeId(1);
+    const LAWYER: SnowflakeId = SnowflakeId(2);
+    const JUDGE: SnowflakeId = SnowflakeId(3);
+
+    fn hours_ago(hours: i64) -> DateTime {
+        DateTime::from_millis(NOW.timestamp_millis() - hours * MILLIS_PER_HOUR)
+    }
+
+    fn lawsuit(guilty: Option<bool>) -> Lawsuit {
+        Lawsuit {
+            id: Uuid::new(),
+            plaintiff: SnowflakeId(4),
+            accused: ACCUSED,
+            plaintiff_lawyer: None,
+            accused_lawyer: Some(LAWYER),
+            judge: JUDGE,
+            reason: "Diebstahl".to_string(),
+            verdict: None,
+            guilty,
+            court_room: SnowflakeId(5),
+            opened_at: Some(hours_ago(2)),
+            closed_at: guilty.map(|_| hours_ago(1)),
+            outcome: None,
+            floor: None,
+            contempts: Vec::new(),
+            log: Vec::new(),
+            witnesses: Vec::new(),
+            visibility: Visibility::default(),
+            docket_message: None,
+            plea: None,
+            plea_deadline: None,
+            appeared: false,
+            appearance_deadline: None,
+            default_judgment_offered: false,
+            deleted_at: None,
+        }
+    }
+
+    fn stay(arrested_hours_ago: i64, released_hours_ago: Option<i64>) -> PrisonEntry {
+        PrisonEntry {
+            guild_id: SnowflakeId(6),
+            user_id: ACCUSED,
+            arrested_at: Some(hours_ago(arrested_hours_ago)),
+            released_at: released_hours_ago.map(hours_ago),
+            release_at: None,
+            schema_version: 0,
+        }
+    }
+
+    fn unlocked(progress: &Progress) -> Vec<&'static str> {
+        ACHIEVEMENTS
+            .iter()
+            .filter(|achievement| progress.get(achievement.counter) >= achievement.threshold)
+            .map(|achievement| achievement.id)
+            .collect()
+    }
+
+    #[test]
+    fn judge_needs_ten_verdicts() {
+        let mut lawsuits = vec![lawsuit(Some(true)); 9];
+        lawsuits.push(lawsuit(None));
+        assert!(!unlocked(&Progress::compute(JUDGE, &lawsuits, &[], NOW)).contains(&"judged_10"));
+
+        lawsuits.push(lawsuit(Some(false)));
+        assert!(unlocked(&Progress::compute(JUDGE, &lawsuits, &[], NOW)).contains(&"judged_10"));
+    }
+
+    #[test]
+    fn deleted_lawsuits_do_not_count() {
+        let mut deleted = lawsuit(Some(false));
+        deleted.deleted_at = Some(NOW);
+
+        let progress = Progress::compute(ACCUSED, &[deleted], &[], NOW);
+
+        assert_eq!(progress.get(Counter::Acquittals), 0.0);
+    }
+
+    #[test]
+    fn prison_stay_counts_from_exactly_24_hours() {
+        let short = Progress::compute(ACCUSED, &[], &[stay(23, Some(0))], NOW);
+        assert!(!unlocked(&short).contains(&"prison_24h"));
+
+        let exact = Progress::compute(ACCUSED, &[], &[stay(30, Some(6))], NOW);
+        assert!(unlocked(&exact).contains(&"prison_24h"));
+    }
+
+    #[test]
+    fn ongoing_prison_stay_counts_until_now() {
+        let progress = Progress::compute(ACCUSED, &[], &[stay(24, None)], NOW);
+
+        assert_eq!(progress.get(Counter::LongestPrisonHours), 24.0);
+        assert!(unlocked(&progress).contains(&"prison_24h"));
+    }
+
+    #[test]
+    fn separate_stays_are_not_added_up() {
+        let prison = [stay(40, Some(28)), stay(20, Some(8))];
+
+        let progress = Progress::compute(ACCUSED, &[], &prison, NOW);
+
+        assert_eq!(progress.get(Counter::LongestPrisonHours), 12.0);
+    }
+
+    #[test]
+    fn acquittal_from_prison_is_an_appeal_escape() {
+        let acquittal = [lawsuit(Some(false))];
+
+        let free = Progress::compute(ACCUSED, &acquittal, &[stay(10, Some(5))], NOW);
+        assert_eq!(unlocked(&free), ["first_acquittal"]);
+
+        let imprisoned = Progress::compute(ACCUSED, &acquittal, &[stay(10, None)], NOW);
+        assert_eq!(unlocked(&imprisoned), ["first_acquittal", "appeal_escape"]);
+    }
+
+    #[test]
+    fn lawyer_counts_successful_defenses_only() {
+        let lawsuits = [vec![lawsuit(Some(false)); 4], vec![lawsuit(Some(true)); 3]].concat();
+        assert_eq!(
+            Progress::compute(LAWYER, &lawsuits, &[], NOW).get(Counter::SuccessfulDefenses),
+            4.0
+        );
+
+        let lawsuits = [lawsuits, vec![lawsuit(Some(false))]].concat();
+        assert!(unlocked(&Progress::compute(LAWYER, &lawsuits, &[], NOW)).contains(&"defended_5"));
+    }
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+use color_eyre::Result;
+use mongodb::bson::{DateTime, Uuid};
+use poise::{
+    serenity::model::{
+        interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+        prelude::*,
+    },
+    serenity_prelude::{CreateComponents, Http},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    handler::Response,
+    lawsuit::{Closed, Lawsuit, LawsuitCtx},
+    model::SnowflakeId,
+    procedure::CaseEvent,
+    time::{self, MILLIS_PER_MINUTE},
+    venue::Venue,
+    Mongo, WrapErr,
+};
+
+const DEFAULT_JUDGMENT_PREFIX: &str = "default_judgment";
+
+/// The longest time the accused can be given to show up.
+pub const MAX_DEADLINE_MINUTES: u64 = 30 * 24 * 60;
+
+/// When the accused has to show up in the court room.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppearanceConfig {
+    /// How long after the opening the accused has to post in the court room or enter a plea.
+    /// `None` disables default judgments for missed appearances.
+    pub deadline_minutes: Option<u64>,
+    /// Whether the default judgment is ruled right away instead of offering it to the judge.
+    pub automatic: bool,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        Self {
+            deadline_minutes: Some(48 * 60),
+            automatic: false,
+        }
+    }
+}
+
+impl AppearanceConfig {
+    pub fn deadline(&self, now: DateTime) -> Option<DateTime> {
+        self.deadline_minutes
+            .map(|minutes| time::saturating_after(now, minutes, MILLIS_PER_MINUTE))
+    }
+}
+
+impl Lawsuit {
+    fn missed_appearance(&self, now: DateTime) -> bool {
+        !self.appeared
+            && !self.default_judgment_offered
+            && !self.is_closed()
+            && self
+                .appearance_deadline
+                .is_some_and(|deadline| deadline <= now)
+    }
+}
+
+fn default_judgment_buttons(
+    components: &mut CreateComponents,
+    lawsuit_id: Uuid,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(format!("{DEFAULT_JUDGMENT_PREFIX}:rule:{lawsuit_id}"))
+                .label("Versäumnisurteil fällen")
+                .style(ButtonStyle::Danger)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("{DEFAULT_JUDGMENT_PREFIX}:wait:{lawsuit_id}"))
+                .label("Weiter verhandeln")
+                .style(ButtonStyle::Secondary)
+        })
+    })
+}
+
+fn parse_default_judgment_id(custom_id: &str) -> Option<(bool, Uuid)> {
+    let mut parts = custom_id.split(':');
+    if parts.next()? != DEFAULT_JUDGMENT_PREFIX {
+        return None;
+    }
+    let rule = match parts.next()? {
+        "rule" => true,
+        "wait" => false,
+        _ => return None,
+    };
+    let lawsuit_id = Uuid::parse_str(parts.next()?).ok()?;
+    Some((rule, lawsuit_id))
+}
+
+impl LawsuitCtx {
+    /// Rules against the accused because they didn't take part in the lawsuit.
+    pub async fn rule_default_judgment(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        venue: &Venue,
+    ) -> Result<Result<Closed, Response>> {
+        let verdict = format!(
+            "Versäumnisurteil, <@{}> isch nöd erschiene",
+            self.lawsuit.accused
+        );
+        self.rule_verdict(permission_override, user_id, verdict, true, venue.clone())
+            .await
+    }
+
+    /// Offers the judge a default judgment, with `reason` explaining why.
+    async fn offer_default_judgment(&mut self, reason: &str, venue: &Venue) -> Result<()> {
+        let lawsuit = &self.lawsuit;
+        let lawsuit_id = lawsuit.id;
+
+        ChannelId::from(venue.channel_id())
+            .send_message(&self.http, |msg| {
+                msg.content(format!(
+                    "<@{}>, {reason}. Du chasch es versäumnisurteil fälle.",
+                    lawsuit.judge
+                ))
+                .components(|c| default_judgment_buttons(c, lawsuit_id))
+            })
+            .await
+            .wrap_err("offer default judgment")?;
+
+        self.log(None, CaseEvent::DefaultJudgmentOffered).await
+    }
+
+    /// Marks the default judgment as offered. Returns `false` if it already was, so it is only
+    /// offered once.
+    async fn claim_default_judgment(&mut self) -> Result<bool> {
+        let claimed = self
+            .mongo_client
+            .claim_default_judgment(self.guild_id.into(), self.lawsuit.id)
+            .await?;
+        if claimed {
+            self.lawsuit.default_judgment_offered = true;
+        }
+        Ok(claimed)
+    }
+
+    /// Handles a missed appearance, by offering or ruling the default judgment.
+    async fn handle_missed_appearance(&mut self, automatic: bool, venue: &Venue) -> Result<()> {
+        if !self.claim_default_judgment().await? {
+            return Ok(());
+        }
+
+        let reason = format!(
+            "de aagklagti <@{}> isch bis zur frist nöd erschiene",
+            self.lawsuit.accused
+        );
+
+        if !automatic {
+            return self.offer_default_judgment(&reason, venue).await;
+        }
+
+        self.log(None, CaseEvent::DefaultJudgmentOffered).await?;
+        let judge = self.lawsuit.judge.into();
+        if let Err(response) = self
+            .rule_default_judgment(true, judge, venue)
+            .await?
+            .and_then(Closed::into_result)
+        {
+            info!(%response, lawsuit_id = %self.lawsuit.id, "Could not rule default judgment");
+        }
+
+        Ok(())
+    }
+}
+
+/// Marks the accused as appeared when they post in the court room of their lawsuit.
+pub async fn record_appearance(
+    mongo: &Mongo,
+    http: &Arc<Http>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    user_id: UserId,
+) -> Result<()> {
+    let appeared = mongo
+        .mark_appeared(guild_id.into(), channel_id.into(), user_id.into())
+        .await?;
+    if !appeared {
+        return Ok(());
+    }
+
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    if let Some(lawsuit) = state.active_lawsuit(channel_id.into()) {
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit: lawsuit.clone(),
+            mongo_client: mongo.clone(),
+            http: http.clone(),
+            guild_id,
+        };
+        lawsuit_ctx
+            .log(Some(user_id.into()), CaseEvent::AccusedAppeared)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Tells the judges of the lawsuits against the member that they have left the guild.
+pub async fn handle_accused_left(
+    mongo: &Mongo,
+    http: &Arc<Http>,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<()> {
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    let user: SnowflakeId = user_id.into();
+
+    for lawsuit in state
+        .lawsuits
+        .iter()
+        .filter(|lawsuit| lawsuit.accused == user && !lawsuit.is_closed())
+    {
+        let venue = state.venue(lawsuit.court_room);
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit: lawsuit.clone(),
+            mongo_client: mongo.clone(),
+            http: http.clone(),
+            guild_id,
+        };
+
+        lawsuit_ctx.log(None, CaseEvent::AccusedLeft).await?;
+        if !lawsuit_ctx.claim_default_judgment().await? {
+            continue;
+        }
+
+        let reason = format!("de aagklagti <@{user}> hät de server verlah");
+        if let Err(err) = lawsuit_ctx.offer_default_judgment(&reason, &venue).await {
+            error!(?err, lawsuit_id = %lawsuit.id, "Failed to notify judge");
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the buttons offering a default judgment. Returns `false` if the interaction wasn't
+/// for them.
+pub async fn handle_default_judgment_interaction(
+    mongo: &Mongo,
+    http: &Arc<Http>,
+    interaction: &MessageComponentInteraction,
+) -> Result<bool> {
+    let (rule, lawsuit_id) = match parse_default_judgment_id(&interaction.data.custom_id) {
+        Some(parsed) => parsed,
+        None => return Ok(false),
+    };
+    let (guild_id, member) = match (interaction.guild_id, &interaction.member) {
+        (Some(guild_id), Some(member)) => (guild_id, member),
+        _ => return Ok(false),
+    };
+    let permission_override = member
+        .permissions
+        .is_some_and(|p| p.contains(Permissions::MANAGE_GUILD));
+
+    // ruling closes the whole lawsuit, which takes longer than discord waits
+    interaction
+        .create_interaction_response(http, |response| {
+            response
+                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|data| data.ephemeral(true))
+        })
+        .await
+        .wrap_err("defer default judgment interaction")?;
+
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    let lawsuit = state
+        .lawsuits
+        .iter()
+        .find(|lawsuit| lawsuit.id == lawsuit_id);
+
+    let authorized =
+        permission_override || lawsuit.is_none_or(|lawsuit| lawsuit.judge == member.user.id.into());
+
+    let response = match lawsuit {
+        Some(_) if !authorized => Err(Response("du häsch kei recht für da!".to_string())),
+        Some(_) if !rule => Ok("de prozess gaht wiiter".to_string()),
+        Some(lawsuit) => {
+            let venue = state.venue(lawsuit.court_room);
+            let mut lawsuit_ctx = LawsuitCtx {
+                lawsuit: lawsuit.clone(),
+                mongo_client: mongo.clone(),
+                http: http.clone(),
+                guild_id,
+            };
+            lawsuit_ctx
+                .rule_default_judgment(permission_override, member.user.id, &venue)
+                .await?
+                .and_then(Closed::into_result)
+                .map(|()| "s versäumnisurteil isch gfällt".to_string())
+        }
+        None => Err(Response("de prozess gits nüme".to_string())),
+    };
+
+    let content = match response {
+        Ok(content) => content,
+        Err(response) => response.to_string(),
+    };
+
+    interaction
+        .edit_original_interaction_response(http, |response| response.content(content))
+        .await
+        .wrap_err("respond to default judgment interaction")?;
+
+    // the judge has decided, the offer can't be answered again
+    if authorized {
+        if let Err(err) = interaction
+            .message
+            .clone()
+            .edit(http, |msg| msg.components(|c| c))
+            .await
+        {
+            error!(?err, %lawsuit_id, "Failed to remove default judgment buttons");
+        }
+    }
+
+    Ok(true)
+}
+
+/// Offers or rules default judgments in every lawsuit whose accused has missed the deadline.
+#[tracing::instrument(skip_all)]
+pub async fn handle_missed_appearances(mongo: &Mongo, http: &Arc<Http>) -> Result<()> {
+    let now = DateTime::now();
+
+    for state in mongo.find_states_with_missed_appearances(now).await? {
+        for lawsuit in &state.lawsuits {
+            if !lawsuit.missed_appearance(now) {
+                continue;
+            }
+
+            let venue = state.venue(lawsuit.court_room);
+            let mut lawsuit_ctx = LawsuitCtx {
+                lawsuit: lawsuit.clone(),
+                mongo_client: mongo.clone(),
+                http: http.clone(),
+                guild_id: state.guild_id.into(),
+            };
+
+            if let Err(err) = lawsuit_ctx
+                .handle_missed_appearance(state.appearance.automatic, &venue)
+                .await
+            {
+                error!(?err, lawsuit_id = %lawsuit.id, "Failed to handle missed appearance");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use std::sync::Once;
+
+use color_eyre::Result;
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use mongodb::bson::DateTime;
+use plotters::{
+    coord::Shift,
+    prelude::*,
+    style::{register_font, FontStyle},
+};
+use tracing::error;
+
+use crate::{
+    stats::{LawsuitStats, WeekCount},
+    time::MILLIS_PER_WEEK,
+    WrapErr,
+};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 400;
+
+/// Only the most recent weeks fit in the chart.
+const MAX_WEEKS: usize = 26;
+
+const FONT: &str = "sans-serif";
+/// The docker image has no fonts, so the charts bring their own.
+static FONT_BYTES: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+static REGISTER_FONT: Once = Once::new();
+
+const BAR_COLOR: RGBColor = RGBColor(88, 101, 242);
+
+type Area<'a> = DrawingArea<BitMapBackend<'a>, Shift>;
+
+/// Draws on a blank canvas and encodes the result as PNG.
+fn render(draw: impl FnOnce(&Area) -> Result<()>) -> Result<Vec<u8>> {
+    REGISTER_FONT.call_once(|| {
+        if register_font(FONT, FontStyle::Normal, FONT_BYTES).is_err() {
+            error!("Failed to load the chart font");
+        }
+    });
+
+    let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).wrap_err("fill chart background")?;
+        draw(&root)?;
+        root.present().wrap_err("draw chart")?;
+    }
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(&pixels, WIDTH, HEIGHT, ColorType::Rgb8)
+        .wrap_err("encode chart")?;
+    Ok(png)
+}
+
+/// Draws vertical bars with a label below each of them.
+fn bar_chart(area: &Area, caption: &str, bars: &[(String, u64)]) -> Result<()> {
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(caption, (FONT, 24))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(40)
+        .build_cartesian_2d(
+            (0..bars.len().saturating_sub(1)).into_segmented(),
+            0..max + max / 10 + 1,
+        )
+        .wrap_err("build bar chart")?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(bars.len().max(1))
+        .x_label_formatter(&|segment| {
+            match segment {
+                SegmentValue::CenterOf(i) => bars.get(*i).map(|(label, _)| label.clone()),
+                _ => None,
+            }
+            .unwrap_or_default()
+        })
+        .label_style((FONT, 14))
+        .draw()
+        .wrap_err("draw bar chart mesh")?;
+
+    chart
+        .draw_series(bars.iter().enumerate().map(|(i, (_, value))| {
+            let mut bar = Rectangle::new(
+                [
+                    (SegmentValue::Exact(i), 0),
+                    (SegmentValue::Exact(i + 1), *value),
+                ],
+                BAR_COLOR.filled(),
+            );
+            bar.set_margin(0, 0, 8, 8);
+            bar
+        }))
+        .wrap_err("draw bars")?;
+
+    Ok(())
+}
+
+/// Formats the start of a week like `17.10.`.
+fn week_label(week: DateTime) -> String {
+    match week.try_to_rfc3339_string() {
+        Ok(date) => format!("{}.{}.", &date[8..10], &date[5..7]),
+        Err(_) => String::new(),
+    }
+}
+
+/// The number of new lawsuits in every week, including the weeks without any.
+pub fn cases_per_week_chart(weeks: &[WeekCount]) -> Result<Vec<u8>> {
+    let mut counts = Vec::new();
+    if let (Some(first), Some(last)) = (weeks.first(), weeks.last()) {
+        let last = last.week.timestamp_millis();
+        let first = first
+            .week
+            .timestamp_millis()
+            .max(last - (MAX_WEEKS as i64 - 1) * MILLIS_PER_WEEK);
+
+        for millis in (first..=last).step_by(MILLIS_PER_WEEK as usize) {
+            let count = weeks
+                .iter()
+                .find(|week| week.week.timestamp_millis() == millis)
+                .map_or(0, |week| week.count);
+            counts.push((DateTime::from_millis(millis), count));
+        }
+    }
+
+    render(|area| {
+        let max = counts
+            .iter()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut chart = ChartBuilder::on(area)
+            .caption("Neue Prozesse pro Woche", (FONT, 24))
+            .margin(15)
+            .margin_right(30)
+            .x_label_area_size(40)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0..counts.len().max(2) - 1, 0..max + max / 10 + 1)
+            .wrap_err("build weekly chart")?;
+
+        chart
+            .configure_mesh()
+            .x_labels(counts.len().clamp(1, 10))
+            .x_label_formatter(&|i| {
+                counts
+                    .get(*i)
+                    .map(|(week, _)| week_label(*week))
+                    .unwrap_or_default()
+            })
+            .label_style((FONT, 14))
+            .draw()
+            .wrap_err("draw weekly chart mesh")?;
+
+        chart
+            .draw_series(LineSeries::new(
+                counts.iter().enumerate().map(|(i, (_, count))| (i, *count)),
+                BAR_COLOR.stroke_width(3),
+            ))
+            .wrap_err("draw weekly line")?;
+        chart
+            .draw_series(
+                counts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, count))| Circle::new((i, *count), 4, BAR_COLOR.filled())),
+            )
+            .wrap_err("draw weekly points")?;
+
+        Ok(())
+    })
+}
+
+/// How the lawsuits have ended, and how many are still going on.
+pub fn outcome_chart(stats: &LawsuitStats) -> Result<Vec<u8>> {
+    let bars = [
+        ("Verurteilt", stats.convictions),
+        ("Freigesprochen", stats.acquittals),
+        ("Zurückgezogen", stats.withdrawn),
+        ("Abgewiesen", stats.dismissed),
+        ("Vergleich", stats.settled),
+        ("Laufend", stats.active),
+    ]
+    .map(|(label, value)| (label.to_string(), value));
+
+    render(|area| bar_chart(area, "Ausgang der Prozesse", &bars))
+}
+
+/// A ranking of members, `entries` are the display names with their counts.
+pub fn leaderboard_chart(caption: &str, entries: &[(String, u64)]) -> Result<Vec<u8>> {
+    render(|area| bar_chart(area, caption, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::FIRST_MONDAY_MILLIS;
+
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn assert_png(bytes: &[u8]) {
+        assert!(bytes.starts_with(PNG_SIGNATURE));
+        let image = image::load_from_memory(bytes).unwrap();
+        assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT));
+    }
+
+    fn week(week: i64, count: u64) -> WeekCount {
+        WeekCount {
+            week: DateTime::from_millis(week * MILLIS_PER_WEEK + FIRST_MONDAY_MILLIS),
+            count,
+        }
+    }
+
+    #[test]
+    fn renders_cases_per_week() {
+        let weeks = [week(2800, 3), week(2801, 1), week(2804, 7)];
+        assert_png(&cases_per_week_chart(&weeks).unwrap());
+    }
+
+    #[test]
+    fn renders_cases_per_week_over_many_weeks() {
+        let weeks = (2700..2800)
+            .map(|i| week(i, (i % 5) as u64))
+            .collect::<Vec<_>>();
+        assert_png(&cases_per_week_chart(&weeks).unwrap());
+    }
+
+    #[test]
+    fn renders_empty_charts() {
+        assert_png(&cases_per_week_chart(&[]).unwrap());
+        assert_png(&outcome_chart(&LawsuitStats::default()).unwrap());
+        assert_png(&leaderboard_chart("Fleissigste Richter", &[]).unwrap());
+    }
+
+    #[test]
+    fn renders_outcomes() {
+        let stats = LawsuitStats {
+            total: 12,
+            active: 2,
+            convictions: 5,
+            acquittals: 2,
+            withdrawn: 1,
+            dismissed: 1,
+            settled: 1,
+            ..Default::default()
+        };
+        assert_png(&outcome_chart(&stats).unwrap());
+    }
+
+    #[test]
+    fn renders_leaderboard() {
+        let entries = [
+            ("Richterin Müller".to_string(), 12),
+            ("nils#0001".to_string(), 7),
+            ("1234567890".to_string(), 1),
+        ];
+        assert_png(&leaderboard_chart("Fleissigste Richter", &entries).unwrap());
+    }
+
+    #[test]
+    fn labels_weeks_by_their_start() {
+        // 2022-10-17, a monday
+        assert_eq!(
+            week_label(DateTime::from_millis(1_665_964_800_000)),
+            "17.10."
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::Result;
+use poise::{
+    serenity::model::{
+        interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+        prelude::*,
+    },
+    serenity_prelude::{AttachmentType, CollectComponentInteraction},
+    ReplyHandle,
+};
+
+use crate::{Context, WrapErr};
+
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a destructive command can be undone.
+const UNDO_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Waits for the invoking user to click one of the buttons of this command invocation. Gives up
+/// when the bot shuts down.
+async fn await_button(
+    ctx: Context<'_>,
+    prefix: String,
+    timeout: Duration,
+) -> Option<Arc<MessageComponentInteraction>> {
+    // ephemeral replies can only be edited through their handle, so collect by channel and id
+    let collector = CollectComponentInteraction::new(ctx.discord())
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .filter(move |interaction| interaction.data.custom_id.starts_with(&prefix))
+        .timeout(timeout);
+    ctx.data().health.until_shutdown(collector).await
+}
+
+/// Asks the invoking user to confirm a destructive command with buttons. Returns the prompt if
+/// they confirmed in time, so it can be edited with the result. Otherwise, the prompt is edited to
+/// tell them that nothing happened.
+pub async fn confirm<'a>(
+    ctx: Context<'a>,
+    prompt: String,
+    confirm_label: &str,
+    attachment: Option<AttachmentType<'a>>,
+) -> Result<Option<ReplyHandle<'a>>> {
+    let prefix = format!("confirm:{}:", ctx.id());
+    let confirm_id = format!("{prefix}yes");
+    let cancel_id = format!("{prefix}no");
+
+    let handle = ctx
+        .send(|reply| {
+            reply
+                .content(prompt)
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_button(|b| {
+                            b.custom_id(&confirm_id)
+                                .label(confirm_label)
+                                .style(ButtonStyle::Danger)
+                        })
+                        .create_button(|b| {
+                            b.custom_id(&cancel_id)
+                                .label("Abbrechen")
+                                .style(ButtonStyle::Secondary)
+                        })
+                    })
+                })
+                .ephemeral(true);
+            if let Some(attachment) = attachment {
+                reply.attachment(attachment);
+            }
+            reply
+        })
+        .await?;
+
+    let interaction = match await_button(ctx, prefix, CONFIRM_TIMEOUT).await {
+        Some(interaction) => interaction,
+        None => {
+            handle
+                .edit(ctx, |reply| {
+                    reply
+                        .content("kei bestätigung, es isch nüt passiert")
+                        .components(|c| c)
+                })
+                .await?;
+            return Ok(None);
+        }
+    };
+
+    let confirmed = interaction.data.custom_id == confirm_id;
+
+    interaction
+        .create_interaction_response(&ctx.discord().http, |response| {
+            response
+                .kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|data| {
+                    data.content(if confirmed {
+                        "wird erlediget…"
+                    } else {
+                        "abbroche, es isch nüt passiert"
+                    })
+                    .components(|c| c)
+                })
+        })
+        .await
+        .wrap_err("respond to confirm interaction")?;
+
+    Ok(confirmed.then_some(handle))
+}
+
+/// Shows `content` on the confirmed prompt with an undo button for the `UNDO_WINDOW`. Returns
+/// `true` if the user clicked it, the caller then edits the prompt with the result of the undo.
+pub async fn offer_undo(ctx: Context<'_>, handle: &ReplyHandle<'_>, content: &str) -> Result<bool> {
+    let prefix = format!("undo:{}", ctx.id());
+    let undo_id = prefix.clone();
+
+    handle
+        .edit(ctx, |reply| {
+            reply.content(content).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id(&undo_id)
+                            .label("Rückgängig")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = match await_button(ctx, prefix, UNDO_WINDOW).await {
+        Some(interaction) => interaction,
+        None => {
+            handle
+                .edit(ctx, |reply| reply.content(content).components(|c| c))
+                .await?;
+            return Ok(false);
+        }
+    };
+
+    // undoing might take longer than discord waits for a response
+    interaction
+        .create_interaction_response(&ctx.discord().http, |response| {
+            response.kind(InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await
+        .wrap_err("respond to undo interaction")?;
+
+    Ok(true)
+}
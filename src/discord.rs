@@ -0,0 +1,170 @@
+use std::{
+    fmt::{Display, Formatter},
+    future::Future,
+    time::Duration,
+};
+
+use color_eyre::{Report, Result};
+use poise::{
+    serenity::model::permissions::Permissions,
+    serenity_prelude::{self as serenity, HttpError},
+};
+use tracing::warn;
+
+use crate::{handler::Response, model::SnowflakeId};
+
+/// The channel doesn't exist (anymore).
+pub const UNKNOWN_CHANNEL: isize = 10003;
+/// The message doesn't exist (anymore).
+pub const UNKNOWN_MESSAGE: isize = 10008;
+/// The Discord error code for a member that isn't part of the guild (anymore).
+pub const UNKNOWN_MEMBER: isize = 10007;
+/// The bot can't see the channel or guild.
+pub const MISSING_ACCESS: isize = 50001;
+/// The bot lacks a permission or tried to manage a role above its own.
+pub const MISSING_PERMISSIONS: isize = 50013;
+
+const RETRY_ATTEMPTS: u32 = 3;
+
+pub fn discord_error_code(err: &serenity::Error) -> Option<isize> {
+    match err {
+        serenity::Error::Http(http_err) => match http_err.as_ref() {
+            HttpError::UnsuccessfulRequest(response) => Some(response.error.code),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn serenity_error(err: &Report) -> Option<&serenity::Error> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<serenity::Error>())
+}
+
+/// Client errors (apart from rate limits) won't go away by trying again.
+fn is_transient(err: &Report) -> bool {
+    match serenity_error(err) {
+        Some(serenity::Error::Http(http_err)) => match http_err.as_ref() {
+            HttpError::UnsuccessfulRequest(response) => {
+                let status = response.status_code;
+                !status.is_client_error() || status.as_u16() == 429
+            }
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+/// Retries a Discord operation a few times, unless it failed in a way that won't change.
+pub async fn retry<T, F, Fut>(mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < RETRY_ATTEMPTS && is_transient(&err) => {
+                warn!(?err, attempt, "Discord operation failed, retrying");
+                tokio::time::sleep(Duration::from_millis(500 * u64::from(attempt))).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Attached to the error of a failed setup if undoing the finished steps failed as well.
+#[derive(Debug, Clone, Copy)]
+pub struct IncompleteRollback;
+
+impl Display for IncompleteRollback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("rollback incomplete")
+    }
+}
+
+/// Marks the error of a failed setup with `IncompleteRollback` unless everything was undone.
+pub fn with_rollback(err: Report, rolled_back: bool) -> Report {
+    if rolled_back {
+        err
+    } else {
+        err.wrap_err(IncompleteRollback)
+    }
+}
+
+/// A step of opening a lawsuit. Attached to errors with `wrap_err` so the user can be told
+/// which step failed.
+#[derive(Debug, Clone, Copy)]
+pub enum SetupStep {
+    CreateRole,
+    CreateChannel,
+    CreateVoiceChannel,
+    SaveLawsuit,
+    CreateThread,
+    AssignRole(SnowflakeId),
+    AddToThread(SnowflakeId),
+    SetVisibility,
+    SendOpenMessage,
+}
+
+impl Display for SetupStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreateRole => f.write_str("Rolle für de Gerichtsruum erstelle"),
+            Self::CreateChannel => f.write_str("Gerichtsruum erstelle"),
+            Self::CreateVoiceChannel => f.write_str("Sprachkanal für de Gerichtsruum erstelle"),
+            Self::SaveLawsuit => f.write_str("Prozess speichere"),
+            Self::CreateThread => f.write_str("Thread für de Prozess erstelle"),
+            Self::AssignRole(user) => write!(f, "<@{user}> in Gerichtsruum ilah"),
+            Self::AddToThread(user) => write!(f, "<@{user}> in Thread ilah"),
+            Self::SetVisibility => f.write_str("Sichtbarkeit vom Gerichtsruum setze"),
+            Self::SendOpenMessage => f.write_str("Prozess im Gerichtsruum aakünde"),
+        }
+    }
+}
+
+impl SetupStep {
+    fn required_permission(&self) -> Option<Permissions> {
+        match self {
+            Self::CreateRole | Self::AssignRole(_) | Self::SetVisibility => {
+                Some(Permissions::MANAGE_ROLES)
+            }
+            Self::CreateChannel | Self::CreateVoiceChannel => Some(Permissions::MANAGE_CHANNELS),
+            Self::SendOpenMessage => Some(Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS),
+            Self::CreateThread => Some(Permissions::CREATE_PRIVATE_THREADS),
+            Self::AddToThread(_) => Some(Permissions::SEND_MESSAGES_IN_THREADS),
+            Self::SaveLawsuit => None,
+        }
+    }
+
+    /// Explains a failed setup to the user, if the error happened in a known step.
+    pub fn describe_failure(err: &Report) -> Option<Response> {
+        let step = *err.downcast_ref::<SetupStep>()?;
+
+        let reason = match serenity_error(err).and_then(discord_error_code) {
+            Some(MISSING_PERMISSIONS) => match (step, step.required_permission()) {
+                (Self::AssignRole(_), Some(permission)) => format!(
+                    "em bot fehlt d berechtigung `{permission:?}` oder d rolle vom gerichtsruum isch höcher als die vom bot"
+                ),
+                (_, Some(permission)) => format!("em bot fehlt d berechtigung `{permission:?}`"),
+                (_, None) => "em bot fehlt e berechtigung".to_string(),
+            },
+            Some(MISSING_ACCESS) => "de bot hät kein zuegriff uf de channel".to_string(),
+            Some(UNKNOWN_MEMBER) => "die person isch nüme uf em server".to_string(),
+            Some(code) => format!("discord hät en fehler gmeldet ({code})"),
+            None => "interne fehler".to_string(),
+        };
+
+        let rollback = if err.downcast_ref::<IncompleteRollback>().is_some() {
+            "i ha nöd alles chöne rückgängig mache, es chönd no reste vom prozess übrig si"
+        } else {
+            "i ha alles wieder rückgängig gmacht"
+        };
+
+        Some(Response(format!(
+            "de schritt «{step}» isch fehlgschlage: {reason}. {rollback}"
+        )))
+    }
+}
@@ -0,0 +1,196 @@
+use color_eyre::Result;
+use mongodb::bson::doc;
+use poise::{
+    serenity::model::prelude::*,
+    serenity_prelude::{CreateEmbed, Http},
+};
+use tracing::info;
+
+use crate::{
+    discord::{discord_error_code, UNKNOWN_MESSAGE},
+    doctor::MAX_DESCRIPTION_LENGTH,
+    gallery::Visibility,
+    lawsuit::{close_embed, open_embed, Lawsuit, LawsuitCtx},
+    model::{SnowflakeId, State},
+    Mongo, WrapErr,
+};
+
+const PRIVATE_TITLE: &str = "🔒 Nichtöffentlicher Prozess";
+
+impl Lawsuit {
+    /// Where the lawsuit stands, as shown on the docket.
+    pub fn docket_status(&self) -> &'static str {
+        if self.is_closed() {
+            "🔨 Abgeschlossen"
+        } else if self.log.is_empty() {
+            "📥 Eingereicht"
+        } else {
+            "⚖️ In Verhandlung"
+        }
+    }
+}
+
+/// The docket entry of a single lawsuit. Private lawsuits only show that they exist, the docket
+/// channel is readable by members who can't see the court room.
+pub fn docket_embed<'a>(embed: &'a mut CreateEmbed, lawsuit: &Lawsuit) -> &'a mut CreateEmbed {
+    if lawsuit.visibility == Visibility::Private {
+        return embed
+            .title(PRIVATE_TITLE)
+            .field("Status", lawsuit.docket_status(), true);
+    }
+    if lawsuit.is_closed() {
+        return close_embed(embed, lawsuit);
+    }
+
+    open_embed(embed, lawsuit)
+        .field("Status", lawsuit.docket_status(), true)
+        .field("Gerichtsraum", format!("<#{}>", lawsuit.court_room), true)
+}
+
+/// The pinned overview of all active lawsuits.
+fn overview_embed<'a>(embed: &'a mut CreateEmbed, state: &State) -> &'a mut CreateEmbed {
+    embed.title("Laufende Prozesse");
+
+    let active = state
+        .lawsuits
+        .iter()
+        .filter(|lawsuit| !lawsuit.is_closed())
+        .collect::<Vec<_>>();
+
+    if active.is_empty() {
+        return embed.description("Keine laufenden Prozesse");
+    }
+
+    let mut overview = String::new();
+    for (i, lawsuit) in active.iter().enumerate() {
+        let entry = if lawsuit.visibility == Visibility::Private {
+            format!("{} **{PRIVATE_TITLE}**\n\n", lawsuit.docket_status())
+        } else {
+            format!(
+                "{} **{}**\n<@{}> gegen <@{}> in <#{}>\n\n",
+                lawsuit.docket_status(),
+                lawsuit.reason,
+                lawsuit.plaintiff,
+                lawsuit.accused,
+                lawsuit.court_room
+            )
+        };
+        if overview.len() + entry.len() > MAX_DESCRIPTION_LENGTH {
+            overview.push_str(&format!("… und {} wiiteri", active.len() - i));
+            break;
+        }
+        overview.push_str(&entry);
+    }
+
+    embed.description(overview)
+}
+
+impl LawsuitCtx {
+    /// Posts or updates the docket entry of the lawsuit and the overview of all active lawsuits.
+    /// Does nothing if the guild has no docket channel.
+    pub async fn update_docket(&mut self) -> Result<()> {
+        let state = self
+            .mongo_client
+            .find_or_insert_state(self.guild_id.into())
+            .await?;
+        let channel_id = match state.docket_channel {
+            Some(channel_id) => ChannelId::from(channel_id),
+            None => return Ok(()),
+        };
+
+        let mut embed = CreateEmbed::default();
+        docket_embed(&mut embed, &self.lawsuit);
+
+        let (message_id, posted) =
+            edit_or_post(&self.http, channel_id, self.lawsuit.docket_message, embed)
+                .await
+                .wrap_err("update docket entry")?;
+
+        if posted {
+            self.mongo_client
+                .set_lawsuit(
+                    self.guild_id.into(),
+                    self.lawsuit.id,
+                    doc! { "lawsuits.$.docket_message": SnowflakeId::from(message_id) },
+                )
+                .await?;
+            self.lawsuit.docket_message = Some(message_id.into());
+        }
+
+        update_overview(&self.mongo_client, &self.http, &state).await
+    }
+}
+
+/// Updates the pinned overview of the active lawsuits in the docket channel, posting and pinning
+/// a new one if it doesn't exist.
+pub async fn update_overview(mongo: &Mongo, http: &Http, state: &State) -> Result<()> {
+    let channel_id = match state.docket_channel {
+        Some(channel_id) => ChannelId::from(channel_id),
+        None => return Ok(()),
+    };
+
+    let mut embed = CreateEmbed::default();
+    overview_embed(&mut embed, state);
+
+    let (message_id, posted) = edit_or_post(http, channel_id, state.docket_overview, embed)
+        .await
+        .wrap_err("update docket overview")?;
+
+    if posted {
+        // another update may have posted an overview at the same time, only one of them is kept
+        let claimed = mongo
+            .claim_docket_overview(
+                state.guild_id,
+                channel_id.into(),
+                state.docket_overview,
+                message_id.into(),
+            )
+            .await?;
+        if !claimed {
+            info!(guild_id = %state.guild_id, %message_id, "Docket overview was posted concurrently");
+            channel_id
+                .delete_message(http, message_id)
+                .await
+                .wrap_err("delete duplicate docket overview")?;
+            return Ok(());
+        }
+
+        channel_id
+            .pin(http, message_id)
+            .await
+            .wrap_err("pin docket overview")?;
+        info!(guild_id = %state.guild_id, %message_id, "Posted docket overview");
+    }
+
+    Ok(())
+}
+
+/// Edits the message, or posts a new one if there is none yet or it was deleted. Returns the id
+/// of the message and whether it is new.
+async fn edit_or_post(
+    http: &Http,
+    channel_id: ChannelId,
+    message_id: Option<SnowflakeId>,
+    embed: CreateEmbed,
+) -> Result<(MessageId, bool)> {
+    if let Some(message_id) = message_id {
+        let result = channel_id
+            .edit_message(http, MessageId::from(message_id), |msg| {
+                msg.set_embed(embed.clone())
+            })
+            .await;
+        match result {
+            Ok(message) => return Ok((message.id, false)),
+            // posted in another docket channel or deleted by someone
+            Err(err) if discord_error_code(&err) == Some(UNKNOWN_MESSAGE) => {}
+            Err(err) => return Err(err).wrap_err("edit docket message"),
+        }
+    }
+
+    let message = channel_id
+        .send_message(http, |msg| msg.set_embed(embed))
+        .await
+        .wrap_err("post docket message")?;
+
+    Ok((message.id, true))
+}
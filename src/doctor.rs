@@ -0,0 +1,477 @@
+use color_eyre::Result;
+use poise::{
+    serenity::model::prelude::*,
+    serenity_prelude::{CreateEmbed, Http},
+};
+
+use crate::{
+    model::{SnowflakeId, State},
+    venue::CourtMode,
+    WrapErr,
+};
+
+/// What the bot needs on the whole server.
+const GUILD_PERMISSIONS: &[Permissions] = &[
+    Permissions::MANAGE_ROLES,
+    Permissions::MANAGE_CHANNELS,
+    Permissions::VIEW_CHANNEL,
+    Permissions::SEND_MESSAGES,
+    Permissions::EMBED_LINKS,
+];
+
+/// What the bot needs in the court category to create and manage the court rooms.
+const CATEGORY_PERMISSIONS: &[Permissions] = &[
+    Permissions::VIEW_CHANNEL,
+    Permissions::MANAGE_CHANNELS,
+    Permissions::MANAGE_ROLES,
+    Permissions::SEND_MESSAGES,
+    Permissions::EMBED_LINKS,
+];
+
+/// What the bot needs in the court channel to hold lawsuits in private threads.
+const THREAD_CHANNEL_PERMISSIONS: &[Permissions] = &[
+    Permissions::VIEW_CHANNEL,
+    Permissions::CREATE_PRIVATE_THREADS,
+    Permissions::SEND_MESSAGES_IN_THREADS,
+    Permissions::MANAGE_THREADS,
+    Permissions::EMBED_LINKS,
+];
+
+/// What the bot needs in every court room.
+const ROOM_PERMISSIONS: &[Permissions] = &[
+    Permissions::VIEW_CHANNEL,
+    Permissions::SEND_MESSAGES,
+    Permissions::EMBED_LINKS,
+];
+
+/// What the bot needs in the voice channel of a court room to give orders.
+const VOICE_PERMISSIONS: &[Permissions] = &[Permissions::VIEW_CHANNEL, Permissions::MANAGE_ROLES];
+
+/// What the bot needs in the docket channel to post, edit and pin the announcements.
+const DOCKET_PERMISSIONS: &[Permissions] = &[
+    Permissions::VIEW_CHANNEL,
+    Permissions::SEND_MESSAGES,
+    Permissions::EMBED_LINKS,
+    Permissions::READ_MESSAGE_HISTORY,
+    Permissions::MANAGE_MESSAGES,
+];
+
+/// Discord rejects longer embed descriptions, leave some room for the rest of the text.
+pub(crate) const MAX_DESCRIPTION_LENGTH: usize = 4000;
+
+/// Something that will make commands fail, together with how to fix it.
+#[derive(Debug)]
+pub struct Problem {
+    pub problem: String,
+    pub fix: String,
+}
+
+impl Problem {
+    fn new(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+/// Checks the permissions of the bot, the role hierarchy and the channel overwrites that the
+/// court and the prison rely on.
+#[tracing::instrument(skip(http, state))]
+pub async fn diagnose(http: &Http, guild_id: GuildId, state: &State) -> Result<Vec<Problem>> {
+    let guild = guild_id
+        .to_partial_guild(http)
+        .await
+        .wrap_err("fetch partial guild")?;
+    let bot_id = http
+        .get_current_user()
+        .await
+        .wrap_err("fetch current user")?
+        .id;
+    let bot = guild
+        .member(http, bot_id)
+        .await
+        .wrap_err("fetch bot member")?;
+    let channels = guild.channels(http).await.wrap_err("fetch channels")?;
+
+    let mut problems = Vec::new();
+
+    let permissions = guild_permissions(&guild, &bot);
+    for &permission in GUILD_PERMISSIONS {
+        if !permissions.contains(permission) {
+            problems.push(Problem::new(
+                format!("Em bot fehlt d berechtigung `{permission:?}` uf em server"),
+                format!(
+                    "Gib de rolle vom bot i de server-istellige d berechtigung `{permission:?}`"
+                ),
+            ));
+        }
+    }
+
+    match &state.court_mode {
+        CourtMode::Rooms => match state.court_category {
+            None => problems.push(Problem::new(
+                "Es isch kei kategorie für d gerichtsrüüm gsetzt",
+                "Setz eini mit `/lawsuit set_category`",
+            )),
+            Some(category_id) => match channels.get(&category_id.into()) {
+                None => problems.push(Problem::new(
+                    format!("D kategorie für d gerichtsrüüm ({category_id}) gits nüme"),
+                    "Setz e neui mit `/lawsuit set_category`",
+                )),
+                Some(category) => {
+                    let permissions = guild
+                        .user_permissions_in(category, &bot)
+                        .wrap_err("compute category permissions")?;
+                    for &permission in CATEGORY_PERMISSIONS {
+                        if !permissions.contains(permission) {
+                            problems.push(Problem::new(
+                                format!(
+                                    "Em bot fehlt d berechtigung `{permission:?}` i de kategorie <#{}>",
+                                    category.id
+                                ),
+                                format!(
+                                    "Erlaub em bot `{permission:?}` i de berechtigunge vo de kategorie"
+                                ),
+                            ));
+                        }
+                    }
+                }
+            },
+        },
+        CourtMode::Threads { channel_id } => match channels.get(&(*channel_id).into()) {
+            None => problems.push(Problem::new(
+                format!("De kanal für d prozess-threads ({channel_id}) gits nüme"),
+                "Setz en neue mit `/lawsuit set_thread_channel`",
+            )),
+            Some(channel) => {
+                let permissions = guild
+                    .user_permissions_in(channel, &bot)
+                    .wrap_err("compute thread channel permissions")?;
+                for &permission in THREAD_CHANNEL_PERMISSIONS {
+                    if !permissions.contains(permission) {
+                        problems.push(Problem::new(
+                            format!(
+                                "Em bot fehlt d berechtigung `{permission:?}` im <#{}>",
+                                channel.id
+                            ),
+                            format!(
+                                "Erlaub em bot `{permission:?}` i de berechtigunge vom channel"
+                            ),
+                        ));
+                    }
+                }
+            }
+        },
+    }
+
+    if let Some(channel_id) = state.docket_channel {
+        match channels.get(&channel_id.into()) {
+            None => problems.push(Problem::new(
+                format!("De kanal für d prozess-aakündigunge ({channel_id}) gits nüme"),
+                "Setz en neue mit `/lawsuit set_docket`",
+            )),
+            Some(channel) => {
+                let permissions = guild
+                    .user_permissions_in(channel, &bot)
+                    .wrap_err("compute docket channel permissions")?;
+                for &permission in DOCKET_PERMISSIONS {
+                    if !permissions.contains(permission) {
+                        problems.push(Problem::new(
+                            format!(
+                                "Em bot fehlt d berechtigung `{permission:?}` im <#{}>",
+                                channel.id
+                            ),
+                            format!(
+                                "Erlaub em bot `{permission:?}` i de berechtigunge vom channel"
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    let bot_position = highest_role_position(&guild, &bot);
+    let mut check_role = |role_id: SnowflakeId, what: &str| match guild.roles.get(&role_id.into()) {
+        None => problems.push(Problem::new(
+            format!("D {what} ({role_id}) gits nüme"),
+            format!("Setz d {what} neu"),
+        )),
+        Some(role) if role.managed => problems.push(Problem::new(
+            format!("D {what} <@&{role_id}> wird vo re integration verwaltet"),
+            "Nimm e normali rolle",
+        )),
+        Some(role) if guild.owner_id != bot.user.id && role.position >= bot_position => problems
+            .push(Problem::new(
+                format!("D {what} <@&{role_id}> isch nöd under de höchste rolle vom bot"),
+                format!("Zieh d rolle vom bot i de server-istellige über <@&{role_id}>"),
+            )),
+        Some(_) => {}
+    };
+
+    if let Some(role_id) = state.prison_role {
+        check_role(role_id, "gfängnis-rolle");
+    }
+    if let Some(role_id) = state.lawyer_role {
+        check_role(role_id, "aawalts-rolle");
+    }
+    if let Some(role_id) = state.spectator_role {
+        check_role(role_id, "zueschauer-rolle");
+    }
+    for room in &state.court_rooms {
+        check_role(room.role_id, "rolle vom gerichtsruum");
+    }
+
+    for room in &state.court_rooms {
+        let channel = match channels.get(&room.channel_id.into()) {
+            Some(channel) => channel,
+            None => {
+                problems.push(Problem::new(
+                    format!("De gerichtsruum {} gits nüme", room.channel_id),
+                    "Lösch d gerichtsrüüm mit `/lawsuit clear` und lass sie neu erstelle",
+                ));
+                continue;
+            }
+        };
+
+        let permissions = guild
+            .user_permissions_in(channel, &bot)
+            .wrap_err("compute court room permissions")?;
+        for &permission in ROOM_PERMISSIONS {
+            if !permissions.contains(permission) {
+                problems.push(Problem::new(
+                    format!(
+                        "Em bot fehlt d berechtigung `{permission:?}` im <#{}>",
+                        channel.id
+                    ),
+                    format!("Erlaub em bot `{permission:?}` i de berechtigunge vom channel"),
+                ));
+            }
+        }
+
+        let role_may_write = channel.permission_overwrites.iter().any(|overwrite| {
+            overwrite.kind == PermissionOverwriteType::Role(room.role_id.into())
+                && overwrite.allow.contains(Permissions::SEND_MESSAGES)
+        });
+        if !role_may_write {
+            problems.push(Problem::new(
+                format!(
+                    "D rolle <@&{}> dörf im <#{}> nöd schriibe",
+                    room.role_id, channel.id
+                ),
+                format!(
+                    "Erlaub de rolle <@&{}> `SEND_MESSAGES` i de berechtigunge vom channel",
+                    room.role_id
+                ),
+            ));
+        }
+    }
+
+    for room in &state.court_rooms {
+        let voice_channel_id = match room.voice_channel_id {
+            Some(id) => id,
+            None => continue,
+        };
+        let channel = match channels.get(&voice_channel_id.into()) {
+            Some(channel) => channel,
+            None => {
+                problems.push(Problem::new(
+                    format!("De sprachkanal {voice_channel_id} vom gerichtsruum gits nüme"),
+                    "Lösch d gerichtsrüüm mit `/lawsuit clear` und lass sie neu erstelle",
+                ));
+                continue;
+            }
+        };
+
+        let permissions = guild
+            .user_permissions_in(channel, &bot)
+            .wrap_err("compute voice channel permissions")?;
+        for &permission in VOICE_PERMISSIONS {
+            if !permissions.contains(permission) {
+                problems.push(Problem::new(
+                    format!(
+                        "Em bot fehlt d berechtigung `{permission:?}` im <#{}>",
+                        channel.id
+                    ),
+                    format!("Erlaub em bot `{permission:?}` i de berechtigunge vom channel"),
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// The permissions of a member on the server, ignoring channel overwrites.
+fn guild_permissions(guild: &PartialGuild, member: &Member) -> Permissions {
+    if guild.owner_id == member.user.id {
+        return Permissions::all();
+    }
+
+    let everyone = guild
+        .roles
+        .get(&RoleId(guild.id.0))
+        .map(|role| role.permissions)
+        .unwrap_or_else(Permissions::empty);
+
+    let permissions = member
+        .roles
+        .iter()
+        .filter_map(|role_id| guild.roles.get(role_id))
+        .fold(everyone, |permissions, role| permissions | role.permissions);
+
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        Permissions::all()
+    } else {
+        permissions
+    }
+}
+
+fn highest_role_position(guild: &PartialGuild, member: &Member) -> i64 {
+    member
+        .roles
+        .iter()
+        .filter_map(|role_id| guild.roles.get(role_id))
+        .map(|role| role.position)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Builds the checklist shown by `/court doctor`.
+pub fn checklist_embed<'a>(
+    embed: &'a mut CreateEmbed,
+    problems: &[Problem],
+) -> &'a mut CreateEmbed {
+    embed.title("Gerichts-Diagnose");
+
+    if problems.is_empty() {
+        return embed.description("✅ Alles i ordnig");
+    }
+
+    let mut checklist = String::new();
+    for (i, problem) in problems.iter().enumerate() {
+        let entry = format!("❌ {}\n➡️ {}\n\n", problem.problem, problem.fix);
+        if checklist.len() + entry.len() > MAX_DESCRIPTION_LENGTH {
+            checklist.push_str(&format!("… und {} wiiteri", problems.len() - i));
+            break;
+        }
+        checklist.push_str(&entry);
+    }
+
+    embed.description(checklist)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const GUILD_ID: u64 = 100;
+    const OWNER_ID: u64 = 1;
+    const MODERATOR_ROLE: u64 = 200;
+    const ADMIN_ROLE: u64 = 300;
+
+    fn role(id: u64, permissions: Permissions, position: i64) -> serde_json::Value {
+        json!({
+            "id": id.to_string(),
+            "guild_id": GUILD_ID.to_string(),
+            "color": 0,
+            "hoist": false,
+            "managed": false,
+            "name": format!("role {id}"),
+            "permissions": permissions.bits().to_string(),
+            "position": position,
+        })
+    }
+
+    fn guild() -> PartialGuild {
+        serde_json::from_value(json!({
+            "id": GUILD_ID.to_string(),
+            "afk_timeout": 300,
+            "default_message_notifications": 0,
+            "emojis": [],
+            "stickers": [],
+            "features": [],
+            "mfa_level": 0,
+            "name": "Gericht",
+            "owner_id": OWNER_ID.to_string(),
+            "roles": [
+                role(GUILD_ID, Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES, 0),
+                role(MODERATOR_ROLE, Permissions::MANAGE_ROLES, 5),
+                role(ADMIN_ROLE, Permissions::ADMINISTRATOR, 2),
+            ],
+            "verification_level": 0,
+            "premium_subscription_count": 0,
+            "nsfw_level": 0,
+            "system_channel_flags": 0,
+        }))
+        .expect("deserialize guild")
+    }
+
+    fn member(user_id: u64, roles: &[u64]) -> Member {
+        serde_json::from_value(json!({
+            "deaf": false,
+            "guild_id": GUILD_ID.to_string(),
+            "joined_at": null,
+            "mute": false,
+            "nick": null,
+            "roles": roles.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "user": {
+                "id": user_id.to_string(),
+                "username": "angeklagter",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "premium_since": null,
+            "permissions": null,
+            "avatar": null,
+            "communication_disabled_until": null,
+        }))
+        .expect("deserialize member")
+    }
+
+    #[test]
+    fn member_without_roles_has_everyone_permissions() {
+        assert_eq!(
+            guild_permissions(&guild(), &member(2, &[])),
+            Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES
+        );
+    }
+
+    #[test]
+    fn role_permissions_add_up() {
+        assert_eq!(
+            guild_permissions(&guild(), &member(2, &[MODERATOR_ROLE])),
+            Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::MANAGE_ROLES
+        );
+    }
+
+    #[test]
+    fn administrator_and_owner_have_all_permissions() {
+        assert_eq!(
+            guild_permissions(&guild(), &member(2, &[ADMIN_ROLE])),
+            Permissions::all()
+        );
+        assert_eq!(
+            guild_permissions(&guild(), &member(OWNER_ID, &[])),
+            Permissions::all()
+        );
+    }
+
+    #[test]
+    fn highest_role_position_ignores_unknown_roles() {
+        let guild = guild();
+
+        assert_eq!(highest_role_position(&guild, &member(2, &[])), 0);
+        assert_eq!(
+            highest_role_position(&guild, &member(2, &[ADMIN_ROLE, MODERATOR_ROLE])),
+            5
+        );
+        assert_eq!(
+            highest_role_position(&guild, &member(2, &[ADMIN_ROLE, 999])),
+            2
+        );
+    }
+}
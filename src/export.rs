@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use color_eyre::Result;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    handler::Response,
+    lawsuit::{Lawsuit, LawsuitOutcome},
+    model::{PrisonEntry, SnowflakeId, State, UnlockedAchievement},
+    Mongo, WrapErr,
+};
+
+/// Bumped whenever an export can't be imported by an older version anymore.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Discord doesn't allow bigger uploads without boosts anyway.
+pub const MAX_IMPORT_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Everything the bot stores about a guild.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildExport {
+    pub version: u32,
+    pub exported_at: DateTime,
+    pub state: State,
+    pub prison: Vec<PrisonEntry>,
+    pub achievements: Vec<UnlockedAchievement>,
+}
+
+impl GuildExport {
+    pub async fn load(mongo: &Mongo, guild_id: SnowflakeId) -> Result<Self> {
+        Ok(Self {
+            version: EXPORT_VERSION,
+            exported_at: DateTime::now(),
+            state: mongo.find_or_insert_state(guild_id).await?,
+            prison: mongo.find_guild_prison_entries(guild_id).await?,
+            achievements: mongo.find_guild_achievements(guild_id).await?,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).wrap_err("serialize export")
+    }
+
+    /// Reads an export and checks that it can be imported into the guild.
+    pub fn parse(bytes: &[u8], guild_id: SnowflakeId) -> Result<Self, Response> {
+        let export = serde_json::from_slice::<Self>(bytes)
+            .map_err(|err| Response(format!("das isch kein gültige export: {err}")))?;
+        export.validate(guild_id)?;
+        Ok(export)
+    }
+
+    fn validate(&self, guild_id: SnowflakeId) -> Result<(), Response> {
+        if self.version != EXPORT_VERSION {
+            return Err(Response(format!(
+                "de export hät version {}, ich chan nur version {EXPORT_VERSION} importiere",
+                self.version
+            )));
+        }
+        if self.state.guild_id != guild_id {
+            return Err(Response("de export isch vomene andere server".to_string()));
+        }
+        if self.prison.iter().any(|entry| entry.guild_id != guild_id)
+            || self
+                .achievements
+                .iter()
+                .any(|achievement| achievement.guild_id != guild_id)
+        {
+            return Err(Response(
+                "de export enthaltet date vomene andere server".to_string(),
+            ));
+        }
+
+        let mut ids = HashSet::new();
+        if let Some(lawsuit) = self.state.lawsuits.iter().find(|l| !ids.insert(l.id)) {
+            return Err(Response(format!(
+                "de prozess {} chunnt mehrmals vor",
+                lawsuit.id
+            )));
+        }
+
+        let mut rooms = HashSet::new();
+        if let Some(lawsuit) = self
+            .state
+            .lawsuits
+            .iter()
+            .filter(|lawsuit| !lawsuit.is_closed())
+            .find(|lawsuit| !rooms.insert(lawsuit.court_room))
+        {
+            return Err(Response(format!(
+                "im gerichtsraum <#{}> laufed mehreri prozess",
+                lawsuit.court_room
+            )));
+        }
+
+        let mut prisoners = HashSet::new();
+        if let Some(entry) = self
+            .prison
+            .iter()
+            .filter(|entry| entry.released_at.is_none())
+            .find(|entry| !prisoners.insert(entry.user_id))
+        {
+            return Err(Response(format!(
+                "<@{}> isch mehrmals im gfängnis",
+                entry.user_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// What the export contains, compared to what the guild has now.
+    pub fn summary(&self, current: &GuildExport) -> String {
+        let active = |state: &State| {
+            state
+                .lawsuits
+                .iter()
+                .filter(|lawsuit| !lawsuit.is_closed())
+                .count()
+        };
+        let imprisoned = |prison: &[PrisonEntry]| {
+            prison
+                .iter()
+                .filter(|entry| entry.released_at.is_none())
+                .count()
+        };
+
+        format!(
+            "Export vom <t:{}:f>\n\
+             Prozesse: {} ({} laufend), bisher {} ({} laufend)\n\
+             Gerichtsräume: {}, bisher {}\n\
+             Anwälte: {}, bisher {}\n\
+             Gefängnis-Einträge: {} ({} im Gefängnis), bisher {} ({} im Gefängnis)\n\
+             Errungenschaften: {}, bisher {}",
+            self.exported_at.timestamp_millis() / 1000,
+            self.state.lawsuits.len(),
+            active(&self.state),
+            current.state.lawsuits.len(),
+            active(&current.state),
+            self.state.court_rooms.len(),
+            current.state.court_rooms.len(),
+            self.state.lawyers.len(),
+            current.state.lawyers.len(),
+            self.prison.len(),
+            imprisoned(&self.prison),
+            current.prison.len(),
+            imprisoned(&current.prison),
+            self.achievements.len(),
+            current.achievements.len(),
+        )
+    }
+
+    pub fn json_filename(&self) -> String {
+        format!("gericht-{}.json", self.state.guild_id)
+    }
+
+    pub fn csv_filename(&self) -> String {
+        format!("prozesse-{}.csv", self.state.guild_id)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_date(date: Option<DateTime>) -> String {
+    date.and_then(|date| date.try_to_rfc3339_string().ok())
+        .unwrap_or_default()
+}
+
+fn csv_id(id: Option<SnowflakeId>) -> String {
+    id.map(|id| id.to_string()).unwrap_or_default()
+}
+
+/// The lawsuits as CSV, one row per lawsuit.
+pub fn lawsuits_csv(lawsuits: &[Lawsuit]) -> String {
+    let mut csv = String::from(
+        "id,plaintiff,accused,plaintiff_lawyer,accused_lawyer,judge,reason,plea,verdict,guilty,outcome,opened_at,closed_at\n",
+    );
+
+    for lawsuit in lawsuits {
+        let outcome = match &lawsuit.outcome {
+            Some(LawsuitOutcome::Verdict) => "verdict".to_string(),
+            Some(LawsuitOutcome::Withdrawn) => "withdrawn".to_string(),
+            Some(LawsuitOutcome::Dismissed { reason }) => format!("dismissed: {reason}"),
+            Some(LawsuitOutcome::Settled) => "settled".to_string(),
+            None => String::new(),
+        };
+
+        let row = [
+            lawsuit.id.to_string(),
+            lawsuit.plaintiff.to_string(),
+            lawsuit.accused.to_string(),
+            csv_id(lawsuit.plaintiff_lawyer),
+            csv_id(lawsuit.accused_lawyer),
+            lawsuit.judge.to_string(),
+            lawsuit.reason.clone(),
+            lawsuit
+                .plea
+                .map(|plea| plea.to_string())
+                .unwrap_or_default(),
+            lawsuit.verdict.clone().unwrap_or_default(),
+            lawsuit
+                .guilty
+                .map(|guilty| guilty.to_string())
+                .unwrap_or_default(),
+            outcome,
+            csv_date(lawsuit.opened_at),
+            csv_date(lawsuit.closed_at),
+        ];
+
+        csv.push_str(
+            &row.iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        csv.push('\n');
+    }
+
+    csv
+}
@@ -0,0 +1,224 @@
+use color_eyre::{eyre::ContextCompat, Result};
+use mongodb::{bson, bson::doc};
+use poise::{
+    serenity::model::{
+        interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+        prelude::*,
+    },
+    serenity_prelude::{CreateComponents, Http},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    handler::Response,
+    lawsuit::LawsuitCtx,
+    model::{CourtRoom, SnowflakeId},
+    procedure::CaseEvent,
+    venue::Venue,
+    Mongo, WrapErr,
+};
+
+const WATCH_BUTTON_ID: &str = "court_watch";
+
+/// What spectators may do in a public court room.
+const SPECTATOR_ALLOW: Permissions =
+    Permissions::VIEW_CHANNEL.union(Permissions::READ_MESSAGE_HISTORY);
+
+/// Who besides the participants can follow a lawsuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// Only the participants can see the court room.
+    #[default]
+    Private,
+    /// Everyone can read along, or only the spectators if the guild has a spectator role.
+    Public,
+}
+
+/// The overwrites of a court room for the participants and the audience of a lawsuit.
+pub fn room_overwrites(
+    guild_id: GuildId,
+    court_role: SnowflakeId,
+    visibility: Visibility,
+    spectator_role: Option<SnowflakeId>,
+) -> Vec<PermissionOverwrite> {
+    let everyone = PermissionOverwriteType::Role(RoleId(guild_id.0));
+    let hidden = PermissionOverwrite {
+        allow: Permissions::empty(),
+        deny: Permissions::VIEW_CHANNEL,
+        kind: everyone,
+    };
+
+    let mut overwrites = vec![PermissionOverwrite {
+        allow: Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+        deny: Permissions::empty(),
+        kind: PermissionOverwriteType::Role(court_role.into()),
+    }];
+
+    match (visibility, spectator_role) {
+        (Visibility::Private, _) => overwrites.push(hidden),
+        (Visibility::Public, None) => overwrites.push(PermissionOverwrite {
+            allow: SPECTATOR_ALLOW,
+            deny: Permissions::SEND_MESSAGES,
+            kind: everyone,
+        }),
+        (Visibility::Public, Some(spectator_role)) => {
+            overwrites.push(hidden);
+            overwrites.push(PermissionOverwrite {
+                allow: SPECTATOR_ALLOW,
+                deny: Permissions::SEND_MESSAGES,
+                kind: PermissionOverwriteType::Role(spectator_role.into()),
+            });
+        }
+    }
+
+    overwrites
+}
+
+/// Adds the button to join or leave the spectators.
+pub fn watch_button(components: &mut CreateComponents) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(WATCH_BUTTON_ID)
+                .label("Zueluege")
+                .style(ButtonStyle::Secondary)
+        })
+    })
+}
+
+impl LawsuitCtx {
+    /// Sets the overwrites of the court room according to the visibility of the lawsuit.
+    pub async fn apply_visibility(&self, room: &CourtRoom, visibility: Visibility) -> Result<()> {
+        let spectator_role = self
+            .mongo_client
+            .find_or_insert_state(self.guild_id.into())
+            .await?
+            .spectator_role;
+
+        let channel_id = ChannelId::from(room.channel_id);
+        for overwrite in room_overwrites(self.guild_id, room.role_id, visibility, spectator_role) {
+            channel_id
+                .create_permission(&self.http, &overwrite)
+                .await
+                .wrap_err("set court room overwrite")?;
+        }
+
+        if let (Visibility::Private, Some(spectator_role)) = (visibility, spectator_role) {
+            let kind = PermissionOverwriteType::Role(spectator_role.into());
+            let channel = channel_id
+                .to_channel(&self.http)
+                .await
+                .wrap_err("fetch court room")?
+                .guild()
+                .wrap_err("court room is not in a guild")?;
+
+            if channel
+                .permission_overwrites
+                .iter()
+                .any(|overwrite| overwrite.kind == kind)
+            {
+                channel_id
+                    .delete_permission(&self.http, kind)
+                    .await
+                    .wrap_err("remove spectator overwrite")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Opens the court room to the audience or hides it again.
+    pub async fn set_visibility(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        visibility: Visibility,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        if self.lawsuit.judge != user_id.into() && !permission_override {
+            return Ok(Err(Response("du häsch kei recht für da!".to_string())));
+        }
+        let room = match venue {
+            Venue::Room(room) => room,
+            Venue::Thread(_) => {
+                return Ok(Err(Response(
+                    "prozess i threads sind immer privat".to_string(),
+                )))
+            }
+        };
+
+        self.mongo_client
+            .set_lawsuit(
+                self.guild_id.into(),
+                self.lawsuit.id,
+                doc! { "lawsuits.$.visibility": bson::to_bson(&visibility).wrap_err("invalid bson for visibility")? },
+            )
+            .await?;
+        self.lawsuit.visibility = visibility;
+
+        self.apply_visibility(room, visibility).await?;
+
+        // private lawsuits are hidden on the docket
+        if let Err(err) = self.update_docket().await {
+            error!(?err, "Failed to update docket");
+        }
+
+        self.log(
+            Some(user_id.into()),
+            CaseEvent::VisibilityChanged { visibility },
+        )
+        .await?;
+
+        Ok(Ok(()))
+    }
+}
+
+/// Handles a click on the watch button, which gives or takes the spectator role. Returns `false`
+/// if the interaction wasn't for the watch button.
+pub async fn handle_watch_interaction(
+    mongo: &Mongo,
+    http: &Http,
+    interaction: &MessageComponentInteraction,
+) -> Result<bool> {
+    if interaction.data.custom_id != WATCH_BUTTON_ID {
+        return Ok(false);
+    }
+    let guild_id = interaction
+        .guild_id
+        .wrap_err("watch button outside of guild")?;
+
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+
+    let content = match (state.spectator_role, &interaction.member) {
+        (Some(role_id), Some(member)) => {
+            let mut member = member.clone();
+            if member.roles.contains(&role_id.into()) {
+                member
+                    .remove_role(http, role_id)
+                    .await
+                    .wrap_err("remove spectator role")?;
+                "du luegsch nüme zue"
+            } else {
+                member
+                    .add_role(http, role_id)
+                    .await
+                    .wrap_err("add spectator role")?;
+                info!(%guild_id, user_id = %member.user.id, "New spectator");
+                "du chasch jetzt bi allne öffentliche prozess zueluege"
+            }
+        }
+        _ => "da server hät kei zueschauer-rolle",
+    };
+
+    interaction
+        .create_interaction_response(http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|data| data.content(content).ephemeral(true))
+        })
+        .await
+        .wrap_err("respond to watch interaction")?;
+
+    Ok(true)
+}
@@ -1,20 +1,45 @@
-use std::fmt::{Debug, Display, Formatter};
+use std::{
+    fmt::{Debug, Display, Formatter},
+    sync::Arc,
+};
 
 use color_eyre::{eyre::ContextCompat, Result};
-use mongodb::bson::Uuid;
-use poise::{serenity::model::prelude::*, serenity_prelude as serenity, Event};
+use mongodb::bson::{DateTime, Uuid};
+use poise::{
+    serenity::model::{
+        interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+        prelude::*,
+    },
+    serenity_prelude as serenity, Event,
+};
 use tracing::{debug, error, info};
 
 use crate::{
-    lawsuit::{Lawsuit, LawsuitCtx},
-    model::SnowflakeId,
-    Context, Mongo, Report, WrapErr,
+    achievements::{load_progress, process_event, CourtEvent, ACHIEVEMENTS},
+    appearance,
+    confirm::{confirm, offer_undo},
+    docket,
+    doctor::{checklist_embed, diagnose},
+    export::{lawsuits_csv, GuildExport, MAX_IMPORT_BYTES},
+    gallery::{self, Visibility},
+    health::Health,
+    lawsuit::{Closed, Lawsuit, LawsuitCtx, LawsuitEdit},
+    lawyer::{BAR_EXAM, PASSING_SCORE},
+    model::{SnowflakeId, State},
+    plea,
+    record::{is_eligible, CriminalRecord},
+    retention,
+    room::RoomAllocator,
+    venue::{CourtMode, Venue},
+    witness, Context, Mongo, Report, WrapErr,
 };
 
 pub struct Handler {
     pub dev_guild_id: Option<GuildId>,
     pub set_global_commands: bool,
     pub mongo: Mongo,
+    pub room_allocator: RoomAllocator,
+    pub health: Arc<Health>,
 }
 
 impl Debug for Handler {
@@ -23,6 +48,7 @@ impl Debug for Handler {
     }
 }
 
+#[derive(Debug)]
 pub struct Response(pub String);
 
 impl Display for Response {
@@ -31,6 +57,30 @@ impl Display for Response {
     }
 }
 
+/// Confirms a change of the setup, together with the problems the checks of `/court doctor` find
+/// afterwards, if any.
+async fn confirm_setup(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+    let state = ctx
+        .data()
+        .mongo
+        .find_or_insert_state(guild_id.into())
+        .await?;
+
+    let problems = diagnose(&ctx.discord().http, guild_id, &state).await?;
+    ctx.send(|reply| {
+        reply.content("isch gsetzt");
+        if !problems.is_empty() {
+            reply.embed(|embed| checklist_embed(embed, &problems));
+        }
+        reply
+    })
+    .await
+    .wrap_err("reply")?;
+
+    Ok(())
+}
+
 impl Handler {
     async fn handle_guild_member_join(
         &self,
@@ -65,20 +115,83 @@ impl Handler {
 }
 
 pub mod lawsuit {
+    use std::time::Duration;
+
     use color_eyre::eyre::eyre;
+    use futures::StreamExt;
 
     use super::*;
 
+    const HIRE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+    const SETTLEMENT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+    /// Returns the user invoking the command and whether they may act in place of the judge.
+    pub(super) fn invoking_member(ctx: Context<'_>) -> Result<(UserId, bool)> {
+        let application_context = match ctx {
+            Context::Application(ctx) => ctx,
+            Context::Prefix(_) => return Err(eyre!("wrong context, cannot happen!")),
+        };
+
+        let member = application_context
+            .interaction
+            .member()
+            .wrap_err("member not found")?;
+
+        let permission_override = member
+            .permissions
+            .map(|p| p.contains(Permissions::MANAGE_GUILD))
+            .unwrap_or(false);
+
+        Ok((member.user.id, permission_override))
+    }
+
+    /// Finds the ongoing lawsuit in the channel of the command, telling the user if there is none.
+    pub(super) async fn find_active_case(
+        ctx: Context<'_>,
+        state: &State,
+    ) -> Result<Option<(Lawsuit, Venue)>> {
+        let channel_id = ctx.channel_id().into();
+
+        match state.active_lawsuit(channel_id) {
+            Some(lawsuit) => Ok(Some((lawsuit.clone(), state.venue(channel_id)))),
+            None => {
+                ctx.say("i dem channel lauft kein aktive prozess!").await?;
+                Ok(None)
+            }
+        }
+    }
+
     #[poise::command(
         slash_command,
         guild_only,
-        subcommands("create", "set_category", "close", "clear")
+        subcommands(
+            "create",
+            "set_category",
+            "set_reputation",
+            "set_voice",
+            "set_thread_channel",
+            "set_spectator_role",
+            "set_docket",
+            "set_plea",
+            "set_appearance",
+            "set_retention",
+            "edit",
+            "visibility",
+            "close",
+            "withdraw",
+            "dismiss",
+            "settle",
+            "hire",
+            "witness",
+            "clear"
+        )
     )]
     pub async fn lawsuit(_: Context<'_>) -> Result<()> {
         unreachable!()
     }
 
     /// Einen neuen Gerichtsprozess erstellen
+    #[allow(clippy::too_many_arguments)]
     #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
     async fn create(
         ctx: Context<'_>,
@@ -88,6 +201,7 @@ pub mod lawsuit {
         #[description = "Der Grund für die Klage"] reason: String,
         #[description = "Der Anwalt des Klägers"] plaintiff_lawyer: Option<User>,
         #[description = "Der Anwalt des Angeklagten"] accused_lawyer: Option<User>,
+        #[description = "Dürfen andere Mitglieder zuschauen?"] public: Option<bool>,
     ) -> Result<()> {
         lawsuit_create_impl(
             ctx,
@@ -97,6 +211,7 @@ pub mod lawsuit {
             reason,
             plaintiff_lawyer,
             accused_lawyer,
+            public.unwrap_or(false),
         )
         .await
         .wrap_err("lawsuit_create")
@@ -113,20 +228,335 @@ pub mod lawsuit {
             .wrap_err("lawsuit_set_category")
     }
 
+    /// Die Berechnung des Rufs einstellen
+    #[allow(clippy::too_many_arguments)]
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_reputation(
+        ctx: Context<'_>,
+        #[description = "Der Ruf ohne Vorstrafen"] base: Option<f64>,
+        #[description = "Abzug pro Verurteilung"] conviction_weight: Option<f64>,
+        #[description = "Bonus pro Freispruch"] acquittal_weight: Option<f64>,
+        #[description = "Abzug pro Stunde im Gefängnis"] prison_hour_weight: Option<f64>,
+        #[description = "Nach wie vielen Tagen ein Eintrag nur noch halb zählt"]
+        half_life_days: Option<f64>,
+        #[description = "Mindestruf für Richter und Anwälte"] min_reputation: Option<f64>,
+        #[description = "Den Mindestruf aufheben"] remove_min_reputation: Option<bool>,
+    ) -> Result<()> {
+        lawsuit_set_reputation_impl(
+            ctx,
+            base,
+            conviction_weight,
+            acquittal_weight,
+            prison_hour_weight,
+            half_life_days,
+            min_reputation,
+            remove_min_reputation.unwrap_or(false),
+        )
+        .await
+        .wrap_err("lawsuit_set_reputation")
+    }
+
+    /// Gerichtsräume mit einem Sprachkanal erstellen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_voice(
+        ctx: Context<'_>,
+        #[description = "Sollen Gerichtsräume einen Sprachkanal bekommen?"] enabled: bool,
+    ) -> Result<()> {
+        lawsuit_set_voice_impl(ctx, enabled)
+            .await
+            .wrap_err("lawsuit_set_voice")
+    }
+
+    /// Prozesse als private Threads in einem Kanal statt in Gerichtsräumen führen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_thread_channel(
+        ctx: Context<'_>,
+        #[description = "Der Kanal für die Threads, ohne Kanal werden Gerichtsräume verwendet"]
+        channel: Option<Channel>,
+    ) -> Result<()> {
+        lawsuit_set_thread_channel_impl(ctx, channel)
+            .await
+            .wrap_err("lawsuit_set_thread_channel")
+    }
+
+    /// Die Rolle für Zuschauer öffentlicher Prozesse setzen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_spectator_role(
+        ctx: Context<'_>,
+        #[description = "Die Rolle, ohne Rolle dürfen alle zuschauen"] role: Option<Role>,
+    ) -> Result<()> {
+        lawsuit_set_spectator_role_impl(ctx, role)
+            .await
+            .wrap_err("lawsuit_set_spectator_role")
+    }
+
+    /// Den Kanal setzen, in dem alle Prozesse angekündigt werden
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_docket(
+        ctx: Context<'_>,
+        #[description = "Der Kanal, ohne Kanal werden Prozesse nicht angekündigt"] channel: Option<
+            Channel,
+        >,
+    ) -> Result<()> {
+        lawsuit_set_docket_impl(ctx, channel)
+            .await
+            .wrap_err("lawsuit_set_docket")
+    }
+
+    /// Die Frist und die Strafe bei einem Geständnis einstellen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_plea(
+        ctx: Context<'_>,
+        #[description = "Wie viele Minuten der Angeklagte Zeit hat, sonst gilt nicht schuldig"]
+        timeout_minutes: Option<u64>,
+        #[description = "Stunden Gefängnis bei einem Geständnis ohne Verhandlung"]
+        guilty_sentence_hours: Option<f64>,
+        #[description = "Nach einem Geständnis wieder normal verhandeln"]
+        remove_guilty_sentence: Option<bool>,
+    ) -> Result<()> {
+        lawsuit_set_plea_impl(
+            ctx,
+            timeout_minutes,
+            guilty_sentence_hours,
+            remove_guilty_sentence.unwrap_or(false),
+        )
+        .await
+        .wrap_err("lawsuit_set_plea")
+    }
+
+    /// Die Frist zum Erscheinen des Angeklagten einstellen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_appearance(
+        ctx: Context<'_>,
+        #[description = "Wie viele Minuten der Angeklagte Zeit hat"] deadline_minutes: Option<u64>,
+        #[description = "Die Frist aufheben, es gibt keine Versäumnisurteile mehr"]
+        remove_deadline: Option<bool>,
+        #[description = "Soll das Versäumnisurteil ohne Richter gefällt werden?"] automatic: Option<
+            bool,
+        >,
+    ) -> Result<()> {
+        lawsuit_set_appearance_impl(
+            ctx,
+            deadline_minutes,
+            remove_deadline.unwrap_or(false),
+            automatic,
+        )
+        .await
+        .wrap_err("lawsuit_set_appearance")
+    }
+
+    /// Einstellen, wie lange abgeschlossene Prozesse aufbewahrt werden
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_retention(
+        ctx: Context<'_>,
+        #[description = "Nach wie vielen Tagen Prozesse gelöscht werden"] closed_case_days: Option<
+            u64,
+        >,
+        #[description = "Abgeschlossene Prozesse nie löschen"] keep_closed_cases: Option<bool>,
+        #[description = "Nach wie vielen Tagen Protokolle gelöscht werden"] transcript_days: Option<
+            u64,
+        >,
+        #[description = "Protokolle nie löschen"] keep_transcripts: Option<bool>,
+    ) -> Result<()> {
+        lawsuit_set_retention_impl(
+            ctx,
+            closed_case_days,
+            keep_closed_cases.unwrap_or(false),
+            transcript_days,
+            keep_transcripts.unwrap_or(false),
+        )
+        .await
+        .wrap_err("lawsuit_set_retention")
+    }
+
+    /// Festlegen, ob beim laufenden Prozess zugeschaut werden darf
+    #[poise::command(slash_command, guild_only)]
+    async fn visibility(
+        ctx: Context<'_>,
+        #[description = "Dürfen andere Mitglieder zuschauen?"] public: bool,
+    ) -> Result<()> {
+        lawsuit_visibility_impl(ctx, public)
+            .await
+            .wrap_err("lawsuit_visibility")
+    }
+
+    /// Den laufenden Gerichtsprozess bearbeiten
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn edit(
+        ctx: Context<'_>,
+        #[description = "Der neue Richter"] judge: Option<User>,
+        #[description = "Der neue Anwalt des Klägers"] plaintiff_lawyer: Option<User>,
+        #[description = "Der neue Anwalt des Angeklagten"] accused_lawyer: Option<User>,
+        #[description = "Der neue Grund für die Klage"] reason: Option<String>,
+        #[description = "Den Anwalt des Klägers entfernen"] remove_plaintiff_lawyer: Option<bool>,
+        #[description = "Den Anwalt des Angeklagten entfernen"] remove_accused_lawyer: Option<bool>,
+    ) -> Result<()> {
+        lawsuit_edit_impl(
+            ctx,
+            judge,
+            plaintiff_lawyer,
+            accused_lawyer,
+            reason,
+            remove_plaintiff_lawyer.unwrap_or(false),
+            remove_accused_lawyer.unwrap_or(false),
+        )
+        .await
+        .wrap_err("lawsuit_edit")
+    }
+
     /// Den Gerichtsprozess abschliessen und ein Urteil fällen
     #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
-    async fn close(ctx: Context<'_>, #[description = "Das Urteil"] verdict: String) -> Result<()> {
-        lawsuit_close_impl(ctx, verdict)
+    async fn close(
+        ctx: Context<'_>,
+        #[description = "Das Urteil"] verdict: String,
+        #[description = "Ist der Angeklagte schuldig?"] guilty: bool,
+    ) -> Result<()> {
+        lawsuit_close_impl(ctx, verdict, guilty)
             .await
             .wrap_err("lawsuit_close")
     }
 
+    /// Die Klage zurückziehen
+    #[poise::command(slash_command, guild_only)]
+    async fn withdraw(ctx: Context<'_>) -> Result<()> {
+        lawsuit_withdraw_impl(ctx)
+            .await
+            .wrap_err("lawsuit_withdraw")
+    }
+
+    /// Die Klage ohne Urteil abweisen
+    #[poise::command(slash_command, guild_only)]
+    async fn dismiss(
+        ctx: Context<'_>,
+        #[description = "Die Begründung"] reason: String,
+    ) -> Result<()> {
+        lawsuit_dismiss_impl(ctx, reason)
+            .await
+            .wrap_err("lawsuit_dismiss")
+    }
+
+    /// Einen Vergleich vorschlagen, dem beide Parteien zustimmen müssen
+    #[poise::command(slash_command, guild_only)]
+    async fn settle(ctx: Context<'_>) -> Result<()> {
+        lawsuit_settle_impl(ctx).await.wrap_err("lawsuit_settle")
+    }
+
+    /// Einen Anwalt für den laufenden Prozess anfragen
+    #[poise::command(slash_command, guild_only)]
+    async fn hire(ctx: Context<'_>, #[description = "Der Anwalt"] lawyer: User) -> Result<()> {
+        lawsuit_hire_impl(ctx, lawyer)
+            .await
+            .wrap_err("lawsuit_hire")
+    }
+
+    #[poise::command(slash_command, guild_only, subcommands("add", "dismiss_witness"))]
+    async fn witness(_: Context<'_>) -> Result<()> {
+        unreachable!()
+    }
+
+    /// Einen Zeugen zum laufenden Prozess vorladen
+    #[poise::command(slash_command, guild_only)]
+    async fn add(ctx: Context<'_>, #[description = "Der Zeuge"] user: User) -> Result<()> {
+        lawsuit_witness_add_impl(ctx, user)
+            .await
+            .wrap_err("lawsuit_witness_add")
+    }
+
+    /// Einen Zeugen aus dem laufenden Prozess entlassen
+    #[poise::command(slash_command, guild_only, rename = "dismiss")]
+    async fn dismiss_witness(
+        ctx: Context<'_>,
+        #[description = "Der Zeuge"] user: User,
+    ) -> Result<()> {
+        lawsuit_witness_dismiss_impl(ctx, user)
+            .await
+            .wrap_err("lawsuit_witness_dismiss")
+    }
+
     /// Alle Rechtsprozessdaten löschen
     #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
     async fn clear(ctx: Context<'_>) -> Result<()> {
         lawsuit_clear_impl(ctx).await.wrap_err("lawsuit_clear")
     }
 
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_witness_add_impl(ctx: Context<'_>, user: User) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, _) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let response = lawsuit_ctx
+            .summon_witness(permission_override, user_id, &user)
+            .await?;
+
+        match response {
+            Ok(()) => {
+                ctx.say(format!(
+                    "<@{}> isch als zeuge vorglade und hät e vorladig per DM becho",
+                    user.id
+                ))
+                .await?;
+            }
+            Err(response) => {
+                ctx.say(response.to_string()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_witness_dismiss_impl(ctx: Context<'_>, user: User) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let response = lawsuit_ctx
+            .dismiss_witness(permission_override, user_id, user.id.into(), &venue)
+            .await?;
+
+        match response {
+            Ok(()) => {
+                ctx.say(format!("<@{}> isch als zeuge entlah", user.id))
+                    .await?;
+            }
+            Err(response) => {
+                ctx.say(response.to_string()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument(skip(ctx))]
     async fn lawsuit_create_impl(
         ctx: Context<'_>,
@@ -136,8 +566,29 @@ pub mod lawsuit {
         reason: String,
         plaintiff_lawyer: Option<User>,
         accused_lawyer: Option<User>,
+        public: bool,
     ) -> Result<()> {
         let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        // setting up the court room takes a few discord calls
+        ctx.defer().await?;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let officials = std::iter::once(&judge)
+            .chain(plaintiff_lawyer.as_ref())
+            .chain(accused_lawyer.as_ref());
+        for official in officials {
+            if !is_eligible(mongo_client, &state, official.id.into()).await? {
+                ctx.say(format!(
+                    "<@{}> hät en z schlechte ruef zum als richter oder anwalt dihocke",
+                    official.id
+                ))
+                .await?;
+                return Ok(());
+            }
+        }
 
         let lawsuit = Lawsuit {
             id: Uuid::new(),
@@ -148,22 +599,55 @@ pub mod lawsuit {
             accused_lawyer: accused_lawyer.map(|user| user.id.into()),
             reason: reason.to_owned(),
             verdict: None,
+            guilty: None,
             court_room: SnowflakeId(0),
+            opened_at: Some(DateTime::now()),
+            closed_at: None,
+            outcome: None,
+            floor: None,
+            contempts: Vec::new(),
+            log: Vec::new(),
+            witnesses: Vec::new(),
+            docket_message: None,
+            plea: None,
+            plea_deadline: None,
+            appeared: false,
+            appearance_deadline: None,
+            default_judgment_offered: false,
+            deleted_at: None,
+            visibility: if public {
+                Visibility::Public
+            } else {
+                Visibility::Private
+            },
         };
 
         let lawsuit_ctx = LawsuitCtx {
             lawsuit,
-            mongo_client: ctx.data().mongo.clone(),
+            mongo_client: mongo_client.clone(),
             http: ctx.discord().http.clone(),
             guild_id,
         };
 
         let response = lawsuit_ctx
-            .initialize()
+            .initialize(&ctx.data().room_allocator)
             .await
             .wrap_err("initialize lawsuit")?;
 
-        ctx.say(response.to_string()).await?;
+        match response {
+            // the court room is hidden from people who aren't spectators yet
+            Ok(response) if public && state.spectator_role.is_some() => {
+                ctx.send(|reply| {
+                    reply
+                        .content(response.to_string())
+                        .components(gallery::watch_button)
+                })
+                .await?;
+            }
+            Ok(response) | Err(response) => {
+                ctx.say(response.to_string()).await?;
+            }
+        }
 
         Ok(())
     }
@@ -179,7 +663,7 @@ pub mod lawsuit {
                     .mongo
                     .set_court_category(guild_id.into(), id.into())
                     .await?;
-                ctx.say("isch gsetzt").await?;
+                confirm_setup(ctx).await?;
             }
             None => {
                 ctx.say("Das ist keine Kategorie!").await?;
@@ -189,56 +673,1610 @@ pub mod lawsuit {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument(skip(ctx))]
-    async fn lawsuit_close_impl(ctx: Context<'_>, verdict: String) -> Result<()> {
+    async fn lawsuit_set_reputation_impl(
+        ctx: Context<'_>,
+        base: Option<f64>,
+        conviction_weight: Option<f64>,
+        acquittal_weight: Option<f64>,
+        prison_hour_weight: Option<f64>,
+        half_life_days: Option<f64>,
+        min_reputation: Option<f64>,
+        remove_min_reputation: bool,
+    ) -> Result<()> {
+        if min_reputation.is_some() && remove_min_reputation {
+            ctx.say("du chasch de mindestruef nöd glichzitig setze und ufhebe")
+                .await?;
+            return Ok(());
+        }
+
         let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
 
-        let application_context = match ctx {
-            Context::Application(ctx) => ctx,
-            Context::Prefix(_) => return Err(eyre!("wrong context, cannot happen!")),
-        };
+        let mut config = mongo_client
+            .find_or_insert_state(guild_id.into())
+            .await?
+            .reputation;
 
-        let member = application_context
-            .interaction
-            .member()
-            .wrap_err("member not found")?;
+        if let Some(base) = base {
+            config.base = base;
+        }
+        if let Some(conviction_weight) = conviction_weight {
+            config.conviction_weight = conviction_weight;
+        }
+        if let Some(acquittal_weight) = acquittal_weight {
+            config.acquittal_weight = acquittal_weight;
+        }
+        if let Some(prison_hour_weight) = prison_hour_weight {
+            config.prison_hour_weight = prison_hour_weight;
+        }
+        if let Some(half_life_days) = half_life_days {
+            config.half_life_days = half_life_days;
+        }
+        if min_reputation.is_some() || remove_min_reputation {
+            config.min_reputation = min_reputation;
+        }
 
-        let permission_override = member
-            .permissions
-            .map(|p| p.contains(Permissions::MANAGE_GUILD))
-            .unwrap_or(false);
+        mongo_client
+            .set_reputation_config(guild_id.into(), &config)
+            .await?;
 
-        let room_id = ctx.channel_id();
-        let mongo_client = &ctx.data().mongo;
+        ctx.say("isch gsetzt").await?;
 
-        let state = mongo_client
-            .find_or_insert_state(guild_id.into())
-            .await
-            .wrap_err("find guild for verdict")?;
+        Ok(())
+    }
 
-        let lawsuit = state
-            .lawsuits
-            .iter()
-            .find(|l| l.court_room == room_id.into() && l.verdict.is_none());
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_voice_impl(ctx: Context<'_>, enabled: bool) -> Result<()> {
+        ctx.data()
+            .mongo
+            .set_voice_rooms(
+                ctx.guild_id().wrap_err("guild_id not found")?.into(),
+                enabled,
+            )
+            .await?;
 
-        let lawsuit = match lawsuit {
-            Some(lawsuit) => lawsuit.clone(),
-            None => {
-                ctx.say("i dem channel lauft kein aktive prozess!").await?;
-                return Ok(());
-            }
-        };
+        ctx.say("isch gsetzt").await.wrap_err("reply")?;
 
-        let room = state
-            .court_rooms
-            .iter()
-            .find(|r| r.channel_id == room_id.into());
-        let room = match room {
-            Some(room) => room.clone(),
-            None => {
-                ctx.say("i dem channel lauft kein aktive prozess!").await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_thread_channel_impl(
+        ctx: Context<'_>,
+        channel: Option<Channel>,
+    ) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+
+        let court_mode = match channel {
+            Some(Channel::Guild(channel)) if channel.kind == ChannelType::Text => {
+                CourtMode::Threads {
+                    channel_id: channel.id.into(),
+                }
+            }
+            Some(_) => {
+                ctx.say("Das ist kein Textkanal!").await?;
                 return Ok(());
             }
+            None => CourtMode::Rooms,
+        };
+
+        ctx.data()
+            .mongo
+            .set_court_mode(guild_id.into(), &court_mode)
+            .await?;
+
+        confirm_setup(ctx).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_spectator_role_impl(ctx: Context<'_>, role: Option<Role>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+
+        ctx.data()
+            .mongo
+            .set_spectator_role(guild_id.into(), role.map(|role| role.id.into()))
+            .await?;
+
+        confirm_setup(ctx).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_docket_impl(ctx: Context<'_>, channel: Option<Channel>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let channel_id = match channel {
+            Some(Channel::Guild(channel)) if channel.kind == ChannelType::Text => {
+                Some(channel.id.into())
+            }
+            Some(_) => {
+                ctx.say("Das ist kein Textkanal!").await?;
+                return Ok(());
+            }
+            None => None,
+        };
+
+        mongo_client
+            .set_docket_channel(guild_id.into(), channel_id)
+            .await?;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+        docket::update_overview(mongo_client, &ctx.discord().http, &state).await?;
+
+        confirm_setup(ctx).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_plea_impl(
+        ctx: Context<'_>,
+        timeout_minutes: Option<u64>,
+        guilty_sentence_hours: Option<f64>,
+        remove_guilty_sentence: bool,
+    ) -> Result<()> {
+        if guilty_sentence_hours.is_some() && remove_guilty_sentence {
+            ctx.say("du chasch d strof nöd glichzitig setze und ufhebe")
+                .await?;
+            return Ok(());
+        }
+        if timeout_minutes.is_some_and(|minutes| minutes > plea::MAX_TIMEOUT_MINUTES) {
+            ctx.say(format!(
+                "d frist cha höchstens {} minute si",
+                plea::MAX_TIMEOUT_MINUTES
+            ))
+            .await?;
+            return Ok(());
+        }
+        if guilty_sentence_hours
+            .is_some_and(|hours| !(0.0..=plea::MAX_GUILTY_SENTENCE_HOURS).contains(&hours))
+        {
+            ctx.say(format!(
+                "d strof mues zwüsche 0 und {} stunde si",
+                plea::MAX_GUILTY_SENTENCE_HOURS
+            ))
+            .await?;
+            return Ok(());
+        }
+
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let mut config = mongo_client
+            .find_or_insert_state(guild_id.into())
+            .await?
+            .plea;
+
+        if let Some(timeout_minutes) = timeout_minutes {
+            config.timeout_minutes = timeout_minutes;
+        }
+        if guilty_sentence_hours.is_some() || remove_guilty_sentence {
+            config.guilty_sentence_hours = guilty_sentence_hours;
+        }
+
+        mongo_client
+            .set_plea_config(guild_id.into(), &config)
+            .await?;
+
+        ctx.say("isch gsetzt").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_appearance_impl(
+        ctx: Context<'_>,
+        deadline_minutes: Option<u64>,
+        remove_deadline: bool,
+        automatic: Option<bool>,
+    ) -> Result<()> {
+        if deadline_minutes.is_some() && remove_deadline {
+            ctx.say("du chasch d frist nöd glichzitig setze und ufhebe")
+                .await?;
+            return Ok(());
+        }
+        if deadline_minutes
+            .is_some_and(|minutes| !(1..=appearance::MAX_DEADLINE_MINUTES).contains(&minutes))
+        {
+            ctx.say(format!(
+                "d frist mues zwüsche 1 und {} minute si",
+                appearance::MAX_DEADLINE_MINUTES
+            ))
+            .await?;
+            return Ok(());
+        }
+
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let mut config = mongo_client
+            .find_or_insert_state(guild_id.into())
+            .await?
+            .appearance;
+
+        if deadline_minutes.is_some() || remove_deadline {
+            config.deadline_minutes = deadline_minutes;
+        }
+        if let Some(automatic) = automatic {
+            config.automatic = automatic;
+        }
+
+        mongo_client
+            .set_appearance_config(guild_id.into(), &config)
+            .await?;
+
+        ctx.say("isch gsetzt").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_set_retention_impl(
+        ctx: Context<'_>,
+        closed_case_days: Option<u64>,
+        keep_closed_cases: bool,
+        transcript_days: Option<u64>,
+        keep_transcripts: bool,
+    ) -> Result<()> {
+        if (closed_case_days.is_some() && keep_closed_cases)
+            || (transcript_days.is_some() && keep_transcripts)
+        {
+            ctx.say("du chasch e dauer nöd glichzitig setze und ufhebe")
+                .await?;
+            return Ok(());
+        }
+        let in_range = |days: u64| (1..=retention::MAX_RETENTION_DAYS).contains(&days);
+        if !closed_case_days.is_none_or(in_range) || !transcript_days.is_none_or(in_range) {
+            ctx.say(format!(
+                "d dauer mues zwüsche 1 und {} täg si",
+                retention::MAX_RETENTION_DAYS
+            ))
+            .await?;
+            return Ok(());
+        }
+
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let mut config = mongo_client
+            .find_or_insert_state(guild_id.into())
+            .await?
+            .retention;
+
+        if closed_case_days.is_some() || keep_closed_cases {
+            config.closed_case_days = closed_case_days;
+        }
+        if transcript_days.is_some() || keep_transcripts {
+            config.transcript_days = transcript_days;
+        }
+
+        mongo_client
+            .set_retention_config(guild_id.into(), &config)
+            .await?;
+
+        ctx.say("isch gsetzt").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_visibility_impl(ctx: Context<'_>, public: bool) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let visibility = if public {
+            Visibility::Public
+        } else {
+            Visibility::Private
+        };
+        let response = lawsuit_ctx
+            .set_visibility(permission_override, user_id, visibility, &venue)
+            .await?;
+
+        match response {
+            Ok(()) if public && state.spectator_role.is_some() => {
+                ctx.send(|reply| {
+                    reply
+                        .content("de prozess isch jetzt öffentlich")
+                        .components(gallery::watch_button)
+                })
+                .await?;
+            }
+            Ok(()) if public => {
+                ctx.say("de prozess isch jetzt öffentlich").await?;
+            }
+            Ok(()) => {
+                ctx.say("de prozess isch jetzt privat").await?;
+            }
+            Err(response) => {
+                ctx.say(response.to_string()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_close_impl(ctx: Context<'_>, verdict: String, guilty: bool) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+
+        let (user_id, permission_override) = invoking_member(ctx)?;
+
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client
+            .find_or_insert_state(guild_id.into())
+            .await
+            .wrap_err("find guild for verdict")?;
+
+        let (lawsuit, venue) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let response = lawsuit_ctx
+            .rule_verdict(
+                permission_override,
+                user_id,
+                verdict.to_string(),
+                guilty,
+                venue,
+            )
+            .await?
+            .and_then(Closed::into_result);
+
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say("ich han en dir abschlosse").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_edit_impl(
+        ctx: Context<'_>,
+        judge: Option<User>,
+        plaintiff_lawyer: Option<User>,
+        accused_lawyer: Option<User>,
+        reason: Option<String>,
+        remove_plaintiff_lawyer: bool,
+        remove_accused_lawyer: bool,
+    ) -> Result<()> {
+        if (plaintiff_lawyer.is_some() && remove_plaintiff_lawyer)
+            || (accused_lawyer.is_some() && remove_accused_lawyer)
+        {
+            ctx.say("du chasch en aawalt nöd glichzitig neu setze und entferne")
+                .await?;
+            return Ok(());
+        }
+
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let lawyers = plaintiff_lawyer.iter().chain(accused_lawyer.iter());
+        for lawyer in lawyers.clone() {
+            if !state.lawyers.contains(&lawyer.id.into()) {
+                ctx.say(format!("<@{}> isch kein registrierte aawalt", lawyer.id))
+                    .await?;
+                return Ok(());
+            }
+        }
+
+        for official in judge.iter().chain(lawyers) {
+            if !is_eligible(mongo_client, &state, official.id.into()).await? {
+                ctx.say(format!(
+                    "<@{}> hät en z schlechte ruef zum als richter oder anwalt dihocke",
+                    official.id
+                ))
+                .await?;
+                return Ok(());
+            }
+        }
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let edit = LawsuitEdit {
+            judge: judge.map(|user| user.id.into()),
+            plaintiff_lawyer: lawyer_change(plaintiff_lawyer, remove_plaintiff_lawyer),
+            accused_lawyer: lawyer_change(accused_lawyer, remove_accused_lawyer),
+            reason,
+        };
+
+        if let Err(response) = lawsuit_ctx.edit(edit, &venue).await? {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say("isch gänderet").await?;
+
+        Ok(())
+    }
+
+    /// The change to a lawyer for `LawsuitEdit`, either a new one, none or no change at all.
+    fn lawyer_change(lawyer: Option<User>, remove: bool) -> Option<Option<SnowflakeId>> {
+        match lawyer {
+            Some(lawyer) => Some(Some(lawyer.id.into())),
+            None if remove => Some(None),
+            None => None,
+        }
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_withdraw_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let response = lawsuit_ctx
+            .withdraw(permission_override, user_id, venue)
+            .await?
+            .and_then(Closed::into_result);
+
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say("d klag isch zruggzoge").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_dismiss_impl(ctx: Context<'_>, reason: String) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let response = lawsuit_ctx
+            .dismiss(permission_override, user_id, reason, venue)
+            .await?
+            .and_then(Closed::into_result);
+
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say("d klag isch abgwise").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_settle_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+        let http = &ctx.discord().http;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, _) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        if lawsuit.side_of(ctx.author().id.into()).is_none() {
+            ctx.say("nur de kläger oder de aagklagti chönd en vergleich vorschlah")
+                .await?;
+            return Ok(());
+        }
+
+        let parties = [lawsuit.plaintiff, lawsuit.accused];
+        let mut confirmed = vec![ctx.author().id.into()];
+
+        let handle = ctx
+            .send(|reply| {
+                reply
+                    .content(settlement_text(&parties, &confirmed))
+                    .components(|c| {
+                        c.create_action_row(|row| {
+                            row.create_button(|b| {
+                                b.custom_id("settle_confirm")
+                                    .label("Vergleich annehmen")
+                                    .style(ButtonStyle::Success)
+                            })
+                        })
+                    })
+            })
+            .await?;
+
+        let mut message = handle.message().await?;
+        let mut interactions = message
+            .await_component_interactions(ctx.discord())
+            .timeout(SETTLEMENT_TIMEOUT)
+            .build();
+
+        while let Some(interaction) = ctx.data().health.until_shutdown(interactions.next()).await {
+            let user_id = interaction.user.id.into();
+
+            if !parties.contains(&user_id) {
+                interaction
+                    .create_interaction_response(http, |response| {
+                        response.interaction_response_data(|data| {
+                            data.content("das isch nöd din prozess").ephemeral(true)
+                        })
+                    })
+                    .await
+                    .wrap_err("respond to settlement interaction")?;
+                continue;
+            }
+
+            if !confirmed.contains(&user_id) {
+                confirmed.push(user_id);
+            }
+
+            let settled = parties.iter().all(|party| confirmed.contains(party));
+
+            interaction
+                .create_interaction_response(http, |response| {
+                    response
+                        .kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|data| {
+                            data.content(settlement_text(&parties, &confirmed));
+                            if settled {
+                                data.components(|c| c);
+                            }
+                            data
+                        })
+                })
+                .await
+                .wrap_err("respond to settlement interaction")?;
+
+            if settled {
+                // the lawsuit might have changed or closed while the parties were deciding
+                let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+                let current = match state.active_lawsuit(lawsuit.court_room) {
+                    Some(current) if current.id == lawsuit.id => current.clone(),
+                    _ => {
+                        ctx.say("de prozess isch scho abgschlosse").await?;
+                        return Ok(());
+                    }
+                };
+
+                let mut lawsuit_ctx = LawsuitCtx {
+                    lawsuit: current,
+                    mongo_client: mongo_client.clone(),
+                    http: http.clone(),
+                    guild_id,
+                };
+
+                if let Err(response) = lawsuit_ctx
+                    .settle(state.venue(lawsuit.court_room))
+                    .await?
+                    .and_then(Closed::into_result)
+                {
+                    ctx.say(response.to_string()).await?;
+                }
+
+                return Ok(());
+            }
+        }
+
+        message
+            .edit(http, |msg| {
+                msg.content("de vergleich isch nöd zstand cho")
+                    .components(|c| c)
+            })
+            .await
+            .wrap_err("edit settlement message")?;
+
+        Ok(())
+    }
+
+    fn settlement_text(parties: &[SnowflakeId], confirmed: &[SnowflakeId]) -> String {
+        let parties = parties
+            .iter()
+            .map(|party| {
+                let mark = if confirmed.contains(party) {
+                    "✅"
+                } else {
+                    "⏳"
+                };
+                format!("{mark} <@{party}>")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("Vergleich vorgeschlagen, beide Parteien müssen zustimmen:\n{parties}")
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_hire_impl(ctx: Context<'_>, lawyer: User) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+        let http = &ctx.discord().http;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, _) = match find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let side = match lawsuit.side_of(ctx.author().id.into()) {
+            Some(side) => side,
+            None => {
+                ctx.say("nur de kläger oder de aagklagti chönd en aawalt aastelle")
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        if !state.lawyers.contains(&lawyer.id.into()) {
+            ctx.say(format!("<@{}> isch kein registrierte aawalt", lawyer.id))
+                .await?;
+            return Ok(());
+        }
+
+        if lawsuit.is_participant(lawyer.id.into()) {
+            ctx.say(format!("<@{}> isch scho am prozess beteiligt", lawyer.id))
+                .await?;
+            return Ok(());
+        }
+
+        if !is_eligible(mongo_client, &state, lawyer.id.into()).await? {
+            ctx.say(format!(
+                "<@{}> hät en z schlechte ruef zum als richter oder anwalt dihocke",
+                lawyer.id
+            ))
+            .await?;
+            return Ok(());
+        }
+
+        let handle = ctx
+            .send(|reply| {
+                reply
+                    .content(format!(
+                        "<@{}>, <@{}> möcht dich als aawalt aastelle",
+                        lawyer.id,
+                        ctx.author().id
+                    ))
+                    .components(|c| {
+                        c.create_action_row(|row| {
+                            row.create_button(|b| {
+                                b.custom_id("hire_accept")
+                                    .label("Annehmen")
+                                    .style(ButtonStyle::Success)
+                            })
+                            .create_button(|b| {
+                                b.custom_id("hire_decline")
+                                    .label("Ablehnen")
+                                    .style(ButtonStyle::Danger)
+                            })
+                        })
+                    })
+            })
+            .await?;
+
+        let mut message = handle.message().await?;
+        let interaction = ctx
+            .data()
+            .health
+            .until_shutdown(
+                message
+                    .await_component_interaction(ctx.discord())
+                    .author_id(lawyer.id)
+                    .timeout(HIRE_TIMEOUT),
+            )
+            .await;
+
+        let interaction = match interaction {
+            Some(interaction) => interaction,
+            None => {
+                message
+                    .edit(http, |msg| {
+                        msg.content(format!("<@{}> hät nöd reagiert", lawyer.id))
+                            .components(|c| c)
+                    })
+                    .await
+                    .wrap_err("edit hire message")?;
+                return Ok(());
+            }
+        };
+
+        if interaction.data.custom_id != "hire_accept" {
+            interaction
+                .create_interaction_response(http, |response| {
+                    response
+                        .kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|data| {
+                            data.content(format!("<@{}> hät abglehnt", lawyer.id))
+                                .components(|c| c)
+                        })
+                })
+                .await
+                .wrap_err("respond to hire interaction")?;
+            return Ok(());
+        }
+
+        interaction
+            .create_interaction_response(http, |response| {
+                response.kind(InteractionResponseType::DeferredUpdateMessage)
+            })
+            .await
+            .wrap_err("respond to hire interaction")?;
+
+        // the lawsuit might have changed or closed while waiting for the lawyer
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+        let response = match state.active_lawsuit(lawsuit.court_room) {
+            Some(current) if current.id == lawsuit.id => {
+                if !state.lawyers.contains(&lawyer.id.into()) {
+                    Err(Response(format!(
+                        "<@{}> isch kein registrierte aawalt",
+                        lawyer.id
+                    )))
+                } else if current.is_participant(lawyer.id.into()) {
+                    Err(Response(format!(
+                        "<@{}> isch scho am prozess beteiligt",
+                        lawyer.id
+                    )))
+                } else {
+                    let mut lawsuit_ctx = LawsuitCtx {
+                        lawsuit: current.clone(),
+                        mongo_client: mongo_client.clone(),
+                        http: http.clone(),
+                        guild_id,
+                    };
+                    lawsuit_ctx
+                        .hire_lawyer(side, lawyer.id.into(), &state.venue(lawsuit.court_room))
+                        .await?
+                }
+            }
+            _ => Err(Response("de prozess isch scho abgschlosse".to_string())),
+        };
+
+        let content = match response {
+            Ok(()) => format!("<@{}> vertritt jetzt <@{}>", lawyer.id, ctx.author().id),
+            Err(response) => response.to_string(),
+        };
+        interaction
+            .edit_original_interaction_response(http, |response| {
+                response.content(content).components(|c| c)
+            })
+            .await
+            .wrap_err("edit hire message")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawsuit_clear_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let export = GuildExport::load(mongo_client, guild_id.into()).await?;
+        let json = export.to_json()?;
+
+        let prompt = confirm(
+            ctx,
+            "Da löscht alli prozess, s gfängnis und d errungeschafte. \
+             Do isch en export, mit /court import chasch en wieder iispiele."
+                .to_string(),
+            "Alles löschen",
+            Some(serenity::AttachmentType::Bytes {
+                data: json.into(),
+                filename: export.json_filename(),
+            }),
+        )
+        .await?;
+        let prompt = match prompt {
+            Some(prompt) => prompt,
+            None => return Ok(()),
+        };
+
+        // the snapshot is taken now, the export above misses what changed during the prompt
+        let deleted_at = mongo_client.delete_guild(guild_id.into()).await?;
+
+        if !offer_undo(ctx, &prompt, "alles weg").await? {
+            return Ok(());
+        }
+
+        // restoring replaces everything, that would lose what was stored since the deletion
+        if mongo_client.has_guild_data(guild_id.into()).await? {
+            prompt
+                .edit(ctx, |reply| {
+                    reply
+                        .content(
+                            "sit em lösche isch scho wieder öppis gspeicheret worde, \
+                             ich cha nüt meh widerherstelle",
+                        )
+                        .components(|c| c)
+                })
+                .await?;
+            return Ok(());
+        }
+
+        let restored = mongo_client
+            .restore_guild(guild_id.into(), deleted_at)
+            .await?;
+        let content = if restored {
+            "alles widerhergstellt"
+        } else {
+            "es git nüt zum widerherstelle"
+        };
+        prompt
+            .edit(ctx, |reply| reply.content(content).components(|c| c))
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub mod lawyer {
+    use std::time::Duration;
+
+    use super::*;
+
+    const QUESTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+    #[poise::command(
+        slash_command,
+        guild_only,
+        subcommands("apply", "list", "revoke", "set_role", "set_exam")
+    )]
+    pub async fn lawyer(_: Context<'_>) -> Result<()> {
+        unreachable!()
+    }
+
+    /// Sich als Anwalt zulassen
+    #[poise::command(slash_command, guild_only)]
+    async fn apply(ctx: Context<'_>) -> Result<()> {
+        lawyer_apply_impl(ctx).await.wrap_err("lawyer_apply")
+    }
+
+    /// Alle zugelassenen Anwälte anzeigen
+    #[poise::command(slash_command, guild_only)]
+    async fn list(ctx: Context<'_>) -> Result<()> {
+        lawyer_list_impl(ctx).await.wrap_err("lawyer_list")
+    }
+
+    /// Jemandem die Zulassung als Anwalt entziehen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn revoke(ctx: Context<'_>, #[description = "Der Anwalt"] user: User) -> Result<()> {
+        lawyer_revoke_impl(ctx, user)
+            .await
+            .wrap_err("lawyer_revoke")
+    }
+
+    /// Die Rolle für Anwälte setzen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_role(ctx: Context<'_>, #[description = "Die Rolle"] role: Role) -> Result<()> {
+        lawyer_set_role_impl(ctx, role)
+            .await
+            .wrap_err("lawyer_set_role")
+    }
+
+    /// Die Anwaltsprüfung ein- oder ausschalten
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_exam(
+        ctx: Context<'_>,
+        #[description = "Müssen Anwälte eine Prüfung ablegen?"] enabled: bool,
+    ) -> Result<()> {
+        lawyer_set_exam_impl(ctx, enabled)
+            .await
+            .wrap_err("lawyer_set_exam")
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawyer_apply_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+        let user_id = ctx.author().id;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        if state.lawyers.contains(&user_id.into()) {
+            ctx.say("du bisch scho aawalt").await?;
+            return Ok(());
+        }
+
+        if !is_eligible(mongo_client, &state, user_id.into()).await? {
+            ctx.say("du häsch en z schlechte ruef zum aawalt werde")
+                .await?;
+            return Ok(());
+        }
+
+        if state.bar_exam {
+            match bar_exam(ctx).await? {
+                Some(score) if score >= PASSING_SCORE => {}
+                Some(_) => {
+                    ctx.say("du bisch dur d prüefig gheit").await?;
+                    return Ok(());
+                }
+                None => {
+                    ctx.say("d ziit isch abgloffe").await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        mongo_client
+            .add_lawyer(guild_id.into(), user_id.into())
+            .await?;
+
+        if let Some(role) = state.lawyer_role {
+            let http = &ctx.discord().http;
+            guild_id
+                .member(http, user_id)
+                .await
+                .wrap_err("fetching guild member")?
+                .add_role(http, role)
+                .await
+                .wrap_err("add lawyer role")?;
+        }
+
+        ctx.say("du bisch jetzt als aawalt zuegloh").await?;
+
+        Ok(())
+    }
+
+    /// Asks the bar exam questions and returns the amount of correct answers, or `None` if the
+    /// user didn't answer in time.
+    async fn bar_exam(ctx: Context<'_>) -> Result<Option<usize>> {
+        let http = &ctx.discord().http;
+
+        let handle = ctx
+            .send(|reply| {
+                reply
+                    .content(BAR_EXAM[0].question)
+                    .components(|c| answer_buttons(c, 0))
+                    .ephemeral(true)
+            })
+            .await?;
+        let message = handle.message().await?;
+
+        let mut score = 0;
+
+        for (index, question) in BAR_EXAM.iter().enumerate() {
+            let interaction = ctx
+                .data()
+                .health
+                .until_shutdown(
+                    message
+                        .await_component_interaction(ctx.discord())
+                        .author_id(ctx.author().id)
+                        .timeout(QUESTION_TIMEOUT),
+                )
+                .await;
+
+            let interaction = match interaction {
+                Some(interaction) => interaction,
+                None => return Ok(None),
+            };
+
+            if interaction.data.custom_id == format!("bar_exam_{}", question.correct) {
+                score += 1;
+            }
+
+            let next = index + 1;
+            interaction
+                .create_interaction_response(http, |response| {
+                    response
+                        .kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|data| match BAR_EXAM.get(next) {
+                            Some(question) => data
+                                .content(question.question)
+                                .components(|c| answer_buttons(c, next)),
+                            None => data
+                                .content(format!("{score}/{} richtig", BAR_EXAM.len()))
+                                .components(|c| c),
+                        })
+                })
+                .await
+                .wrap_err("respond to bar exam interaction")?;
+        }
+
+        Ok(Some(score))
+    }
+
+    fn answer_buttons(
+        components: &mut serenity::CreateComponents,
+        question: usize,
+    ) -> &mut serenity::CreateComponents {
+        components.create_action_row(|row| {
+            for (index, answer) in BAR_EXAM[question].answers.iter().enumerate() {
+                row.create_button(|b| {
+                    b.custom_id(format!("bar_exam_{index}"))
+                        .label(answer)
+                        .style(ButtonStyle::Primary)
+                });
+            }
+            row
+        })
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawyer_list_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+
+        let state = ctx
+            .data()
+            .mongo
+            .find_or_insert_state(guild_id.into())
+            .await?;
+
+        if state.lawyers.is_empty() {
+            ctx.say("es git no kei aawält").await?;
+            return Ok(());
+        }
+
+        let lawyers = state
+            .lawyers
+            .iter()
+            .map(|lawyer| format!("<@{lawyer}>"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        ctx.send(|reply| reply.embed(|embed| embed.title("Anwälte").description(lawyers)))
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawyer_revoke_impl(ctx: Context<'_>, user: User) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        mongo_client
+            .remove_lawyer(guild_id.into(), user.id.into())
+            .await?;
+
+        if let Some(role) = state.lawyer_role {
+            let http = &ctx.discord().http;
+            guild_id
+                .member(http, user.id)
+                .await
+                .wrap_err("fetching guild member")?
+                .remove_role(http, role)
+                .await
+                .wrap_err("remove lawyer role")?;
+        }
+
+        ctx.say("zuelassig entzoge").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawyer_set_role_impl(ctx: Context<'_>, role: Role) -> Result<()> {
+        ctx.data()
+            .mongo
+            .set_lawyer_role(
+                ctx.guild_id().wrap_err("guild_id not found")?.into(),
+                role.id.into(),
+            )
+            .await?;
+
+        confirm_setup(ctx).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn lawyer_set_exam_impl(ctx: Context<'_>, enabled: bool) -> Result<()> {
+        ctx.data()
+            .mongo
+            .set_bar_exam(
+                ctx.guild_id().wrap_err("guild_id not found")?.into(),
+                enabled,
+            )
+            .await?;
+
+        ctx.say("isch gsetzt").await.wrap_err("reply")?;
+
+        Ok(())
+    }
+}
+
+pub mod prison {
+    use super::*;
+    #[poise::command(
+        slash_command,
+        guild_only,
+        subcommands("set_role", "arrest", "release")
+    )]
+    pub async fn prison(_: Context<'_>) -> Result<()> {
+        unreachable!()
+    }
+
+    /// Die Rolle für Gefangene setzen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn set_role(ctx: Context<'_>, #[description = "Die Rolle"] role: Role) -> Result<()> {
+        prison_set_role_impl(ctx, role)
+            .await
+            .wrap_err("prison_set_role")
+    }
+
+    /// Jemanden einsperren
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn arrest(
+        ctx: Context<'_>,
+        #[description = "Die Person zum einsperren"] user: User,
+    ) -> Result<()> {
+        prison_arrest_impl(ctx, user)
+            .await
+            .wrap_err("prison_arrest")
+    }
+
+    /// Einen Gefangenen freilassen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn release(
+        ctx: Context<'_>,
+        #[description = "Die Person zum freilassen"] user: User,
+    ) -> Result<()> {
+        prison_release_impl(ctx, user)
+            .await
+            .wrap_err("prison_release")
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn prison_set_role_impl(ctx: Context<'_>, role: Role) -> Result<()> {
+        ctx.data()
+            .mongo
+            .set_prison_role(
+                ctx.guild_id().wrap_err("guild_id not found")?.into(),
+                role.id.into(),
+            )
+            .await?;
+
+        confirm_setup(ctx).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn prison_arrest_impl(ctx: Context<'_>, user: User) -> Result<()> {
+        let mongo_client = &ctx.data().mongo;
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let http = &ctx.discord().http;
+
+        if confirm(
+            ctx,
+            format!("Wotsch <@{}> würkli iisperre?", user.id),
+            "Iisperre",
+            None,
+        )
+        .await?
+        .is_none()
+        {
+            return Ok(());
+        }
+
+        let response =
+            crate::prison::imprison(mongo_client, http, guild_id, user.id.into(), None).await?;
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say("isch igsperrt").await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn prison_release_impl(ctx: Context<'_>, user: User) -> Result<()> {
+        let mongo_client = &ctx.data().mongo;
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let http = &ctx.discord().http;
+
+        if confirm(
+            ctx,
+            format!("Wotsch <@{}> würkli freilah?", user.id),
+            "Freilah",
+            None,
+        )
+        .await?
+        .is_none()
+        {
+            return Ok(());
+        }
+
+        let response = crate::prison::release(mongo_client, http, guild_id, user.id.into()).await?;
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say("d'freiheit wartet").await?;
+
+        if let Err(err) = process_event(
+            mongo_client,
+            http,
+            guild_id,
+            ctx.channel_id(),
+            CourtEvent::PrisonReleased(user.id.into()),
+        )
+        .await
+        {
+            error!(?err, "Error processing achievements");
+        }
+
+        Ok(())
+    }
+}
+
+pub mod court {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        chart,
+        lawsuit::VoiceOrder,
+        procedure::MAX_CONTEMPT,
+        stats::{stats_embed, CourtStats, StatsFilter},
+    };
+
+    const MAX_LOG_ENTRIES: usize = 25;
+
+    #[poise::command(
+        slash_command,
+        guild_only,
+        subcommands(
+            "doctor", "order", "floor", "contempt", "log", "stats", "export", "import"
+        )
+    )]
+    pub async fn court(_: Context<'_>) -> Result<()> {
+        unreachable!()
+    }
+
+    #[poise::command(slash_command, guild_only, subcommands("silence", "speak"))]
+    async fn order(_: Context<'_>) -> Result<()> {
+        unreachable!()
+    }
+
+    /// Im Sprachkanal des Gerichtsraums Ruhe anordnen
+    #[poise::command(slash_command, guild_only)]
+    async fn silence(
+        ctx: Context<'_>,
+        #[description = "Die Person, sonst alle"] user: Option<User>,
+    ) -> Result<()> {
+        court_order_impl(ctx, VoiceOrder::Silence, user)
+            .await
+            .wrap_err("court_order_silence")
+    }
+
+    /// Im Sprachkanal des Gerichtsraums das Wort erteilen
+    #[poise::command(slash_command, guild_only)]
+    async fn speak(
+        ctx: Context<'_>,
+        #[description = "Die Person, sonst alle"] user: Option<User>,
+    ) -> Result<()> {
+        court_order_impl(ctx, VoiceOrder::Speak, user)
+            .await
+            .wrap_err("court_order_speak")
+    }
+
+    /// Einer Partei das Wort erteilen, alle anderen dürfen nicht mehr schreiben
+    #[poise::command(slash_command, guild_only)]
+    async fn floor(
+        ctx: Context<'_>,
+        #[description = "Die Person, ohne Person dürfen wieder alle schreiben"] user: Option<User>,
+    ) -> Result<()> {
+        court_floor_impl(ctx, user).await.wrap_err("court_floor")
+    }
+
+    /// Jemanden wegen Missachtung des Gerichts stummschalten
+    #[poise::command(slash_command, guild_only)]
+    async fn contempt(
+        ctx: Context<'_>,
+        #[description = "Die Person"] user: User,
+        #[description = "Dauer in Minuten"] minutes: u64,
+    ) -> Result<()> {
+        court_contempt_impl(ctx, user, minutes)
+            .await
+            .wrap_err("court_contempt")
+    }
+
+    /// Das Protokoll des laufenden Prozesses anzeigen
+    #[poise::command(slash_command, guild_only)]
+    async fn log(ctx: Context<'_>) -> Result<()> {
+        court_log_impl(ctx).await.wrap_err("court_log")
+    }
+
+    /// Prüfen, ob der Bot alle nötigen Berechtigungen hat
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn doctor(ctx: Context<'_>) -> Result<()> {
+        court_doctor_impl(ctx).await.wrap_err("court_doctor")
+    }
+
+    /// Alle Daten des Gerichts als Datei exportieren
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn export(
+        ctx: Context<'_>,
+        #[description = "Die Prozesse zusätzlich als CSV"] csv: Option<bool>,
+    ) -> Result<()> {
+        court_export_impl(ctx, csv.unwrap_or(false))
+            .await
+            .wrap_err("court_export")
+    }
+
+    /// Die Daten des Gerichts aus einem Export wiederherstellen
+    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+    async fn import(
+        ctx: Context<'_>,
+        #[description = "Die exportierte JSON-Datei"] file: serenity::Attachment,
+        #[description = "Nur prüfen, ohne etwas zu ändern (Standard: ja)"] dry_run: Option<bool>,
+    ) -> Result<()> {
+        court_import_impl(ctx, file, dry_run.unwrap_or(true))
+            .await
+            .wrap_err("court_import")
+    }
+
+    /// Statistiken über das Gericht anzeigen
+    #[poise::command(slash_command, guild_only)]
+    async fn stats(
+        ctx: Context<'_>,
+        #[description = "Nur die letzten Tage"] days: Option<u64>,
+        #[description = "Nur Prozesse mit dieser Person"] user: Option<User>,
+        #[description = "Diagramme anhängen"] charts: Option<bool>,
+    ) -> Result<()> {
+        court_stats_impl(ctx, days, user, charts.unwrap_or(false))
+            .await
+            .wrap_err("court_stats")
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn court_stats_impl(
+        ctx: Context<'_>,
+        days: Option<u64>,
+        user: Option<User>,
+        charts: bool,
+    ) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        if charts {
+            ctx.defer().await?;
+        }
+
+        let now = DateTime::now();
+        let filter = StatsFilter::last_days(days, user.as_ref().map(|user| user.id.into()), now);
+        let stats = CourtStats::load(mongo_client, guild_id.into(), filter, now).await?;
+
+        let mut title = "Gerichtsstatistik".to_string();
+        if let Some(user) = &user {
+            title.push_str(&format!(" von {}", user.tag()));
+        }
+        if let Some(days) = days {
+            title.push_str(&format!(" (letzte {days} Tage)"));
+        }
+
+        let charts = if charts {
+            render_stats_charts(ctx, &stats).await?
+        } else {
+            Vec::new()
+        };
+
+        ctx.send(|reply| {
+            reply.embed(|embed| stats_embed(embed.title(title), &stats));
+            for (filename, data) in charts {
+                reply.attachment(serenity::AttachmentType::Bytes {
+                    data: data.into(),
+                    filename: filename.to_string(),
+                });
+            }
+            reply
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Renders the charts for the stats, named by their file names.
+    async fn render_stats_charts(
+        ctx: Context<'_>,
+        stats: &CourtStats,
+    ) -> Result<Vec<(&'static str, Vec<u8>)>> {
+        let mut judges = Vec::new();
+        for entry in &stats.lawsuits.judges {
+            let name = match UserId::from(entry.user).to_user(ctx.discord()).await {
+                Ok(user) => user.tag(),
+                Err(_) => entry.user.to_string(),
+            };
+            judges.push((name, entry.count));
+        }
+        let lawsuits = stats.lawsuits.clone();
+
+        tokio::task::spawn_blocking(move || {
+            Ok(vec![
+                (
+                    "prozesse.png",
+                    chart::cases_per_week_chart(&lawsuits.weeks)?,
+                ),
+                ("ausgang.png", chart::outcome_chart(&lawsuits)?),
+                (
+                    "richter.png",
+                    chart::leaderboard_chart("Fleissigste Richter", &judges)?,
+                ),
+            ])
+        })
+        .await
+        .wrap_err("render charts")?
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn court_export_impl(ctx: Context<'_>, csv: bool) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+
+        ctx.defer_ephemeral().await?;
+
+        let export = GuildExport::load(&ctx.data().mongo, guild_id.into()).await?;
+        let json = export.to_json()?;
+        let csv = csv.then(|| lawsuits_csv(&export.state.lawsuits));
+
+        ctx.send(|reply| {
+            reply
+                .content(format!(
+                    "{} prozess, {} gfängnis-iiträg und {} errungeschafte",
+                    export.state.lawsuits.len(),
+                    export.prison.len(),
+                    export.achievements.len()
+                ))
+                .attachment(serenity::AttachmentType::Bytes {
+                    data: json.into(),
+                    filename: export.json_filename(),
+                });
+            if let Some(csv) = csv {
+                reply.attachment(serenity::AttachmentType::Bytes {
+                    data: csv.into_bytes().into(),
+                    filename: export.csv_filename(),
+                });
+            }
+            reply.ephemeral(true)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx, file), fields(filename = %file.filename))]
+    async fn court_import_impl(
+        ctx: Context<'_>,
+        file: serenity::Attachment,
+        dry_run: bool,
+    ) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+
+        if file.size > MAX_IMPORT_BYTES {
+            ctx.say("d datei isch z gross").await?;
+            return Ok(());
+        }
+
+        ctx.defer_ephemeral().await?;
+
+        let bytes = file.download().await.wrap_err("download import file")?;
+        let import = match GuildExport::parse(&bytes, guild_id.into()) {
+            Ok(import) => import,
+            Err(response) => {
+                ctx.say(response.to_string()).await?;
+                return Ok(());
+            }
+        };
+
+        let current = GuildExport::load(mongo_client, guild_id.into()).await?;
+        let summary = import.summary(&current);
+
+        let content = if dry_run {
+            format!("{summary}\n\nNüt gänderet, für de import dry_run uf false setze.")
+        } else {
+            mongo_client
+                .import_guild(&import.state, &import.prison, &import.achievements)
+                .await?;
+            format!("{summary}\n\nImportiert.")
+        };
+
+        ctx.say(content).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn court_doctor_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+
+        ctx.defer().await?;
+
+        let state = ctx
+            .data()
+            .mongo
+            .find_or_insert_state(guild_id.into())
+            .await?;
+        let problems = diagnose(&ctx.discord().http, guild_id, &state).await?;
+
+        ctx.send(|reply| reply.embed(|embed| checklist_embed(embed, &problems)))
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn court_floor_impl(ctx: Context<'_>, user: Option<User>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = lawsuit::invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match lawsuit::find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
         };
 
         let mut lawsuit_ctx = LawsuitCtx {
@@ -248,13 +2286,9 @@ pub mod lawsuit {
             guild_id,
         };
 
+        let holder = user.as_ref().map(|user| user.id.into());
         let response = lawsuit_ctx
-            .rule_verdict(
-                permission_override,
-                member.user.id,
-                verdict.to_string(),
-                room,
-            )
+            .grant_floor(permission_override, user_id, holder, &venue)
             .await?;
 
         if let Err(response) = response {
@@ -262,143 +2296,499 @@ pub mod lawsuit {
             return Ok(());
         }
 
-        ctx.say("ich han en dir abschlosse").await?;
+        let reply = match user {
+            Some(user) => format!("<@{}> hät s wort", user.id),
+            None => "alli dörfed wieder schriibe".to_string(),
+        };
+        ctx.say(reply).await?;
 
         Ok(())
     }
 
     #[tracing::instrument(skip(ctx))]
-    async fn lawsuit_clear_impl(ctx: Context<'_>) -> Result<()> {
+    async fn court_contempt_impl(ctx: Context<'_>, user: User, minutes: u64) -> Result<()> {
         let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = lawsuit::invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let duration = Duration::from_secs(minutes * 60);
+        if minutes == 0 || duration > MAX_CONTEMPT {
+            ctx.say(format!(
+                "d duur mues zwüsche 1 und {} minute si",
+                MAX_CONTEMPT.as_secs() / 60
+            ))
+            .await?;
+            return Ok(());
+        }
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match lawsuit::find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let response = lawsuit_ctx
+            .hold_in_contempt(
+                permission_override,
+                user_id,
+                user.id.into(),
+                duration,
+                &venue,
+            )
+            .await?;
+
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        ctx.say(format!(
+            "<@{}> isch wege missachtig vom gricht für {minutes} minute stummgschaltet",
+            user.id
+        ))
+        .await?;
 
-        ctx.data().mongo.delete_guild(guild_id.into()).await?;
-        ctx.say("alles weg").await?;
         Ok(())
     }
-}
 
-pub mod prison {
-    use super::*;
-    #[poise::command(
-        slash_command,
-        guild_only,
-        subcommands("set_role", "arrest", "release")
-    )]
-    pub async fn prison(_: Context<'_>) -> Result<()> {
-        unreachable!()
-    }
+    #[tracing::instrument(skip(ctx))]
+    async fn court_log_impl(ctx: Context<'_>) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let state = ctx
+            .data()
+            .mongo
+            .find_or_insert_state(guild_id.into())
+            .await?;
 
-    /// Die Rolle für Gefangene setzen
-    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
-    async fn set_role(ctx: Context<'_>, #[description = "Die Rolle"] role: Role) -> Result<()> {
-        prison_set_role_impl(ctx, role)
-            .await
-            .wrap_err("prison_set_role")
+        let (lawsuit, _) = match lawsuit::find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let log = if lawsuit.log.is_empty() {
+            "Noch keine Einträge".to_string()
+        } else {
+            // only the latest entries fit into an embed
+            let skip = lawsuit.log.len().saturating_sub(MAX_LOG_ENTRIES);
+            lawsuit.log[skip..]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        ctx.send(|reply| reply.embed(|embed| embed.title("Protokoll").description(log)))
+            .await?;
+
+        Ok(())
     }
 
-    /// Jemanden einsperren
-    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
-    async fn arrest(
+    #[tracing::instrument(skip(ctx))]
+    async fn court_order_impl(
         ctx: Context<'_>,
-        #[description = "Die Person zum einsperren"] user: User,
+        order: VoiceOrder,
+        user: Option<User>,
     ) -> Result<()> {
-        prison_arrest_impl(ctx, user)
-            .await
-            .wrap_err("prison_arrest")
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let (user_id, permission_override) = lawsuit::invoking_member(ctx)?;
+        let mongo_client = &ctx.data().mongo;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, venue) = match lawsuit::find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: ctx.discord().http.clone(),
+            guild_id,
+        };
+
+        let target = user.as_ref().map(|user| user.id.into());
+        let response = lawsuit_ctx
+            .order_voice(permission_override, user_id, order, target, &venue)
+            .await?;
+
+        if let Err(response) = response {
+            ctx.say(response.to_string()).await?;
+            return Ok(());
+        }
+
+        let reply = match (order, user) {
+            (VoiceOrder::Silence, Some(user)) => format!("ruhe, <@{}>!", user.id),
+            (VoiceOrder::Silence, None) => "ruhe im gerichtssaal!".to_string(),
+            (VoiceOrder::Speak, Some(user)) => format!("<@{}> hät s wort", user.id),
+            (VoiceOrder::Speak, None) => "alli dörfed wieder rede".to_string(),
+        };
+        ctx.say(reply).await?;
+
+        Ok(())
     }
+}
 
-    /// Einen Gefangenen freilassen
-    #[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
-    async fn release(
+pub mod objection {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::procedure::CaseEvent;
+
+    const RULING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+    /// Als Anwalt Einspruch erheben
+    #[poise::command(slash_command, guild_only)]
+    pub async fn objection(
         ctx: Context<'_>,
-        #[description = "Die Person zum freilassen"] user: User,
+        #[description = "Der Grund für den Einspruch"] reason: String,
     ) -> Result<()> {
-        prison_release_impl(ctx, user)
-            .await
-            .wrap_err("prison_release")
+        objection_impl(ctx, reason).await.wrap_err("objection")
     }
 
     #[tracing::instrument(skip(ctx))]
-    async fn prison_set_role_impl(ctx: Context<'_>, role: Role) -> Result<()> {
-        ctx.data()
-            .mongo
-            .set_prison_role(
-                ctx.guild_id().wrap_err("guild_id not found")?.into(),
-                role.id.into(),
+    async fn objection_impl(ctx: Context<'_>, reason: String) -> Result<()> {
+        let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
+        let mongo_client = &ctx.data().mongo;
+        let http = &ctx.discord().http;
+        let author = ctx.author().id;
+
+        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
+
+        let (lawsuit, _) = match lawsuit::find_active_case(ctx, &state).await? {
+            Some(case) => case,
+            None => return Ok(()),
+        };
+
+        let lawyers = [lawsuit.plaintiff_lawyer, lawsuit.accused_lawyer];
+        if !lawyers.contains(&Some(author.into())) {
+            ctx.say("nur d aawält vo dem prozess chönd iisprooch erhebe")
+                .await?;
+            return Ok(());
+        }
+
+        let judge = lawsuit.judge;
+        let mut lawsuit_ctx = LawsuitCtx {
+            lawsuit,
+            mongo_client: mongo_client.clone(),
+            http: http.clone(),
+            guild_id,
+        };
+
+        lawsuit_ctx
+            .log(
+                Some(author.into()),
+                CaseEvent::Objection {
+                    reason: reason.clone(),
+                },
             )
             .await?;
 
-        ctx.say("isch gsetzt").await.wrap_err("reply")?;
+        let text = format!("⚖️ Iisprooch vo <@{author}>: {reason}");
+
+        let handle = ctx
+            .send(|reply| {
+                reply
+                    .content(format!("{text}\n<@{judge}>, stattgeh oder ablehne?"))
+                    .components(|c| {
+                        c.create_action_row(|row| {
+                            row.create_button(|b| {
+                                b.custom_id("objection_sustain")
+                                    .label("Stattgegeben")
+                                    .style(ButtonStyle::Success)
+                            })
+                            .create_button(|b| {
+                                b.custom_id("objection_overrule")
+                                    .label("Abgelehnt")
+                                    .style(ButtonStyle::Danger)
+                            })
+                        })
+                    })
+            })
+            .await?;
+
+        let mut message = handle.message().await?;
+        let mut interactions = message
+            .await_component_interactions(ctx.discord())
+            .timeout(RULING_TIMEOUT)
+            .build();
+
+        while let Some(interaction) = ctx.data().health.until_shutdown(interactions.next()).await {
+            let permission_override = interaction
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.contains(Permissions::MANAGE_GUILD));
+
+            if interaction.user.id != UserId::from(judge) && !permission_override {
+                interaction
+                    .create_interaction_response(http, |response| {
+                        response.interaction_response_data(|data| {
+                            data.content("nur de richter cha über de iisprooch entscheide")
+                                .ephemeral(true)
+                        })
+                    })
+                    .await
+                    .wrap_err("respond to objection interaction")?;
+                continue;
+            }
+
+            let (event, ruling) = match interaction.data.custom_id.as_str() {
+                "objection_sustain" => (CaseEvent::ObjectionSustained, "stattgegeben"),
+                _ => (CaseEvent::ObjectionOverruled, "abgelehnt"),
+            };
+
+            lawsuit_ctx
+                .log(Some(interaction.user.id.into()), event)
+                .await?;
+
+            interaction
+                .create_interaction_response(http, |response| {
+                    response
+                        .kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|data| {
+                            data.content(format!("{text}\n**{ruling}**"))
+                                .components(|c| c)
+                        })
+                })
+                .await
+                .wrap_err("respond to objection interaction")?;
+
+            return Ok(());
+        }
+
+        message
+            .edit(http, |msg| {
+                msg.content(format!("{text}\nde richter hät nöd entschiede"))
+                    .components(|c| c)
+            })
+            .await
+            .wrap_err("edit objection message")?;
 
         Ok(())
     }
+}
+
+pub mod record {
+    use super::*;
+
+    /// Das Strafregister und den Ruf von jemandem anzeigen
+    #[poise::command(slash_command, guild_only)]
+    pub async fn record(ctx: Context<'_>, #[description = "Die Person"] user: User) -> Result<()> {
+        record_impl(ctx, user).await.wrap_err("record")
+    }
 
     #[tracing::instrument(skip(ctx))]
-    async fn prison_arrest_impl(ctx: Context<'_>, user: User) -> Result<()> {
-        let mongo_client = &ctx.data().mongo;
+    async fn record_impl(ctx: Context<'_>, user: User) -> Result<()> {
         let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
-        let http = &ctx.discord().http;
+        let mongo_client = &ctx.data().mongo;
 
         let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
-        let role = state.prison_role;
-
-        let role = match role {
-            Some(role) => role,
-            None => {
-                ctx.say("du mosch zerst e rolle setze mit /prison set_role")
-                    .await?;
-                return Ok(());
-            }
+        let record = CriminalRecord::load(mongo_client, &state, user.id.into()).await?;
+
+        let now = DateTime::now();
+        let reputation = record.reputation(&state.reputation, now);
+
+        let convictions = if record.convictions.is_empty() {
+            "Keine".to_string()
+        } else {
+            record
+                .convictions
+                .iter()
+                .map(|lawsuit| match lawsuit.closed_at {
+                    Some(closed_at) => format!(
+                        "<t:{}:d>: {}",
+                        closed_at.timestamp_millis() / 1000,
+                        lawsuit.reason
+                    ),
+                    None => lawsuit.reason.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
         };
 
-        mongo_client
-            .add_to_prison(guild_id.into(), user.id.into())
-            .await?;
+        ctx.send(|reply| {
+            reply.embed(|embed| {
+                embed
+                    .title(format!("Strafregister von {}", user.tag()))
+                    .field("Ruf", format!("{reputation:.1}"), true)
+                    .field("Freisprüche", record.acquittals.len(), true)
+                    .field("Geständnisse", record.guilty_pleas(), true)
+                    .field(
+                        "Zeit im Gefängnis",
+                        format!(
+                            "{:.1}h ({} mal)",
+                            record.prison_hours(now),
+                            record.prison_entries.len()
+                        ),
+                        true,
+                    )
+                    .field("Verurteilungen", convictions, false)
+            })
+        })
+        .await?;
 
-        guild_id
-            .member(http, user.id)
-            .await
-            .wrap_err("fetching guild member")?
-            .add_role(http, role)
-            .await
-            .wrap_err("add guild member role")?;
+        Ok(())
+    }
+}
 
-        ctx.say("isch igsperrt").await?;
+pub mod achievements {
+    use super::*;
 
-        Ok(())
+    /// Die Errungenschaften von jemandem anzeigen
+    #[poise::command(slash_command, guild_only)]
+    pub async fn achievements(
+        ctx: Context<'_>,
+        #[description = "Die Person"] user: Option<User>,
+    ) -> Result<()> {
+        achievements_impl(ctx, user).await.wrap_err("achievements")
     }
 
     #[tracing::instrument(skip(ctx))]
-    async fn prison_release_impl(ctx: Context<'_>, user: User) -> Result<()> {
-        let mongo_client = &ctx.data().mongo;
+    async fn achievements_impl(ctx: Context<'_>, user: Option<User>) -> Result<()> {
         let guild_id = ctx.guild_id().wrap_err("guild_id not found")?;
-        let http = &ctx.discord().http;
+        let mongo_client = &ctx.data().mongo;
+        let user = user.unwrap_or_else(|| ctx.author().clone());
 
-        let state = mongo_client.find_or_insert_state(guild_id.into()).await?;
-        let role = state.prison_role;
+        let unlocked = mongo_client
+            .find_achievements(guild_id.into(), user.id.into())
+            .await?;
+        let progress = load_progress(mongo_client, guild_id.into(), user.id.into()).await?;
 
-        let role = match role {
-            Some(role) => role,
-            None => {
-                ctx.say("du mosch zerst e rolle setze mit /prison set_role")
-                    .await?;
-                return Ok(());
+        let lines = ACHIEVEMENTS
+            .iter()
+            .map(
+                |achievement| match unlocked.iter().find(|u| u.achievement == achievement.id) {
+                    Some(unlocked) => format!(
+                        "🏆 **{}** - {} (<t:{}:d>)",
+                        achievement.name,
+                        achievement.description,
+                        unlocked.unlocked_at.timestamp_millis() / 1000
+                    ),
+                    None => format!(
+                        "🔒 **{}** - {} ({}/{})",
+                        achievement.name,
+                        achievement.description,
+                        progress.get(achievement.counter).floor(),
+                        achievement.threshold
+                    ),
+                },
+            )
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        ctx.send(|reply| {
+            reply.embed(|embed| {
+                embed
+                    .title(format!("Errungenschaften von {}", user.tag()))
+                    .description(lines)
+            })
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub mod privacy {
+    use super::*;
+    use crate::privacy::{erase_member, MemberData};
+
+    #[poise::command(slash_command, subcommands("mydata", "erase"))]
+    pub async fn privacy(_: Context<'_>) -> Result<()> {
+        unreachable!()
+    }
+
+    /// Alle Daten, die der Bot über dich gespeichert hat, per DM erhalten
+    #[poise::command(slash_command)]
+    async fn mydata(ctx: Context<'_>) -> Result<()> {
+        privacy_mydata_impl(ctx).await.wrap_err("privacy_mydata")
+    }
+
+    /// Dich aus allen Prozessen anonymisieren und deine Daten löschen
+    #[poise::command(slash_command)]
+    async fn erase(ctx: Context<'_>) -> Result<()> {
+        privacy_erase_impl(ctx).await.wrap_err("privacy_erase")
+    }
+
+    #[tracing::instrument(skip(ctx))]
+    async fn privacy_mydata_impl(ctx: Context<'_>) -> Result<()> {
+        ctx.defer_ephemeral().await?;
+
+        let user_id = ctx.author().id;
+        let data = MemberData::load(&ctx.data().mongo, user_id.into()).await?;
+        let json = serde_json::to_vec_pretty(&data).wrap_err("serialize member data")?;
+
+        let sent = ctx
+            .author()
+            .direct_message(&ctx.discord().http, |m| {
+                m.content(format!(
+                    "{} prozess, {} gfängnis-iiträg und {} errungeschafte",
+                    data.lawsuit_count(),
+                    data.prison.len(),
+                    data.achievements.len()
+                ))
+                .add_file(serenity::AttachmentType::Bytes {
+                    data: json.into(),
+                    filename: format!("daten-{user_id}.json"),
+                })
+            })
+            .await;
+
+        let content = match sent {
+            Ok(_) => "ich han dir dini date per DM gschickt",
+            Err(err) => {
+                info!(?err, "Failed to send member data per DM");
+                "ich chan dir kei DM schicke, lass DMs vo server-mitglieder zue"
             }
         };
-
-        mongo_client
-            .remove_from_prison(guild_id.into(), user.id.into())
+        ctx.send(|reply| reply.content(content).ephemeral(true))
             .await?;
 
-        guild_id
-            .member(http, user.id)
-            .await
-            .wrap_err("fetching guild member")?
-            .remove_role(http, role)
-            .await
-            .wrap_err("remove guild member role")?;
+        Ok(())
+    }
 
-        ctx.say("d'freiheit wartet").await?;
+    #[tracing::instrument(skip(ctx))]
+    async fn privacy_erase_impl(ctx: Context<'_>) -> Result<()> {
+        let mongo_client = &ctx.data().mongo;
+
+        let prompt = confirm(
+            ctx,
+            "Du wirsch i allne prozess und gfängnis-iiträg dur e anonymi id ersetzt, \
+             dini errungeschafte und dini zuelassig als anwalt werdet glöscht. \
+             Das chasch nöd rückgängig mache, hol dir vorher mit /privacy mydata dini date."
+                .to_string(),
+            "Löschen",
+            None,
+        )
+        .await?;
+        let prompt = match prompt {
+            Some(prompt) => prompt,
+            None => return Ok(()),
+        };
+
+        let content = match erase_member(mongo_client, ctx.author().id.into()).await? {
+            Ok(erasure) => format!(
+                "du bisch us {} prozess und {} gfängnis-iiträg entfernt, \
+                 {} errungeschafte sind glöscht",
+                erasure.lawsuits, erasure.prison_entries, erasure.achievements
+            ),
+            Err(response) => response.to_string(),
+        };
+
+        prompt
+            .edit(ctx, |reply| reply.content(content).components(|c| c))
+            .await?;
 
         Ok(())
     }
@@ -410,18 +2800,86 @@ pub async fn listener(
     _: poise::FrameworkContext<'_, Handler, Report>,
     data: &Handler,
 ) -> Result<()> {
-    #[allow(clippy::single_match)]
     match event {
+        Event::GuildCreate { guild, .. } => {
+            if let Err(err) = data.mongo.set_guild_departed(guild.id.into(), None).await {
+                error!(?err, "An error occurred in guild_create handler");
+            }
+        }
+        // unavailable guilds are outages, the bot is still in them
+        Event::GuildDelete { incomplete, .. } if !incomplete.unavailable => {
+            info!(guild_id = %incomplete.id, "Removed from guild");
+            if let Err(err) = data
+                .mongo
+                .set_guild_departed(incomplete.id.into(), Some(DateTime::now()))
+                .await
+            {
+                error!(?err, "An error occurred in guild_delete handler");
+            }
+        }
         Event::GuildMemberAddition { new_member } => {
             if let Err(err) = data.handle_guild_member_join(ctx, new_member).await {
                 error!(?err, "An error occurred in guild_member_addition handler");
             }
         }
+        Event::Message { new_message } => {
+            if let (Some(guild_id), false) = (new_message.guild_id, new_message.author.bot) {
+                if let Err(err) = appearance::record_appearance(
+                    &data.mongo,
+                    &ctx.http,
+                    guild_id,
+                    new_message.channel_id,
+                    new_message.author.id,
+                )
+                .await
+                {
+                    error!(?err, "An error occurred in message handler");
+                }
+            }
+        }
+        Event::GuildMemberRemoval { guild_id, user, .. } => {
+            if let Err(err) =
+                appearance::handle_accused_left(&data.mongo, &ctx.http, *guild_id, user.id).await
+            {
+                error!(?err, "An error occurred in guild_member_removal handler");
+            }
+        }
+        Event::InteractionCreate {
+            interaction: Interaction::MessageComponent(component),
+        } => {
+            // buttons that come in during the shutdown can be clicked again after the restart
+            let _guard = match data.health.command_started() {
+                Some(guard) => guard,
+                None => return Ok(()),
+            };
+            if let Err(err) = handle_component_interaction(ctx, data, component).await {
+                error!(?err, "An error occurred in component interaction handler");
+            }
+        }
         _ => {}
     }
     Ok(())
 }
 
+/// Handles the buttons that outlive a command, like subpoenas and the watch button.
+async fn handle_component_interaction(
+    ctx: &serenity::Context,
+    data: &Handler,
+    component: &MessageComponentInteraction,
+) -> Result<()> {
+    if witness::handle_subpoena_interaction(&data.mongo, &ctx.http, component).await? {
+        return Ok(());
+    }
+    if plea::handle_plea_interaction(&data.mongo, &ctx.http, component).await? {
+        return Ok(());
+    }
+    if appearance::handle_default_judgment_interaction(&data.mongo, &ctx.http, component).await? {
+        return Ok(());
+    }
+    gallery::handle_watch_interaction(&data.mongo, &ctx.http, component).await?;
+    Ok(())
+}
+
 pub async fn error_handler(error: poise::FrameworkError<'_, Handler, Report>) {
     match error {
         poise::FrameworkError::MissingUserPermissions { ctx, .. } => {
@@ -432,6 +2890,16 @@ pub async fn error_handler(error: poise::FrameworkError<'_, Handler, Report>) {
                 .say("du chasch de command nur uf emene serve nutze!")
                 .await;
         }
+        // the only check, see `command_check` in main
+        poise::FrameworkError::CommandCheckFailed { ctx, error: None } => {
+            let _ = ctx
+                .send(|reply| {
+                    reply
+                        .content("de bot startet grad neu, probiers in es paar sekunde nomal")
+                        .ephemeral(true)
+                })
+                .await;
+        }
         err => {
             error!(?err, "Error during command execution");
         }
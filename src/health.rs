@@ -0,0 +1,250 @@
+use std::{
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use poise::{serenity::gateway::ConnectionStage, serenity_prelude::ShardManager};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
+use tracing::{debug, error, info};
+
+use crate::{Mongo, WrapErr};
+
+/// Requests are a single line, anything slower than this is not a health check.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often the shutdown looks whether the commands have finished, and commands whether the
+/// shutdown has started.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the bot is doing right now, for the health endpoints and the shutdown.
+#[derive(Debug, Default)]
+pub struct Health {
+    ready: AtomicBool,
+    shutting_down: AtomicBool,
+    running_commands: AtomicUsize,
+}
+
+impl Health {
+    /// The bot is set up and handles commands.
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    pub fn set_shutting_down(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst) && !self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Counts the command as running until the guard is dropped. Returns `None` once the shutdown
+    /// has started, new commands are not handled anymore then.
+    pub fn command_started(self: &Arc<Self>) -> Option<CommandGuard> {
+        // counted before looking, so the shutdown either waits for it or it is rejected
+        self.running_commands.fetch_add(1, Ordering::SeqCst);
+        let guard = CommandGuard(self.clone());
+        (!self.shutting_down.load(Ordering::SeqCst)).then_some(guard)
+    }
+
+    /// Completes once the shutdown has started.
+    async fn shutdown_started(&self) {
+        while !self.shutting_down.load(Ordering::SeqCst) {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Waits for `future` unless the shutdown starts first, then returns `None`. For commands
+    /// waiting for a button, so they don't hold up the shutdown.
+    pub async fn until_shutdown<T>(&self, future: impl Future<Output = Option<T>>) -> Option<T> {
+        tokio::select! {
+            value = future => value,
+            _ = self.shutdown_started() => None,
+        }
+    }
+
+    pub fn running_commands(&self) -> usize {
+        self.running_commands.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no command is running anymore. Returns `false` if they took too long.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let idle = async {
+            while self.running_commands() > 0 {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, idle).await.is_ok()
+    }
+}
+
+/// A running command, see `Health::command_started`.
+pub struct CommandGuard(Arc<Health>);
+
+impl Drop for CommandGuard {
+    fn drop(&mut self) {
+        self.0.running_commands.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Whether every shard is connected to the gateway.
+async fn gateway_connected(shard_manager: &Mutex<ShardManager>) -> bool {
+    let runners = shard_manager.lock().await.runners.clone();
+    let runners = runners.lock().await;
+    !runners.is_empty()
+        && runners
+            .values()
+            .all(|runner| runner.stage == ConnectionStage::Connected)
+}
+
+async fn mongo_reachable(mongo: &Mongo) -> bool {
+    matches!(
+        tokio::time::timeout(PING_TIMEOUT, mongo.ping()).await,
+        Ok(Ok(()))
+    )
+}
+
+/// The status line and the body of the response to a request for `path`.
+async fn respond(
+    path: &str,
+    health: &Health,
+    mongo: &Mongo,
+    shard_manager: &Mutex<ShardManager>,
+) -> (&'static str, &'static str) {
+    match path {
+        "/healthz" => {
+            if !gateway_connected(shard_manager).await {
+                ("503 Service Unavailable", "gateway not connected")
+            } else if !mongo_reachable(mongo).await {
+                ("503 Service Unavailable", "mongodb not reachable")
+            } else {
+                ("200 OK", "ok")
+            }
+        }
+        "/readyz" => {
+            if health.is_ready() {
+                ("200 OK", "ready")
+            } else {
+                ("503 Service Unavailable", "not ready")
+            }
+        }
+        _ => ("404 Not Found", "not found"),
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    health: &Health,
+    mongo: &Mongo,
+    shard_manager: &Mutex<ShardManager>,
+) -> Result<()> {
+    let mut buf = [0; 1024];
+    let read = tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut buf))
+        .await
+        .wrap_err("request timed out")?
+        .wrap_err("read request")?;
+
+    // only the request line matters, e.g. `GET /healthz HTTP/1.1`
+    let request = String::from_utf8_lossy(&buf[..read]);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(path)) => respond(path, health, mongo, shard_manager).await,
+        _ => ("400 Bad Request", "bad request"),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .wrap_err("write response")?;
+    stream.shutdown().await.wrap_err("close connection")
+}
+
+/// Serves `/healthz`, whether the bot is connected to discord and mongodb, and `/readyz`,
+/// whether it handles commands.
+pub async fn serve(
+    addr: SocketAddr,
+    health: Arc<Health>,
+    mongo: Mongo,
+    shard_manager: Arc<Mutex<ShardManager>>,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .wrap_err_with(|| format!("bind health server to {addr}"))?;
+
+    info!(%addr, "Serving health checks");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                error!(?err, "Failed to accept health check connection");
+                continue;
+            }
+        };
+
+        let health = health.clone();
+        let mongo = mongo.clone();
+        let shard_manager = shard_manager.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, &health, &mongo, &shard_manager).await {
+                debug!(?err, %peer, "Failed to answer health check");
+            }
+        });
+    }
+}
+
+/// Asks the health server of a running bot at `addr` whether it is healthy, for the healthcheck
+/// of the container.
+pub async fn check(mut addr: SocketAddr) -> Result<()> {
+    // the server listens on every interface, but only the local one can be connected to
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+
+    let request = async {
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .wrap_err_with(|| format!("connect to {addr}"))?;
+        stream
+            .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .wrap_err("send request")?;
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .wrap_err("read response")?;
+        Ok::<_, color_eyre::Report>(response)
+    };
+
+    let response = tokio::time::timeout(REQUEST_TIMEOUT + PING_TIMEOUT, request)
+        .await
+        .wrap_err("health check timed out")??;
+
+    if response.starts_with("HTTP/1.1 200") {
+        Ok(())
+    } else {
+        let body = response.split("\r\n\r\n").nth(1).unwrap_or_default();
+        Err(eyre!("unhealthy: {body}"))
+    }
+}
@@ -1,17 +1,30 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use color_eyre::Result;
-use mongodb::bson::{doc, Uuid};
+use color_eyre::{eyre::ContextCompat, Result};
+use mongodb::{
+    bson,
+    bson::{doc, DateTime, Uuid},
+};
 use poise::{
     serenity::model::prelude::*,
-    serenity_prelude::{CreateMessage, Http},
+    serenity_prelude::{CreateEmbed, CreateMessage, Http},
 };
 use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
 use crate::{
+    achievements::{self, CourtEvent},
+    discord::{
+        discord_error_code, retry, with_rollback, SetupStep, UNKNOWN_CHANNEL, UNKNOWN_MEMBER,
+    },
+    gallery::{self, room_overwrites, Visibility},
     handler::Response,
-    model::{CourtRoom, SnowflakeId},
+    model::{CourtRoom, SnowflakeId, State},
+    plea::{self, Plea},
+    procedure::{CaseLogEntry, Contempt},
+    room::{RoomAllocator, RoomStore},
+    venue::{CourtMode, Venue},
+    witness::Witness,
     Mongo, WrapErr,
 };
 
@@ -25,7 +38,141 @@ pub struct Lawsuit {
     pub judge: SnowflakeId,
     pub reason: String,
     pub verdict: Option<String>,
+    pub guilty: Option<bool>,
     pub court_room: SnowflakeId,
+    pub opened_at: Option<DateTime>,
+    pub closed_at: Option<DateTime>,
+    pub outcome: Option<LawsuitOutcome>,
+    /// The participant who may speak, everyone else except the judge is muted.
+    #[serde(default)]
+    pub floor: Option<SnowflakeId>,
+    #[serde(default)]
+    pub contempts: Vec<Contempt>,
+    #[serde(default)]
+    pub log: Vec<CaseLogEntry>,
+    #[serde(default)]
+    pub witnesses: Vec<Witness>,
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// The entry of the lawsuit in the docket channel.
+    #[serde(default)]
+    pub docket_message: Option<SnowflakeId>,
+    #[serde(default)]
+    pub plea: Option<Plea>,
+    /// When the default plea counts if the accused hasn't entered one.
+    #[serde(default)]
+    pub plea_deadline: Option<DateTime>,
+    /// Whether the accused has posted in the court room or entered a plea.
+    #[serde(default)]
+    pub appeared: bool,
+    /// When the judge is offered a default judgment if the accused hasn't appeared.
+    #[serde(default)]
+    pub appearance_deadline: Option<DateTime>,
+    #[serde(default)]
+    pub default_judgment_offered: bool,
+    /// When the lawsuit was deleted by the retention policy, it is purged some time later.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime>,
+}
+
+/// How a lawsuit has ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LawsuitOutcome {
+    Verdict,
+    Withdrawn,
+    Dismissed { reason: String },
+    Settled,
+}
+
+/// A lawsuit that was closed in the database, the cleanup afterwards might have failed.
+#[derive(Debug)]
+pub enum Closed {
+    Cleanly,
+    /// The lawsuit is closed, but the venue could not be cleaned up completely.
+    WithWarning(Response),
+}
+
+impl Closed {
+    /// The warning as a response, for callers that only report it.
+    pub fn into_result(self) -> Result<(), Response> {
+        match self {
+            Closed::Cleanly => Ok(()),
+            Closed::WithWarning(response) => Err(response),
+        }
+    }
+}
+
+/// Discord allows up to 100 characters for thread names.
+const MAX_THREAD_NAME_LENGTH: usize = 100;
+
+/// An order of the judge for the voice channel of the court room.
+#[derive(Debug, Clone, Copy)]
+pub enum VoiceOrder {
+    Silence,
+    Speak,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LawsuitSide {
+    Plaintiff,
+    Accused,
+}
+
+impl Lawsuit {
+    pub fn is_closed(&self) -> bool {
+        // lawsuits from before outcomes were recorded only have a verdict
+        self.outcome.is_some() || self.verdict.is_some()
+    }
+
+    pub fn side_of(&self, user_id: SnowflakeId) -> Option<LawsuitSide> {
+        if self.plaintiff == user_id {
+            Some(LawsuitSide::Plaintiff)
+        } else if self.accused == user_id {
+            Some(LawsuitSide::Accused)
+        } else {
+            None
+        }
+    }
+
+    pub fn lawyer(&self, side: LawsuitSide) -> Option<SnowflakeId> {
+        match side {
+            LawsuitSide::Plaintiff => self.plaintiff_lawyer,
+            LawsuitSide::Accused => self.accused_lawyer,
+        }
+    }
+
+    /// Everyone who has access to the court room during the lawsuit.
+    pub fn participants(&self) -> Vec<SnowflakeId> {
+        let mut participants: Vec<_> = [
+            Some(self.accused),
+            self.accused_lawyer,
+            Some(self.plaintiff),
+            self.plaintiff_lawyer,
+            Some(self.judge),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.testifying_witnesses())
+        .collect();
+        participants.sort_by_key(|user| user.0);
+        participants.dedup();
+        participants
+    }
+
+    pub fn is_participant(&self, user_id: SnowflakeId) -> bool {
+        self.participants().contains(&user_id)
+    }
+}
+
+/// Changes to the participants or the reason of an ongoing lawsuit.
+#[derive(Debug, Default)]
+pub struct LawsuitEdit {
+    pub judge: Option<SnowflakeId>,
+    /// `Some(None)` removes the lawyer.
+    pub plaintiff_lawyer: Option<Option<SnowflakeId>>,
+    pub accused_lawyer: Option<Option<SnowflakeId>>,
+    pub reason: Option<String>,
 }
 
 pub struct LawsuitCtx {
@@ -36,63 +183,154 @@ pub struct LawsuitCtx {
 }
 
 impl LawsuitCtx {
-    pub async fn initialize(mut self) -> Result<Response> {
+    /// Opens the lawsuit in a court room. Everything is set up before this returns, if a step
+    /// fails, the lawsuit is rolled back and the response explains what went wrong.
+    pub async fn initialize(
+        mut self,
+        allocator: &RoomAllocator,
+    ) -> Result<Result<Response, Response>> {
+        match self.try_initialize(allocator).await {
+            Ok(response) => Ok(response),
+            Err(err) => match SetupStep::describe_failure(&err) {
+                Some(response) => {
+                    error!(?err, "Failed to set up lawsuit");
+                    Ok(Err(response))
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    async fn try_initialize(
+        &mut self,
+        allocator: &RoomAllocator,
+    ) -> Result<Result<Response, Response>> {
+        for user in self.lawsuit.participants() {
+            if let Err(err) = self.guild_id.member(&self.http, user).await {
+                if discord_error_code(&err) == Some(UNKNOWN_MEMBER) {
+                    return Ok(Err(Response(format!("<@{user}> isch nöd uf dem server"))));
+                }
+                return Err(err).wrap_err("fetch participant");
+            }
+        }
+
         let state = self
             .mongo_client
             .find_or_insert_state(self.guild_id.into())
             .await?;
 
-        let free_room = state
-            .court_rooms
-            .iter()
-            .find(|r| !r.ongoing_lawsuit)
-            .cloned();
+        let venue = match &state.court_mode {
+            CourtMode::Rooms => match self.claim_room(allocator, &state).await? {
+                Ok(room) => Venue::Room(room),
+                Err(response) => return Ok(Err(response)),
+            },
+            CourtMode::Threads { .. } if self.lawsuit.visibility == Visibility::Public => {
+                return Ok(Err(Response(
+                    "prozess i threads sind immer privat".to_string(),
+                )))
+            }
+            CourtMode::Threads { channel_id } => {
+                Venue::Thread(self.create_thread(*channel_id).await?)
+            }
+        };
 
-        let room = match (free_room, &state.court_category) {
-            (Some(room), _) => room,
-            (None, Some(category)) => {
-                // create room
+        let channel_id = venue.channel_id();
+        self.lawsuit.court_room = channel_id;
+        let now = DateTime::now();
+        self.lawsuit.plea_deadline = Some(state.plea.deadline(now));
+        self.lawsuit.appearance_deadline = state.appearance.deadline(now);
 
-                let result = self
-                    .create_room(state.court_rooms.len(), *category)
-                    .await
-                    .wrap_err("create new room")?;
+        if let Err(response) = self.setup(&venue, state.spectator_role).await? {
+            return Ok(Err(response));
+        }
 
-                match result {
-                    Err(res) => return Ok(res),
-                    Ok(room) => room,
-                }
-            }
-            (None, None) => return Ok(Response(
-                "Zuerst eine Kategorie für die Gerichtsräume festlegen mit `/lawsuit set_category`".to_string(),
-            )),
-        };
+        if let Err(err) = self.update_docket().await {
+            error!(?err, "Failed to announce lawsuit on the docket");
+        }
 
-        let result = self
-            .send_process_open_message(&self.http, self.guild_id, &room)
+        Ok(Ok(Response(format!(
+            "ha eine ufgmacht im channel <#{}>",
+            channel_id
+        ))))
+    }
+
+    async fn claim_room(
+        &self,
+        allocator: &RoomAllocator,
+        state: &State,
+    ) -> Result<Result<CourtRoom, Response>> {
+        let result = allocator
+            .allocate(&self.mongo_client, self.guild_id.into(), |room_number| async move {
+                match state.court_category {
+                    Some(category) => {
+                        self.create_room(
+                            room_number,
+                            category,
+                            state.voice_rooms,
+                            state.spectator_role,
+                        )
+                        .await
+                    }
+                    None => Ok(Err(Response(
+                        "Zuerst eine Kategorie für die Gerichtsräume festlegen mit `/lawsuit set_category`".to_string(),
+                    ))),
+                }
+            })
             .await
-            .wrap_err("send process open message")?;
+            .wrap_err("allocate court room")?;
 
-        if let Err(response) = result {
-            return Ok(response);
+        let room = match result {
+            Ok(room) => room,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        // rooms from before voice rooms were enabled don't have a voice channel yet
+        if state.voice_rooms && room.voice_channel_id.is_none() {
+            return match self.add_voice_channel(room.clone()).await {
+                Ok(room) => Ok(Ok(room)),
+                Err(err) => {
+                    let released = match self
+                        .mongo_client
+                        .release_room(self.guild_id.into(), room.channel_id)
+                        .await
+                    {
+                        Ok(()) => true,
+                        Err(err) => {
+                            error!(?err, "Failed to release court room");
+                            false
+                        }
+                    };
+                    Err(with_rollback(err, released))
+                }
+            };
         }
 
-        let channel_id = room.channel_id;
-        self.lawsuit.court_room = channel_id;
+        Ok(Ok(room))
+    }
 
-        tokio::spawn(async move {
-            if let Err(err) = self.setup(room).await {
-                error!(?err, "Error setting up lawsuit");
-            }
-        });
+    async fn create_thread(&self, channel_id: SnowflakeId) -> Result<SnowflakeId> {
+        let name: String = format!("Prozess: {}", self.lawsuit.reason)
+            .chars()
+            .take(MAX_THREAD_NAME_LENGTH)
+            .collect();
 
-        Ok(Response(format!(
-            "ha eine ufgmacht im channel <#{}>",
-            channel_id
-        )))
+        let thread = ChannelId::from(channel_id)
+            .create_private_thread(&self.http, |thread| {
+                thread.name(name).kind(ChannelType::PrivateThread)
+            })
+            .await
+            .wrap_err(SetupStep::CreateThread)?;
+
+        info!(guild_id = %self.guild_id, thread_id = %thread.id, "Created new court thread");
+
+        Ok(thread.id.into())
     }
 
-    async fn setup(&self, room: CourtRoom) -> Result<()> {
+    async fn setup(
+        &self,
+        venue: &Venue,
+        spectator_role: Option<SnowflakeId>,
+    ) -> Result<Result<(), Response>> {
         let Self {
             mongo_client,
             http,
@@ -101,42 +339,127 @@ impl LawsuitCtx {
         } = self;
         let guild_id = *guild_id;
 
-        mongo_client.add_lawsuit(guild_id.into(), lawsuit).await?;
-        mongo_client
-            .set_court_room(
-                guild_id.into(),
-                room.channel_id,
-                doc! { "court_rooms.$.ongoing_lawsuit": true },
-            )
-            .await?;
+        if let Err(err) = mongo_client.open_lawsuit(guild_id.into(), lawsuit).await {
+            // nothing was saved, the venue just has to be handed back
+            let released = self.release_venue(venue).await;
+            return Err(with_rollback(
+                err.wrap_err(SetupStep::SaveLawsuit),
+                released,
+            ));
+        }
 
-        async fn assign_role(
-            user: SnowflakeId,
-            http: &Http,
-            guild_id: GuildId,
-            role_id: SnowflakeId,
-        ) -> Result<()> {
-            let mut member = guild_id.member(http, user).await.wrap_err("fetch member")?;
-            member
-                .add_role(http, role_id)
-                .await
-                .wrap_err("add role to member")?;
+        let mut admitted = Vec::new();
+        for user in lawsuit.participants() {
+            if let Err(err) = retry(|| venue.admit(http, guild_id, user)).await {
+                let rolled_back = self.roll_back_setup(venue, &admitted).await;
+                return Err(with_rollback(err, rolled_back));
+            }
+            admitted.push(user);
+        }
 
-            Ok(())
+        // reused court rooms still have the overwrites of their last lawsuit
+        if let Venue::Room(room) = venue {
+            if let Err(err) = retry(|| self.apply_visibility(room, lawsuit.visibility)).await {
+                let rolled_back = self.roll_back_setup(venue, &admitted).await;
+                return Err(with_rollback(
+                    err.wrap_err(SetupStep::SetVisibility),
+                    rolled_back,
+                ));
+            }
         }
-        assign_role(lawsuit.accused, http, guild_id, room.role_id).await?;
-        if let Some(accused_lawyer) = lawsuit.accused_lawyer {
-            assign_role(accused_lawyer, http, guild_id, room.role_id).await?;
+
+        let watchable = lawsuit.visibility == Visibility::Public && spectator_role.is_some();
+        let result = match self.send_process_open_message(http, venue, watchable).await {
+            Ok(Ok(())) => self.send_plea_prompt(http, venue).await,
+            result => result,
         }
-        assign_role(lawsuit.plaintiff, http, guild_id, room.role_id).await?;
-        if let Some(plaintiff_lawyer) = lawsuit.plaintiff_lawyer {
-            assign_role(plaintiff_lawyer, http, guild_id, room.role_id).await?;
+        .wrap_err(SetupStep::SendOpenMessage);
+
+        let rolled_back = if matches!(result, Ok(Ok(()))) {
+            true
+        } else {
+            self.roll_back_setup(venue, &admitted).await
+        };
+        if let Err(response) = result.map_err(|err| with_rollback(err, rolled_back))? {
+            return Ok(Err(response));
         }
-        assign_role(lawsuit.judge, http, guild_id, room.role_id).await?;
 
         info!(?lawsuit, "Created lawsuit");
 
-        Ok(())
+        if let Err(err) = achievements::process_event(
+            mongo_client,
+            http,
+            guild_id,
+            venue.channel_id().into(),
+            CourtEvent::LawsuitFiled(lawsuit),
+        )
+        .await
+        {
+            error!(?err, "Error processing achievements");
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Hands back the venue of a lawsuit that was never saved. Returns `false` if the court room
+    /// stays marked as busy or the thread could not be deleted.
+    async fn release_venue(&self, venue: &Venue) -> bool {
+        let mut released = true;
+
+        if let Venue::Room(room) = venue {
+            if let Err(err) = self
+                .mongo_client
+                .release_room(self.guild_id.into(), room.channel_id)
+                .await
+            {
+                error!(?err, "Failed to release court room");
+                released = false;
+            }
+        }
+
+        if let Err(err) = venue.discard(&self.http).await {
+            error!(?err, "Failed to discard venue");
+            released = false;
+        }
+
+        released
+    }
+
+    /// Undoes a partially set up lawsuit so the court room doesn't stay marked as busy. Returns
+    /// `false` if a step could not be undone.
+    async fn roll_back_setup(&self, venue: &Venue, admitted: &[SnowflakeId]) -> bool {
+        let mut rolled_back = true;
+
+        for &user in admitted {
+            if let Err(err) = retry(|| venue.expel(&self.http, self.guild_id, user)).await {
+                error!(?err, %user, "Failed to remove participant during rollback");
+                rolled_back = false;
+            }
+        }
+
+        if let Venue::Room(room) = venue {
+            if let Err(err) = self.apply_visibility(room, Visibility::Private).await {
+                error!(?err, "Failed to hide court room during rollback");
+                rolled_back = false;
+            }
+        }
+
+        if let Err(err) = self
+            .mongo_client
+            .cancel_lawsuit(self.guild_id.into(), self.lawsuit.id, venue)
+            .await
+        {
+            error!(?err, "Failed to roll back lawsuit");
+            rolled_back = false;
+        }
+
+        if let Err(err) = venue.discard(&self.http).await {
+            error!(?err, "Failed to discard venue");
+            rolled_back = false;
+        }
+
+        info!(lawsuit = ?self.lawsuit, rolled_back, "Rolled back lawsuit setup");
+        rolled_back
     }
 
     pub async fn rule_verdict(
@@ -144,142 +467,460 @@ impl LawsuitCtx {
         permission_override: bool,
         user_id: UserId,
         verdict: String,
-        room: CourtRoom,
-    ) -> Result<Result<(), Response>> {
+        guilty: bool,
+        venue: Venue,
+    ) -> Result<Result<Closed, Response>> {
         if self.lawsuit.judge != user_id.into() && !permission_override {
             return Ok(Err(Response("du häsch kei recht für da!".to_string())));
         }
 
         self.lawsuit.verdict = Some(verdict);
-        let lawsuit = &self.lawsuit;
+        self.lawsuit.guilty = Some(guilty);
 
-        async fn remove_role(
-            user: SnowflakeId,
-            http: &Http,
-            guild_id: GuildId,
-            role_id: SnowflakeId,
-        ) -> Result<()> {
-            let mut member = guild_id.member(http, user).await.wrap_err("fetch member")?;
-            member
-                .remove_role(http, role_id)
-                .await
-                .wrap_err("remove role from member")?;
+        self.close(LawsuitOutcome::Verdict, venue).await
+    }
 
-            Ok(())
+    pub async fn withdraw(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        venue: Venue,
+    ) -> Result<Result<Closed, Response>> {
+        if self.lawsuit.plaintiff != user_id.into() && !permission_override {
+            return Ok(Err(Response(
+                "nur de kläger cha d klag zruggzieh!".to_string(),
+            )));
+        }
+
+        self.close(LawsuitOutcome::Withdrawn, venue).await
+    }
+
+    pub async fn dismiss(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        reason: String,
+        venue: Venue,
+    ) -> Result<Result<Closed, Response>> {
+        if self.lawsuit.judge != user_id.into() && !permission_override {
+            return Ok(Err(Response("du häsch kei recht für da!".to_string())));
         }
 
+        self.close(LawsuitOutcome::Dismissed { reason }, venue)
+            .await
+    }
+
+    /// Closes the lawsuit after both parties have agreed to the settlement.
+    pub async fn settle(&mut self, venue: Venue) -> Result<Result<Closed, Response>> {
+        self.close(LawsuitOutcome::Settled, venue).await
+    }
+
+    async fn close(
+        &mut self,
+        outcome: LawsuitOutcome,
+        venue: Venue,
+    ) -> Result<Result<Closed, Response>> {
+        self.lawsuit.outcome = Some(outcome);
+        self.lawsuit.closed_at = Some(DateTime::now());
+        let lawsuit = &self.lawsuit;
+        let outcome = bson::to_bson(&lawsuit.outcome).wrap_err("invalid bson for outcome")?;
+
         let http = &self.http;
         let guild_id = self.guild_id;
 
-        tokio::try_join!(
-            self.mongo_client.set_court_room(
-                self.guild_id.into(),
-                lawsuit.court_room,
-                doc! { "court_rooms.$.ongoing_lawsuit": false },
-            ),
-            self.mongo_client.set_lawsuit(
-                self.guild_id.into(),
+        let closed = self
+            .mongo_client
+            .close_lawsuit(
+                guild_id.into(),
                 lawsuit.id,
-                doc! { "lawsuits.$.verdict": &lawsuit.verdict },
-            ),
-            remove_role(lawsuit.accused, http, guild_id, room.role_id),
-            remove_role(lawsuit.plaintiff, http, guild_id, room.role_id),
-            remove_role(lawsuit.judge, http, guild_id, room.role_id),
-        )?;
+                lawsuit.court_room,
+                doc! {
+                    "verdict": &lawsuit.verdict,
+                    "guilty": lawsuit.guilty,
+                    "closed_at": lawsuit.closed_at,
+                    "outcome": outcome,
+                },
+            )
+            .await?;
+        if !closed {
+            return Ok(Err(Response(
+                "de prozess isch scho abgschlosse".to_string(),
+            )));
+        }
+
+        // the lawsuit is closed in the database now, failing to announce it or to close the venue
+        // is not fatal, everything is cleaned up anyway
+        let response = match self.send_process_close_message(http, &venue).await {
+            Ok(response) => response,
+            Err(err) => {
+                error!(?err, "Failed to send close message");
+                Err(Response(
+                    "d nachricht zum abschluss han i nöd chöne schicke".to_string(),
+                ))
+            }
+        };
 
-        if let Some(accused_lawyer) = lawsuit.accused_lawyer {
-            remove_role(accused_lawyer, http, guild_id, room.role_id).await?;
+        let mut failed = Vec::new();
+        match venue.close(http, guild_id, &lawsuit.participants()).await {
+            Ok(users) => failed.extend(users.iter().map(|user| format!("<@{user}>"))),
+            Err(err) => {
+                error!(?err, "Failed to close venue");
+                failed.push("em thread".to_string());
+            }
         }
-        if let Some(plaintiff_lawyer) = lawsuit.plaintiff_lawyer {
-            remove_role(plaintiff_lawyer, http, guild_id, room.role_id).await?;
+
+        if let Venue::Room(room) = &venue {
+            let reset = match clear_member_overwrites(http, room.channel_id.into()).await {
+                Ok(()) => self.apply_visibility(room, Visibility::Private).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = reset {
+                error!(?err, "Failed to reset court room permissions");
+                failed.push("em gerichtsruum".to_string());
+            }
         }
 
-        let response = self
-            .send_process_close_message(http, guild_id, &room)
-            .await?;
+        if let Err(err) = self.reset_voice_channel(&venue).await {
+            error!(?err, "Failed to reset voice channel");
+            failed.push("em sprachkanal".to_string());
+        }
 
         info!(?lawsuit, "Closed lawsuit");
 
-        if let Err(response) = response {
-            return Ok(Err(response));
+        if let Err(err) = achievements::process_event(
+            &self.mongo_client,
+            http,
+            guild_id,
+            venue.channel_id().into(),
+            CourtEvent::LawsuitClosed(lawsuit),
+        )
+        .await
+        {
+            error!(?err, "Error processing achievements");
         }
 
-        Ok(Ok(()))
+        if let Err(err) = self.update_docket().await {
+            error!(?err, "Failed to update docket");
+        }
+
+        let mut problems = Vec::new();
+        if let Err(Response(response)) = response {
+            problems.push(response);
+        }
+        if !failed.is_empty() {
+            problems.push(format!(
+                "bi {} han i d berechtigunge nöd chöne zruggsetze",
+                failed.join(", ")
+            ));
+        }
+
+        if !problems.is_empty() {
+            return Ok(Ok(Closed::WithWarning(Response(format!(
+                "de prozess isch abgschlosse, aber {}",
+                problems.join(" und ")
+            )))));
+        }
+
+        Ok(Ok(Closed::Cleanly))
     }
 
-    async fn send_process_open_message(
+    /// Mutes or unmutes everyone or a single participant in the voice channel of the court room.
+    pub async fn order_voice(
         &self,
-        http: &Http,
-        guild_id: GuildId,
-        room: &CourtRoom,
+        permission_override: bool,
+        user_id: UserId,
+        order: VoiceOrder,
+        target: Option<SnowflakeId>,
+        venue: &Venue,
     ) -> Result<Result<(), Response>> {
-        self.send_court_message(http, guild_id, room, |msg| {
-            msg.embed(|embed| {
-                let lawsuit = &self.lawsuit;
-                embed
-                    .title("Prozess")
-                    .field("Grund", &lawsuit.reason, false)
-                    .field("Kläger", format!("<@{}>", lawsuit.plaintiff), true)
-                    .field(
-                        "Anwalt des Klägers",
-                        match &lawsuit.plaintiff_lawyer {
-                            Some(lawyer) => format!("<@{}>", lawyer),
-                            None => "Keinen".to_string(),
-                        },
-                        true,
-                    )
-                    .field("Angeklagter", format!("<@{}>", lawsuit.accused), true)
-                    .field(
-                        "Anwalt des Angeklagten",
-                        match &lawsuit.accused_lawyer {
-                            Some(lawyer) => format!("<@{}>", lawyer),
-                            None => "Keinen".to_string(),
+        if self.lawsuit.judge != user_id.into() && !permission_override {
+            return Ok(Err(Response("du häsch kei recht für da!".to_string())));
+        }
+
+        let (room, voice_channel) = match venue {
+            Venue::Room(
+                room @ CourtRoom {
+                    voice_channel_id: Some(id),
+                    ..
+                },
+            ) => (room, ChannelId::from(*id)),
+            _ => {
+                return Ok(Err(Response(
+                    "de gerichtsruum hät kein sprachkanal".to_string(),
+                )))
+            }
+        };
+
+        let http = &self.http;
+        let speak = matches!(order, VoiceOrder::Speak);
+
+        match target {
+            Some(target) => {
+                if !self.lawsuit.is_participant(target) {
+                    return Ok(Err(Response(format!(
+                        "<@{target}> isch nöd am prozess beteiligt"
+                    ))));
+                }
+
+                let (allow, deny) = if speak {
+                    (Permissions::SPEAK, Permissions::empty())
+                } else {
+                    (Permissions::empty(), Permissions::SPEAK)
+                };
+                voice_channel
+                    .create_permission(
+                        http,
+                        &PermissionOverwrite {
+                            allow,
+                            deny,
+                            kind: PermissionOverwriteType::Member(target.into()),
                         },
-                        true,
                     )
-                    .field("Richter", format!("<@{}>", lawsuit.judge), true)
+                    .await
+                    .wrap_err("set voice permission of participant")?;
+            }
+            None => {
+                clear_member_overwrites(http, voice_channel).await?;
+
+                voice_channel
+                    .create_permission(http, &court_role_voice_overwrite(room.role_id, speak))
+                    .await
+                    .wrap_err("set voice permission of court role")?;
+
+                // the judge has to be able to lift the order again
+                if !speak {
+                    voice_channel
+                        .create_permission(
+                            http,
+                            &PermissionOverwrite {
+                                allow: Permissions::SPEAK,
+                                deny: Permissions::empty(),
+                                kind: PermissionOverwriteType::Member(self.lawsuit.judge.into()),
+                            },
+                        )
+                        .await
+                        .wrap_err("set voice permission of judge")?;
+                }
+            }
+        }
+
+        info!(?order, ?target, "Changed voice permissions");
+
+        Ok(Ok(()))
+    }
+
+    /// Lets everyone speak again and removes all orders of the judge.
+    async fn reset_voice_channel(&self, venue: &Venue) -> Result<()> {
+        let (room, voice_channel) = match venue {
+            Venue::Room(
+                room @ CourtRoom {
+                    voice_channel_id: Some(id),
+                    ..
+                },
+            ) => (room, ChannelId::from(*id)),
+            _ => return Ok(()),
+        };
+
+        clear_member_overwrites(&self.http, voice_channel).await?;
+        voice_channel
+            .create_permission(&self.http, &court_role_voice_overwrite(room.role_id, true))
+            .await
+            .wrap_err("reset voice permission of court role")?;
+
+        Ok(())
+    }
+
+    /// Lets the lawyer represent one side of the ongoing lawsuit, replacing the previous one.
+    pub async fn hire_lawyer(
+        &mut self,
+        side: LawsuitSide,
+        lawyer: SnowflakeId,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        let previous = self.lawsuit.lawyer(side);
+        let field = match side {
+            LawsuitSide::Plaintiff => {
+                self.lawsuit.plaintiff_lawyer = Some(lawyer);
+                "lawsuits.$.plaintiff_lawyer"
+            }
+            LawsuitSide::Accused => {
+                self.lawsuit.accused_lawyer = Some(lawyer);
+                "lawsuits.$.accused_lawyer"
+            }
+        };
+
+        let hired = self
+            .mongo_client
+            .set_open_lawsuit(
+                self.guild_id.into(),
+                self.lawsuit.id,
+                self.lawsuit.court_room,
+                doc! { field: lawyer },
+            )
+            .await?;
+        if !hired {
+            return Ok(Err(Response(
+                "de prozess isch scho abgschlosse".to_string(),
+            )));
+        }
+
+        self.replace_participant(previous, Some(lawyer), venue)
+            .await?;
+
+        info!(lawsuit = ?self.lawsuit, ?side, %lawyer, "Hired lawyer");
+
+        Ok(Ok(()))
+    }
+
+    /// Applies the changes to the ongoing lawsuit and posts the amended case in the court room.
+    pub async fn edit(&mut self, edit: LawsuitEdit, venue: &Venue) -> Result<Result<(), Response>> {
+        let mut edited = self.lawsuit.clone();
+        let mut update = doc! {};
+        let mut changes = Vec::new();
+        let mut replaced = Vec::new();
+
+        if let Some(judge) = edit.judge {
+            if judge != edited.judge {
+                changes.push(format!("Richter: <@{}> → <@{}>", edited.judge, judge));
+                replaced.push((Some(edited.judge), Some(judge)));
+                update.insert("lawsuits.$.judge", judge);
+                edited.judge = judge;
+            }
+        }
+        if let Some(lawyer) = edit.plaintiff_lawyer {
+            if lawyer != edited.plaintiff_lawyer {
+                changes.push(format!(
+                    "Anwalt des Klägers: {} → {}",
+                    mention_or_none(edited.plaintiff_lawyer),
+                    mention_or_none(lawyer)
+                ));
+                replaced.push((edited.plaintiff_lawyer, lawyer));
+                update.insert("lawsuits.$.plaintiff_lawyer", lawyer);
+                edited.plaintiff_lawyer = lawyer;
+            }
+        }
+        if let Some(lawyer) = edit.accused_lawyer {
+            if lawyer != edited.accused_lawyer {
+                changes.push(format!(
+                    "Anwalt des Angeklagten: {} → {}",
+                    mention_or_none(edited.accused_lawyer),
+                    mention_or_none(lawyer)
+                ));
+                replaced.push((edited.accused_lawyer, lawyer));
+                update.insert("lawsuits.$.accused_lawyer", lawyer);
+                edited.accused_lawyer = lawyer;
+            }
+        }
+        if let Some(reason) = edit.reason {
+            if reason != edited.reason {
+                changes.push(format!("Grund: {} → {}", edited.reason, reason));
+                update.insert("lawsuits.$.reason", &reason);
+                edited.reason = reason;
+            }
+        }
+
+        if changes.is_empty() {
+            return Ok(Err(Response("es het sich nüt gänderet".to_string())));
+        }
+
+        if let Err(response) = check_officials(&edited) {
+            return Ok(Err(response));
+        }
+
+        let edited_open = self
+            .mongo_client
+            .set_open_lawsuit(self.guild_id.into(), edited.id, edited.court_room, update)
+            .await?;
+        if !edited_open {
+            return Ok(Err(Response(
+                "de prozess isch scho abgschlosse".to_string(),
+            )));
+        }
+        self.lawsuit = edited;
+
+        for (previous, new) in replaced {
+            self.replace_participant(previous, new, venue).await?;
+        }
+
+        info!(lawsuit = ?self.lawsuit, ?changes, "Edited lawsuit");
+
+        if let Err(err) = self.update_docket().await {
+            error!(?err, "Failed to update docket");
+        }
+
+        self.send_court_message(&self.http, venue, |msg| {
+            msg.embed(|embed| {
+                case_fields(embed.title("Prozess geändert"), &self.lawsuit).field(
+                    "Änderungen",
+                    changes.join("\n"),
+                    false,
+                )
             })
         })
         .await
     }
 
+    /// Gives the new participant access to the court room and takes it away from the previous one,
+    /// unless they are still part of the lawsuit in another role.
+    async fn replace_participant(
+        &self,
+        previous: Option<SnowflakeId>,
+        new: Option<SnowflakeId>,
+        venue: &Venue,
+    ) -> Result<()> {
+        if let Some(new) = new {
+            venue.admit(&self.http, self.guild_id, new).await?;
+        }
+        if let Some(previous) = previous {
+            if !self.lawsuit.is_participant(previous) {
+                venue.expel(&self.http, self.guild_id, previous).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_process_open_message(
+        &self,
+        http: &Http,
+        venue: &Venue,
+        watchable: bool,
+    ) -> Result<Result<(), Response>> {
+        self.send_court_message(http, venue, |msg| {
+            msg.embed(|embed| open_embed(embed, &self.lawsuit));
+            if watchable {
+                msg.components(gallery::watch_button);
+            }
+            msg
+        })
+        .await
+    }
+
+    /// Asks the accused for their plea, right after the opening embed.
+    async fn send_plea_prompt(&self, http: &Http, venue: &Venue) -> Result<Result<(), Response>> {
+        let lawsuit = &self.lawsuit;
+        let deadline = lawsuit
+            .plea_deadline
+            .map(|deadline| format!(", bis <t:{}:R>", deadline.timestamp_millis() / 1000))
+            .unwrap_or_default();
+
+        self.send_court_message(http, venue, |msg| {
+            msg.content(format!(
+                "<@{}>, wie plädiersch du{deadline}? Süsch gilt **{}**.",
+                lawsuit.accused,
+                Plea::DEFAULT
+            ))
+            .components(|c| plea::plea_buttons(c, lawsuit.id))
+        })
+        .await
+    }
+
     async fn send_process_close_message(
         &self,
         http: &Http,
-        guild_id: GuildId,
-        room: &CourtRoom,
+        venue: &Venue,
     ) -> Result<Result<(), Response>> {
-        self.send_court_message(http, guild_id, room, |msg| {
-            msg.embed(|embed| {
-                let lawsuit = &self.lawsuit;
-                embed
-                    .title("Prozess abgeschlossen")
-                    .field("Grund", &lawsuit.reason, false)
-                    .field("Kläger", format!("<@{}>", lawsuit.plaintiff), true)
-                    .field(
-                        "Anwalt des Klägers",
-                        match &lawsuit.plaintiff_lawyer {
-                            Some(lawyer) => format!("<@{}>", lawyer),
-                            None => "Keinen".to_string(),
-                        },
-                        true,
-                    )
-                    .field("Angeklagter", format!("<@{}>", lawsuit.accused), true)
-                    .field(
-                        "Anwalt des Angeklagten",
-                        match &lawsuit.accused_lawyer {
-                            Some(lawyer) => format!("<@{}>", lawyer),
-                            None => "Keinen".to_string(),
-                        },
-                        true,
-                    )
-                    .field("Richter", format!("<@{}>", lawsuit.judge), true)
-                    .field(
-                        "Urteil",
-                        lawsuit.verdict.clone().expect("no verdict found!"),
-                        true,
-                    )
-            })
+        self.send_court_message(http, venue, |msg| {
+            msg.embed(|embed| close_embed(embed, &self.lawsuit))
         })
         .await
     }
@@ -287,47 +928,35 @@ impl LawsuitCtx {
     async fn send_court_message<'a, F>(
         &self,
         http: &Http,
-        guild_id: GuildId,
-        room: &CourtRoom,
+        venue: &Venue,
         embed_builder: F,
     ) -> Result<Result<(), Response>>
     where
         for<'b> F: FnOnce(&'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a>,
     {
-        let channels = guild_id
-            .to_partial_guild(http)
-            .await
-            .wrap_err("fetch partial guild")?
-            .channels(http)
-            .await
-            .wrap_err("fetch channels")?;
-        let channel = channels.get(&room.channel_id.into());
+        let result = ChannelId::from(venue.channel_id())
+            .send_message(http, embed_builder)
+            .await;
 
-        match channel {
-            Some(channel) => {
-                channel
-                    .id
-                    .send_message(http, embed_builder)
-                    .await
-                    .wrap_err("send message")?;
-            }
-            None => {
+        match result {
+            Ok(_) => Ok(Ok(())),
+            Err(err) if discord_error_code(&err) == Some(UNKNOWN_CHANNEL) => {
                 // todo: remove the court room from the db
-                return Ok(Err(Response(
+                Ok(Err(Response(
                     "i ha de channel für de prozess nöd gfunde".to_string(),
-                )));
+                )))
             }
+            Err(err) => Err(err).wrap_err("send message"),
         }
-
-        Ok(Ok(()))
     }
 
     async fn create_room(
         &self,
-        room_len: usize,
+        room_number: usize,
         category_id: SnowflakeId,
+        voice: bool,
+        spectator_role: Option<SnowflakeId>,
     ) -> Result<Result<CourtRoom, Response>> {
-        let room_number = room_len + 1;
         let room_name = format!("gerichtsraum-{room_number}");
         let role_name = format!("Gerichtsprozess {room_number}");
 
@@ -337,64 +966,351 @@ impl LawsuitCtx {
             .await
             .wrap_err("fetch partial guild")?;
 
-        let role_id = match guild.role_by_name(&role_name) {
-            Some(role) => role.id,
+        let (role_id, created_role) = match guild.role_by_name(&role_name) {
+            Some(role) => (role.id, false),
             None => {
-                guild
+                let role = guild
                     .create_role(&self.http, |role| {
                         role.name(role_name).permissions(Permissions::empty())
                     })
                     .await
-                    .wrap_err("create role")?
-                    .id
+                    .wrap_err(SetupStep::CreateRole)?;
+                (role.id, true)
             }
         };
+        let created_role = created_role.then_some(role_id);
 
-        let channels = guild
+        let channels = match guild
             .channels(&self.http)
             .await
-            .wrap_err("fetching channels")?;
+            .wrap_err("fetching channels")
+        {
+            Ok(channels) => channels,
+            Err(err) => {
+                let rolled_back = self.remove_created(created_role, None).await;
+                return Err(with_rollback(err, rolled_back));
+            }
+        };
 
-        let channel_id = match channels.values().find(|c| c.name() == room_name) {
-            Some(channel) => {
-                if channel.parent_id != Some(category_id.into()) {
-                    return Ok(Err(Response(format!(
-                        "de channel {room_name} isch i de falsche kategorie, man eh"
-                    ))));
+        let (channel_id, created_channel) =
+            match channels.values().find(|c| c.name() == room_name) {
+                Some(channel) => {
+                    if channel.parent_id != Some(category_id.into()) {
+                        self.remove_created(created_role, None).await;
+                        return Ok(Err(Response(format!(
+                            "de channel {room_name} isch i de falsche kategorie, man eh"
+                        ))));
+                    }
+                    (channel.id, false)
+                }
+                None => {
+                    let created = guild
+                        .create_channel(&self.http, |channel| {
+                            channel.name(&room_name).category(category_id).permissions(
+                                room_overwrites(
+                                    self.guild_id,
+                                    role_id.into(),
+                                    self.lawsuit.visibility,
+                                    spectator_role,
+                                ),
+                            )
+                        })
+                        .await
+                        .wrap_err(SetupStep::CreateChannel);
+                    match created {
+                        Ok(channel) => (channel.id, true),
+                        Err(err) => {
+                            let rolled_back = self.remove_created(created_role, None).await;
+                            return Err(with_rollback(err, rolled_back));
+                        }
+                    }
+                }
+            };
+        let created_channel = created_channel.then_some(channel_id);
+
+        let voice_channel_id = if voice {
+            let voice_channel = self
+                .create_voice_channel(
+                    &guild,
+                    &channels,
+                    &room_name,
+                    Some(category_id.into()),
+                    role_id,
+                )
+                .await;
+            match voice_channel {
+                Ok(voice_channel) => Some(voice_channel.into()),
+                Err(err) => {
+                    let rolled_back = self.remove_created(created_role, created_channel).await;
+                    return Err(with_rollback(err, rolled_back));
                 }
-                channel.id
-            }
-            None => {
-                guild
-                    .create_channel(&self.http, |channel| {
-                        channel
-                            .name(room_name)
-                            .category(category_id)
-                            .permissions(vec![PermissionOverwrite {
-                                allow: Permissions::SEND_MESSAGES,
-                                deny: Permissions::empty(),
-                                kind: PermissionOverwriteType::Role(role_id),
-                            }])
-                    })
-                    .await
-                    .wrap_err("create channel")?
-                    .id
             }
+        } else {
+            None
         };
 
         let room = CourtRoom {
             channel_id: channel_id.into(),
-            ongoing_lawsuit: false,
+            ongoing_lawsuit: true,
             role_id: role_id.into(),
+            voice_channel_id,
         };
 
-        self.mongo_client
-            .add_court_room(self.guild_id.into(), &room)
-            .await
-            .wrap_err("add court room to database")?;
-
         info!(guild_id = %self.guild_id, channel_id = %channel_id, "Created new court room");
 
         Ok(Ok(room))
     }
+
+    /// Deletes the role and the channel `create_room` created before a later step failed, so no
+    /// half finished court room is left behind. Returns `false` if something is left.
+    async fn remove_created(&self, role_id: Option<RoleId>, channel_id: Option<ChannelId>) -> bool {
+        let mut removed = true;
+        if let Some(channel_id) = channel_id {
+            if let Err(err) = channel_id.delete(&self.http).await {
+                error!(?err, %channel_id, "Failed to delete unfinished court room");
+                removed = false;
+            }
+        }
+        if let Some(role_id) = role_id {
+            if let Err(err) = self.guild_id.delete_role(&self.http, role_id).await {
+                error!(?err, %role_id, "Failed to delete role of unfinished court room");
+                removed = false;
+            }
+        }
+        removed
+    }
+
+    /// Creates the voice channel of a court room, or reuses it if it already exists.
+    async fn create_voice_channel(
+        &self,
+        guild: &PartialGuild,
+        channels: &HashMap<ChannelId, GuildChannel>,
+        name: &str,
+        category_id: Option<ChannelId>,
+        role_id: RoleId,
+    ) -> Result<ChannelId> {
+        if let Some(channel) = channels
+            .values()
+            .find(|c| c.kind == ChannelType::Voice && c.name() == name)
+        {
+            return Ok(channel.id);
+        }
+
+        let everyone = PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::SPEAK,
+            kind: PermissionOverwriteType::Role(RoleId(self.guild_id.0)),
+        };
+
+        let channel = guild
+            .create_channel(&self.http, |channel| {
+                channel
+                    .name(name)
+                    .kind(ChannelType::Voice)
+                    .permissions(vec![
+                        everyone,
+                        court_role_voice_overwrite(role_id.into(), true),
+                    ]);
+                if let Some(category_id) = category_id {
+                    channel.category(category_id);
+                }
+                channel
+            })
+            .await
+            .wrap_err(SetupStep::CreateVoiceChannel)?;
+
+        info!(guild_id = %self.guild_id, channel_id = %channel.id, "Created new voice channel");
+
+        Ok(channel.id)
+    }
+
+    /// Gives an existing court room a voice channel named like its text channel.
+    async fn add_voice_channel(&self, room: CourtRoom) -> Result<CourtRoom> {
+        let guild = self
+            .guild_id
+            .to_partial_guild(&self.http)
+            .await
+            .wrap_err("fetch partial guild")?;
+        let channels = guild
+            .channels(&self.http)
+            .await
+            .wrap_err("fetching channels")?;
+        let text_channel = channels
+            .get(&room.channel_id.into())
+            .wrap_err("court room channel not found")?;
+
+        let voice_channel = self
+            .create_voice_channel(
+                &guild,
+                &channels,
+                text_channel.name(),
+                text_channel.parent_id,
+                room.role_id.into(),
+            )
+            .await?;
+
+        self.mongo_client
+            .set_room_voice_channel(self.guild_id.into(), room.channel_id, voice_channel.into())
+            .await?;
+
+        Ok(CourtRoom {
+            voice_channel_id: Some(voice_channel.into()),
+            ..room
+        })
+    }
+}
+
+/// Nobody may judge or represent a lawsuit they are a party of, or fill two of its offices.
+fn check_officials(lawsuit: &Lawsuit) -> Result<(), Response> {
+    let parties = [lawsuit.plaintiff, lawsuit.accused];
+    if parties.contains(&lawsuit.judge) {
+        return Err(Response(format!(
+            "<@{}> isch partei im prozess und cha nöd richter si",
+            lawsuit.judge
+        )));
+    }
+
+    let lawyers = [lawsuit.plaintiff_lawyer, lawsuit.accused_lawyer];
+    for lawyer in lawyers.into_iter().flatten() {
+        if parties.contains(&lawyer) {
+            return Err(Response(format!(
+                "<@{}> isch partei im prozess und cha nöd aawalt si",
+                lawyer
+            )));
+        }
+        if lawyer == lawsuit.judge {
+            return Err(Response(format!(
+                "<@{}> isch de richter und cha nöd aawalt si",
+                lawyer
+            )));
+        }
+    }
+    if let [Some(plaintiff_lawyer), Some(accused_lawyer)] = lawyers {
+        if plaintiff_lawyer == accused_lawyer {
+            return Err(Response(format!(
+                "<@{}> cha nöd beidi siite vertrete",
+                plaintiff_lawyer
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn mention_or_none(user: Option<SnowflakeId>) -> String {
+    match user {
+        Some(user) => format!("<@{}>", user),
+        None => "Keinen".to_string(),
+    }
+}
+
+/// The embed announcing a lawsuit, in the court room or elsewhere.
+pub fn open_embed<'a>(embed: &'a mut CreateEmbed, lawsuit: &Lawsuit) -> &'a mut CreateEmbed {
+    case_fields(embed.title("Prozess"), lawsuit)
+}
+
+/// The embed with the outcome of a closed lawsuit, in the court room or elsewhere.
+pub fn close_embed<'a>(embed: &'a mut CreateEmbed, lawsuit: &Lawsuit) -> &'a mut CreateEmbed {
+    let embed = match &lawsuit.outcome {
+        Some(LawsuitOutcome::Verdict) | None => {
+            case_fields(embed.title("Prozess abgeschlossen"), lawsuit)
+                .field(
+                    "Urteil",
+                    lawsuit.verdict.clone().expect("no verdict found!"),
+                    true,
+                )
+                .field(
+                    "Schuldig",
+                    if lawsuit.guilty == Some(true) {
+                        "Ja"
+                    } else {
+                        "Nein"
+                    },
+                    true,
+                )
+        }
+        Some(LawsuitOutcome::Withdrawn) => case_fields(embed.title("Klage zurückgezogen"), lawsuit),
+        Some(LawsuitOutcome::Dismissed { reason }) => {
+            case_fields(embed.title("Klage abgewiesen"), lawsuit).field("Begründung", reason, false)
+        }
+        Some(LawsuitOutcome::Settled) => case_fields(embed.title("Vergleich geschlossen"), lawsuit),
+    };
+
+    if let Some(plea) = lawsuit.plea {
+        embed.field("Plädiert auf", plea, true);
+    }
+
+    embed
+}
+
+fn case_fields<'a>(embed: &'a mut CreateEmbed, lawsuit: &Lawsuit) -> &'a mut CreateEmbed {
+    embed
+        .field("Grund", &lawsuit.reason, false)
+        .field("Kläger", format!("<@{}>", lawsuit.plaintiff), true)
+        .field(
+            "Anwalt des Klägers",
+            mention_or_none(lawsuit.plaintiff_lawyer),
+            true,
+        )
+        .field("Angeklagter", format!("<@{}>", lawsuit.accused), true)
+        .field(
+            "Anwalt des Angeklagten",
+            mention_or_none(lawsuit.accused_lawyer),
+            true,
+        )
+        .field("Richter", format!("<@{}>", lawsuit.judge), true);
+
+    if !lawsuit.witnesses.is_empty() {
+        let witnesses = lawsuit
+            .witnesses
+            .iter()
+            .map(Witness::mention)
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed.field("Zeugen", witnesses, false);
+    }
+
+    embed
+}
+
+/// The voice permissions of the court role. Everyone else may only listen.
+fn court_role_voice_overwrite(role_id: SnowflakeId, speak: bool) -> PermissionOverwrite {
+    let (allow, deny) = if speak {
+        (
+            Permissions::VIEW_CHANNEL | Permissions::CONNECT | Permissions::SPEAK,
+            Permissions::empty(),
+        )
+    } else {
+        (
+            Permissions::VIEW_CHANNEL | Permissions::CONNECT,
+            Permissions::SPEAK,
+        )
+    };
+
+    PermissionOverwrite {
+        allow,
+        deny,
+        kind: PermissionOverwriteType::Role(role_id.into()),
+    }
+}
+
+/// Removes the orders of the judge for single members, in the court room or its voice channel.
+async fn clear_member_overwrites(http: &Http, channel_id: ChannelId) -> Result<()> {
+    let channel = channel_id
+        .to_channel(http)
+        .await
+        .wrap_err("fetch voice channel")?
+        .guild()
+        .wrap_err("voice channel is not in a guild")?;
+
+    for overwrite in channel.permission_overwrites {
+        if let PermissionOverwriteType::Member(_) = overwrite.kind {
+            channel_id
+                .delete_permission(http, overwrite.kind)
+                .await
+                .wrap_err("delete voice permission of member")?;
+        }
+    }
+
+    Ok(())
 }
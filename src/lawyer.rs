@@ -0,0 +1,37 @@
+/// A multiple choice question of the bar exam.
+#[derive(Debug)]
+pub struct BarQuestion {
+    pub question: &'static str,
+    pub answers: &'static [&'static str],
+    pub correct: usize,
+}
+
+pub const BAR_EXAM: &[BarQuestion] = &[
+    BarQuestion {
+        question: "Wer fällt das Urteil in einem Prozess?",
+        answers: &["Der Kläger", "Der Richter", "Der Anwalt", "Karin"],
+        correct: 1,
+    },
+    BarQuestion {
+        question: "Wen vertritt der Anwalt des Angeklagten?",
+        answers: &["Den Kläger", "Den Richter", "Den Angeklagten"],
+        correct: 2,
+    },
+    BarQuestion {
+        question: "Was gilt bis zum Urteil?",
+        answers: &[
+            "Die Unschuldsvermutung",
+            "Die Schuldvermutung",
+            "Das Faustrecht",
+        ],
+        correct: 0,
+    },
+    BarQuestion {
+        question: "Wo findet ein Prozess statt?",
+        answers: &["Im Gefängnis", "Im Gerichtsraum", "In den DMs"],
+        correct: 1,
+    },
+];
+
+/// How many questions have to be answered correctly to pass the bar exam.
+pub const PASSING_SCORE: usize = 3;
@@ -1,12 +1,40 @@
 extern crate core;
 
+mod achievements;
+mod appearance;
+mod chart;
+mod confirm;
+mod discord;
+mod docket;
+mod doctor;
+mod export;
+mod gallery;
 mod handler;
+mod health;
 mod lawsuit;
+mod lawyer;
+mod migration;
 mod model;
+mod plea;
+mod prison;
+mod privacy;
+mod procedure;
+mod record;
+mod retention;
+mod room;
+mod scheduler;
+mod shutdown;
+mod stats;
+mod time;
+mod venue;
+mod witness;
 
-use std::env;
+use std::{env, net::SocketAddr, sync::Arc};
 
-use color_eyre::{eyre::WrapErr, Report, Result};
+use color_eyre::{
+    eyre::{ContextCompat, WrapErr},
+    Report, Result,
+};
 use poise::{
     serenity_prelude as serenity,
     serenity_prelude::{Activity, GatewayIntents, GuildId},
@@ -14,7 +42,9 @@ use poise::{
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
 
-use crate::{handler::Handler, model::Mongo};
+use crate::{
+    handler::Handler, health::Health, model::Mongo, room::RoomAllocator, shutdown::Shutdown,
+};
 
 type Context<'a> = poise::Context<'a, Handler, Report>;
 
@@ -26,6 +56,18 @@ async fn main() -> Result<()> {
 
     let pretty = env::var("PRETTY").is_ok();
 
+    let health_addr = env::var("HEALTH_ADDR")
+        .ok()
+        .map(|addr| addr.parse::<SocketAddr>())
+        .transpose()
+        .wrap_err("HEALTH_ADDR must be an address like 0.0.0.0:8080")?;
+
+    // the healthcheck of the container runs the same binary
+    if env::args().nth(1).as_deref() == Some("healthcheck") {
+        let addr = health_addr.wrap_err("HEALTH_ADDR not found in the environment")?;
+        return health::check(addr).await;
+    }
+
     setup_tracing(pretty);
 
     info!("Starting up...");
@@ -56,7 +98,13 @@ async fn main() -> Result<()> {
 
     let set_global_commands = env::var("SET_GLOBAL").is_ok();
 
-    poise::Framework::build()
+    let health = Arc::new(Health::default());
+    let shutdown = Shutdown::new();
+    let scheduler_signals = shutdown.signals();
+    let setup_health = health.clone();
+    let health_mongo = mongo.clone();
+
+    let framework = poise::Framework::build()
         .token(token)
         .user_data_setup(move |ctx, ready, framework| {
             Box::pin(async move {
@@ -64,6 +112,8 @@ async fn main() -> Result<()> {
                     dev_guild_id,
                     set_global_commands,
                     mongo,
+                    room_allocator: RoomAllocator::default(),
+                    health: setup_health,
                 };
 
                 let commands = &framework.options().commands;
@@ -97,9 +147,18 @@ async fn main() -> Result<()> {
                     }
                 }
 
+                // the signal is only taken here, the shutdown doesn't wait for a scheduler that
+                // never started
+                match scheduler_signals.signal() {
+                    Some(signal) => scheduler::start(data.mongo.clone(), ctx.http.clone(), signal),
+                    None => info!("Shutting down, not starting the scheduler"),
+                }
+
                 ctx.set_activity(Activity::playing("für Recht und Ordnung sorgen"))
                     .await;
 
+                data.health.set_ready();
+
                 info!(name = %ready.user.name, "Bot is connected!");
 
                 Ok(data)
@@ -108,13 +167,31 @@ async fn main() -> Result<()> {
         .options(poise::FrameworkOptions {
             commands: vec![
                 handler::lawsuit::lawsuit(),
+                handler::lawyer::lawyer(),
                 handler::prison::prison(),
+                handler::court::court(),
+                handler::objection::objection(),
+                handler::record::record(),
+                handler::achievements::achievements(),
+                handler::privacy::privacy(),
                 hello(),
             ],
             on_error: |err| Box::pin(async { handler::error_handler(err).await }),
             listener: |ctx, event, ctx2, data| {
                 Box::pin(async move { handler::listener(ctx, event, ctx2, data).await })
             },
+            command_check: Some(|ctx| {
+                Box::pin(async move {
+                    // dropped once the command is done, even if it failed
+                    match ctx.data().health.command_started() {
+                        Some(guard) => {
+                            ctx.set_invocation_data(guard).await;
+                            Ok(true)
+                        }
+                        None => Ok(false),
+                    }
+                })
+            }),
             pre_command: |ctx| {
                 Box::pin(async move {
                     let channel_name = ctx
@@ -140,9 +217,35 @@ async fn main() -> Result<()> {
             ..Default::default()
         })
         .intents(GatewayIntents::non_privileged() | GatewayIntents::GUILD_MEMBERS)
-        .run()
+        .build()
         .await
         .wrap_err("failed to create discord client")?;
+
+    let shard_manager = framework.shard_manager();
+
+    if let Some(addr) = health_addr {
+        let health = health.clone();
+        let shard_manager = shard_manager.clone();
+        tokio::spawn(async move {
+            if let Err(err) = health::serve(addr, health, health_mongo, shard_manager).await {
+                error!(?err, "Health server failed");
+            }
+        });
+    }
+
+    tokio::spawn(shutdown::shutdown_on_signal(
+        shutdown,
+        health,
+        shard_manager,
+    ));
+
+    framework
+        .start()
+        .await
+        .wrap_err("failed to run discord client")?;
+
+    info!("Shut down");
+
     Ok(())
 }
 
@@ -0,0 +1,444 @@
+use color_eyre::Result;
+use mongodb::bson::{doc, Bson, Document};
+use tracing::info;
+
+use crate::{Mongo, WrapErr};
+
+/// The schema version of the documents this version of the bot writes, the version of the last
+/// migration.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// A change to the stored documents of a collection. Documents remember in `schema_version` up to
+/// which version they are migrated, documents without it are from before migrations existed.
+/// New documents start at `SCHEMA_VERSION`.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub collection: &'static str,
+    /// Rewrites a document of the collection. Must leave documents that are already migrated
+    /// alone, a migration that was interrupted runs again on the next start.
+    migrate: fn(&mut Document) -> Result<()>,
+}
+
+/// Every migration, ordered by version. Never change or remove a migration that was released,
+/// add a new one with the next version and bump `SCHEMA_VERSION`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "verdict_outcomes",
+        collection: "state",
+        migrate: verdict_outcomes,
+    },
+    Migration {
+        version: 2,
+        name: "state_defaults",
+        collection: "state",
+        migrate: fill_state_defaults,
+    },
+    Migration {
+        version: 3,
+        name: "prison_defaults",
+        collection: "prison",
+        migrate: fill_prison_defaults,
+    },
+];
+
+impl Migration {
+    /// Migrates the document and marks it as migrated.
+    pub fn apply(&self, document: &mut Document) -> Result<()> {
+        (self.migrate)(document)?;
+        document.insert("schema_version", self.version);
+        Ok(())
+    }
+}
+
+/// Lawsuits closed before outcomes were recorded only have a verdict, give them the outcome the
+/// statistics count.
+fn verdict_outcomes(state: &mut Document) -> Result<()> {
+    let lawsuits = match state.get_array_mut("lawsuits") {
+        Ok(lawsuits) => lawsuits,
+        Err(_) => return Ok(()),
+    };
+
+    for lawsuit in lawsuits.iter_mut().filter_map(Bson::as_document_mut) {
+        let has_verdict = matches!(lawsuit.get("verdict"), Some(Bson::String(_)));
+        let has_outcome = !matches!(lawsuit.get("outcome"), None | Some(Bson::Null));
+        if has_verdict && !has_outcome {
+            lawsuit.insert("outcome", doc! { "type": "verdict" });
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes every state field that older documents are missing with its default, so that queries
+/// see the same fields as the bot. The defaults are the ones of version 2, later changes to
+/// `State` must not change what this migration writes.
+fn fill_state_defaults(state: &mut Document) -> Result<()> {
+    fill_missing(
+        state,
+        doc! {
+            "court_category": Bson::Null,
+            "prison_role": Bson::Null,
+            "reputation": {
+                "base": 100.0,
+                "conviction_weight": 20.0,
+                "acquittal_weight": 5.0,
+                "prison_hour_weight": 0.5,
+                "half_life_days": 90.0,
+                "min_reputation": Bson::Null,
+            },
+            "lawyers": [],
+            "lawyer_role": Bson::Null,
+            "bar_exam": false,
+            "voice_rooms": false,
+            "court_mode": { "type": "rooms" },
+            "spectator_role": Bson::Null,
+            "docket_channel": Bson::Null,
+            "docket_overview": Bson::Null,
+            "plea": { "timeout_minutes": 1440_i64, "guilty_sentence_hours": Bson::Null },
+            "appearance": { "deadline_minutes": 2880_i64, "automatic": false },
+            "retention": { "closed_case_days": Bson::Null, "transcript_days": Bson::Null },
+            "departed_at": Bson::Null,
+        },
+    );
+    Ok(())
+}
+
+/// Like `fill_state_defaults`, for the prison entries of version 3.
+fn fill_prison_defaults(entry: &mut Document) -> Result<()> {
+    fill_missing(
+        entry,
+        doc! {
+            "arrested_at": Bson::Null,
+            "released_at": Bson::Null,
+            "release_at": Bson::Null,
+        },
+    );
+    Ok(())
+}
+
+fn fill_missing(document: &mut Document, defaults: Document) {
+    for (key, value) in defaults {
+        if !document.contains_key(&key) {
+            document.insert(key, value);
+        }
+    }
+}
+
+/// Brings every document up to `SCHEMA_VERSION`. Runs before the bot uses the database.
+#[tracing::instrument(skip_all)]
+pub async fn run_migrations(mongo: &Mongo) -> Result<()> {
+    for migration in MIGRATIONS {
+        let migrated = mongo
+            .apply_migration(migration)
+            .await
+            .wrap_err_with(|| format!("migration {} {}", migration.version, migration.name))?;
+        let first_run = mongo.record_migration(migration).await?;
+
+        if first_run || migrated > 0 {
+            info!(
+                version = migration.version,
+                name = migration.name,
+                migrated,
+                "Applied migration"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::{self, DateTime, Uuid};
+
+    use super::*;
+    use crate::{
+        lawsuit::LawsuitOutcome,
+        model::{PrisonEntry, State},
+    };
+
+    const GUILD: &str = "1000000000000000001";
+    const ALICE: &str = "1000000000000000002";
+    const BOB: &str = "1000000000000000003";
+    const JUDGE: &str = "1000000000000000004";
+    const ROOM: &str = "1000000000000000005";
+    const ROLE: &str = "1000000000000000006";
+
+    fn date(day: i64) -> DateTime {
+        DateTime::from_millis(1_650_000_000_000 + day * 24 * 60 * 60 * 1000)
+    }
+
+    /// The state as the first version of the bot stored it.
+    fn baseline_state() -> Document {
+        doc! {
+            "guild_id": GUILD,
+            "lawsuits": [
+                {
+                    "id": Uuid::new(),
+                    "plaintiff": ALICE,
+                    "accused": BOB,
+                    "plaintiff_lawyer": Bson::Null,
+                    "accused_lawyer": Bson::Null,
+                    "judge": JUDGE,
+                    "reason": "het mis znüni gässe",
+                    "verdict": "schuldig",
+                    "court_room": ROOM,
+                },
+                {
+                    "id": Uuid::new(),
+                    "plaintiff": BOB,
+                    "accused": ALICE,
+                    "plaintiff_lawyer": Bson::Null,
+                    "accused_lawyer": Bson::Null,
+                    "judge": JUDGE,
+                    "reason": "het zrugg gässe",
+                    "verdict": Bson::Null,
+                    "court_room": ROOM,
+                },
+            ],
+            "court_category": Bson::Null,
+            "court_rooms": [{ "channel_id": ROOM, "ongoing_lawsuit": true, "role_id": ROLE }],
+            "prison_role": ROLE,
+        }
+    }
+
+    /// The state after lawsuits got dates, a ruling, outcomes and lawyers.
+    fn outcome_state() -> Document {
+        doc! {
+            "guild_id": GUILD,
+            "lawsuits": [
+                {
+                    "id": Uuid::new(),
+                    "plaintiff": ALICE,
+                    "accused": BOB,
+                    "plaintiff_lawyer": JUDGE,
+                    "accused_lawyer": Bson::Null,
+                    "judge": JUDGE,
+                    "reason": "het mis velo gno",
+                    "verdict": "unschuldig",
+                    "guilty": false,
+                    "court_room": ROOM,
+                    "opened_at": date(0),
+                    "closed_at": date(2),
+                    "outcome": { "type": "verdict" },
+                },
+                {
+                    "id": Uuid::new(),
+                    "plaintiff": ALICE,
+                    "accused": BOB,
+                    "plaintiff_lawyer": Bson::Null,
+                    "accused_lawyer": Bson::Null,
+                    "judge": JUDGE,
+                    "reason": "het mis velo nomal gno",
+                    "verdict": Bson::Null,
+                    "guilty": Bson::Null,
+                    "court_room": ROOM,
+                    "opened_at": date(3),
+                    "closed_at": date(4),
+                    "outcome": { "type": "dismissed", "reason": "scho entschiede" },
+                },
+            ],
+            "court_category": Bson::Null,
+            "court_rooms": [{ "channel_id": ROOM, "ongoing_lawsuit": false, "role_id": ROLE }],
+            "prison_role": Bson::Null,
+            "reputation": {
+                "base": 50.0,
+                "conviction_weight": 10.0,
+                "acquittal_weight": 2.0,
+                "prison_hour_weight": 0.5,
+                "half_life_days": 30.0,
+                "min_reputation": Bson::Null,
+            },
+            "lawyers": [JUDGE],
+            "lawyer_role": ROLE,
+            "bar_exam": true,
+        }
+    }
+
+    /// The state after hearings got voice channels, a log, witnesses, the docket and pleas.
+    fn hearing_state() -> Document {
+        doc! {
+            "guild_id": GUILD,
+            "lawsuits": [{
+                "id": Uuid::new(),
+                "plaintiff": ALICE,
+                "accused": BOB,
+                "plaintiff_lawyer": Bson::Null,
+                "accused_lawyer": Bson::Null,
+                "judge": JUDGE,
+                "reason": "het im voice chat gschroue",
+                "verdict": Bson::Null,
+                "guilty": Bson::Null,
+                "court_room": ROOM,
+                "opened_at": date(5),
+                "closed_at": Bson::Null,
+                "outcome": Bson::Null,
+                "floor": ALICE,
+                "contempts": [{ "user": BOB, "until": date(6) }],
+                "log": [
+                    { "at": date(5), "actor": JUDGE, "event": { "type": "floor_granted", "to": ALICE } },
+                    { "at": date(5), "actor": BOB, "event": { "type": "objection", "reason": "lüge" } },
+                    { "at": date(5), "actor": Bson::Null, "event": { "type": "plea_defaulted", "plea": "not_guilty" } },
+                ],
+                "witnesses": [{ "user": JUDGE, "status": "summoned" }],
+                "visibility": "public",
+                "docket_message": ROLE,
+                "plea": "not_guilty",
+                "plea_deadline": date(6),
+            }],
+            "court_category": ROOM,
+            "court_rooms": [{
+                "channel_id": ROOM,
+                "ongoing_lawsuit": true,
+                "role_id": ROLE,
+                "voice_channel_id": ROOM,
+            }],
+            "prison_role": ROLE,
+            "lawyers": [],
+            "lawyer_role": Bson::Null,
+            "bar_exam": false,
+            "voice_rooms": true,
+            "court_mode": { "type": "threads", "channel_id": ROOM },
+            "spectator_role": ROLE,
+            "docket_channel": ROOM,
+            "docket_overview": Bson::Null,
+            "plea": { "timeout_minutes": 60_i64, "guilty_sentence_hours": 1.0 },
+        }
+    }
+
+    /// The state as the current version writes it.
+    fn current_state() -> Document {
+        let mut state = bson::from_document::<State>(hearing_state()).unwrap();
+        state.schema_version = SCHEMA_VERSION;
+        bson::to_document(&state).unwrap()
+    }
+
+    fn baseline_prison() -> Document {
+        doc! { "guild_id": GUILD, "user_id": BOB }
+    }
+
+    fn released_prison() -> Document {
+        doc! {
+            "guild_id": GUILD,
+            "user_id": BOB,
+            "arrested_at": date(0),
+            "released_at": date(1),
+        }
+    }
+
+    fn sentenced_prison() -> Document {
+        doc! {
+            "guild_id": GUILD,
+            "user_id": BOB,
+            "arrested_at": date(0),
+            "released_at": Bson::Null,
+            "release_at": date(1),
+            "schema_version": 0_i64,
+        }
+    }
+
+    fn state_fixtures() -> Vec<Document> {
+        vec![
+            baseline_state(),
+            outcome_state(),
+            hearing_state(),
+            current_state(),
+        ]
+    }
+
+    fn prison_fixtures() -> Vec<Document> {
+        vec![baseline_prison(), released_prison(), sentenced_prison()]
+    }
+
+    /// Applies the migrations of the collection the way the runner does.
+    fn migrate(collection: &str, mut document: Document) -> Document {
+        for migration in MIGRATIONS.iter().filter(|m| m.collection == collection) {
+            let version = document.get_i64("schema_version").unwrap_or(0);
+            if version < i64::from(migration.version) {
+                migration.apply(&mut document).unwrap();
+            }
+        }
+        document
+    }
+
+    /// The version of the last migration of the collection, which migrated documents end up with.
+    fn latest_version(collection: &str) -> u32 {
+        MIGRATIONS
+            .iter()
+            .filter(|m| m.collection == collection)
+            .map(|m| m.version)
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn migrations_are_ordered() {
+        let versions = MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>();
+        let expected = (1..=SCHEMA_VERSION).collect::<Vec<_>>();
+        assert_eq!(versions, expected);
+    }
+
+    #[test]
+    fn reads_historical_states() {
+        for fixture in state_fixtures() {
+            bson::from_document::<State>(fixture.clone()).unwrap();
+            let state = bson::from_document::<State>(migrate("state", fixture)).unwrap();
+            assert!(state.schema_version >= latest_version("state"));
+        }
+    }
+
+    #[test]
+    fn reads_historical_prison_entries() {
+        for fixture in prison_fixtures() {
+            bson::from_document::<PrisonEntry>(fixture.clone()).unwrap();
+            let entry = bson::from_document::<PrisonEntry>(migrate("prison", fixture)).unwrap();
+            assert!(entry.schema_version >= latest_version("prison"));
+        }
+    }
+
+    #[test]
+    fn migrations_are_idempotent() {
+        let fixtures = state_fixtures()
+            .into_iter()
+            .map(|fixture| ("state", fixture))
+            .chain(prison_fixtures().into_iter().map(|f| ("prison", f)));
+
+        for (collection, fixture) in fixtures {
+            let once = migrate(collection, fixture);
+            // as if the migrations were interrupted before the documents were marked
+            let mut twice = once.clone();
+            for migration in MIGRATIONS.iter().filter(|m| m.collection == collection) {
+                (migration.migrate)(&mut twice).unwrap();
+            }
+            assert_eq!(once, twice);
+        }
+    }
+
+    #[test]
+    fn fills_missing_defaults() {
+        let state = migrate("state", baseline_state());
+        for key in ["reputation", "lawyers", "court_mode", "plea", "retention"] {
+            assert!(state.contains_key(key), "{key} is missing");
+        }
+
+        let entry = migrate("prison", baseline_prison());
+        assert_eq!(entry.get("released_at"), Some(&Bson::Null));
+    }
+
+    #[test]
+    fn gives_old_verdicts_an_outcome() {
+        let state = bson::from_document::<State>(migrate("state", baseline_state())).unwrap();
+        assert_eq!(state.lawsuits[0].outcome, Some(LawsuitOutcome::Verdict));
+        assert_eq!(state.lawsuits[1].outcome, None);
+
+        let state = bson::from_document::<State>(migrate("state", outcome_state())).unwrap();
+        assert_eq!(
+            state.lawsuits[1].outcome,
+            Some(LawsuitOutcome::Dismissed {
+                reason: "scho entschiede".to_string()
+            })
+        );
+    }
+}
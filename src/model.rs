@@ -4,18 +4,37 @@ use std::{
     str::FromStr,
 };
 
-use color_eyre::Result;
+use color_eyre::{eyre::eyre, Result};
+use futures::TryStreamExt;
 use mongodb::{
     bson,
-    bson::{doc, Bson, Uuid},
-    options::{ClientOptions, Credential, IndexOptions, UpdateOptions},
+    bson::{doc, Bson, DateTime, Document, Uuid},
+    options::{
+        ClientOptions, Credential, FindOneAndUpdateOptions, FindOneOptions, FindOptions,
+        IndexOptions, ReplaceOptions, ReturnDocument, UpdateOptions,
+    },
     Client, Collection, Database, IndexModel,
 };
-use poise::serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use poise::serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::{error, info};
 
-use crate::{lawsuit::Lawsuit, WrapErr};
+use crate::{
+    appearance::AppearanceConfig,
+    export::GuildExport,
+    lawsuit::Lawsuit,
+    migration::{run_migrations, Migration, SCHEMA_VERSION},
+    plea::{Plea, PleaConfig},
+    procedure::{CaseLogEntry, Contempt},
+    record::ReputationConfig,
+    retention::RetentionConfig,
+    room::RoomStore,
+    stats::{LawsuitStats, StatsFilter, FIRST_MONDAY_MILLIS, TOP_COUNT},
+    time::MILLIS_PER_WEEK,
+    venue::{CourtMode, Venue},
+    witness::{Witness, WitnessStatus},
+    WrapErr,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -84,7 +103,7 @@ macro_rules! from_snowflake {
     };
 }
 
-from_snowflake!(GuildId, RoleId, ChannelId, UserId);
+from_snowflake!(GuildId, RoleId, ChannelId, UserId, MessageId);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
@@ -93,6 +112,54 @@ pub struct State {
     pub court_category: Option<SnowflakeId>,
     pub court_rooms: Vec<CourtRoom>,
     pub prison_role: Option<SnowflakeId>,
+    #[serde(default)]
+    pub reputation: ReputationConfig,
+    #[serde(default)]
+    pub lawyers: Vec<SnowflakeId>,
+    pub lawyer_role: Option<SnowflakeId>,
+    #[serde(default)]
+    pub bar_exam: bool,
+    /// Whether new lawsuits get a voice channel next to their court room.
+    #[serde(default)]
+    pub voice_rooms: bool,
+    #[serde(default)]
+    pub court_mode: CourtMode,
+    /// The role that can follow public lawsuits, everyone can if it is not set.
+    #[serde(default)]
+    pub spectator_role: Option<SnowflakeId>,
+    /// The channel where every lawsuit is announced.
+    #[serde(default)]
+    pub docket_channel: Option<SnowflakeId>,
+    /// The pinned overview of the active lawsuits in the docket channel.
+    #[serde(default)]
+    pub docket_overview: Option<SnowflakeId>,
+    #[serde(default)]
+    pub plea: PleaConfig,
+    #[serde(default)]
+    pub appearance: AppearanceConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// When the bot was removed from the guild, its data is purged some time later.
+    #[serde(default)]
+    pub departed_at: Option<DateTime>,
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+impl State {
+    pub fn active_lawsuit(&self, channel_id: SnowflakeId) -> Option<&Lawsuit> {
+        self.lawsuits
+            .iter()
+            .find(|l| l.court_room == channel_id && !l.is_closed())
+    }
+
+    /// Where the lawsuit in the channel takes place. Channels that aren't court rooms are threads.
+    pub fn venue(&self, channel_id: SnowflakeId) -> Venue {
+        match self.court_rooms.iter().find(|r| r.channel_id == channel_id) {
+            Some(room) => Venue::Room(room.clone()),
+            None => Venue::Thread(channel_id),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,12 +167,79 @@ pub struct CourtRoom {
     pub channel_id: SnowflakeId,
     pub ongoing_lawsuit: bool,
     pub role_id: SnowflakeId,
+    #[serde(default)]
+    pub voice_channel_id: Option<SnowflakeId>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrisonEntry {
     pub guild_id: SnowflakeId,
     pub user_id: SnowflakeId,
+    pub arrested_at: Option<DateTime>,
+    pub released_at: Option<DateTime>,
+    /// When the sentence is over, `None` for arrests without an end.
+    #[serde(default)]
+    pub release_at: Option<DateTime>,
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// A snapshot of a guild taken before its data was deleted, to undo the deletion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedGuild {
+    pub guild_id: SnowflakeId,
+    pub deleted_at: DateTime,
+    pub data: GuildExport,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockedAchievement {
+    pub guild_id: SnowflakeId,
+    pub user_id: SnowflakeId,
+    pub achievement: String,
+    pub unlocked_at: DateTime,
+}
+
+/// The state of a guild the bot has not stored anything for yet.
+fn empty_state(guild_id: SnowflakeId) -> State {
+    State {
+        guild_id,
+        lawsuits: vec![],
+        court_category: None,
+        court_rooms: vec![],
+        prison_role: None,
+        reputation: ReputationConfig::default(),
+        lawyers: vec![],
+        lawyer_role: None,
+        bar_exam: false,
+        voice_rooms: false,
+        court_mode: CourtMode::default(),
+        spectator_role: None,
+        docket_channel: None,
+        docket_overview: None,
+        plea: PleaConfig::default(),
+        appearance: AppearanceConfig::default(),
+        retention: RetentionConfig::default(),
+        departed_at: None,
+        schema_version: SCHEMA_VERSION,
+    }
+}
+
+/// Removes the lawsuit, only court rooms have to be marked as free again.
+fn cancel_lawsuit_update(lawsuit_id: Uuid, venue: &Venue) -> (Document, UpdateOptions) {
+    let pull = doc! { "lawsuits": { "id": lawsuit_id } };
+    match venue {
+        Venue::Room(room) => (
+            doc! {
+                "$pull": pull,
+                "$set": { "court_rooms.$[room].ongoing_lawsuit": false },
+            },
+            UpdateOptions::builder()
+                .array_filters(vec![doc! { "room.channel_id": room.channel_id }])
+                .build(),
+        ),
+        Venue::Thread(_) => (doc! { "$pull": pull }, UpdateOptions::default()),
+    }
 }
 
 #[derive(Clone)]
@@ -162,6 +296,44 @@ impl Mongo {
             .await
             .wrap_err("create state index")?;
 
+        mongo
+            .achievement_coll()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "guild_id": 1, "user_id": 1, "achievement": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name("achievements.guild_id_user_id_achievement".to_string())
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+                None,
+            )
+            .await
+            .wrap_err("create achievement index")?;
+
+        mongo
+            .migration_coll()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "version": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name("migrations.version".to_string())
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+                None,
+            )
+            .await
+            .wrap_err("create migration index")?;
+
+        info!("Running migrations");
+
+        run_migrations(&mongo).await?;
+
         Ok(mongo)
     }
 
@@ -186,13 +358,7 @@ impl Mongo {
 
     #[tracing::instrument(skip(self))]
     pub async fn new_state(&self, guild_id: SnowflakeId) -> Result<State> {
-        let state = State {
-            guild_id,
-            lawsuits: vec![],
-            court_category: None,
-            court_rooms: vec![],
-            prison_role: None,
-        };
+        let state = empty_state(guild_id);
 
         let coll = self.db.collection::<State>("state");
         coll.insert_one(&state, None)
@@ -238,137 +404,1793 @@ impl Mongo {
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn add_court_room(&self, guild_id: SnowflakeId, room: &CourtRoom) -> Result<()> {
+    pub async fn set_reputation_config(
+        &self,
+        guild_id: SnowflakeId,
+        config: &ReputationConfig,
+    ) -> Result<()> {
         let _ = self.find_or_insert_state(guild_id).await?;
         let coll = self.state_coll();
         coll.update_one(
             doc! { "guild_id": &guild_id  },
-            doc! { "$push": { "court_rooms": bson::to_bson(room).wrap_err("invalid bson for room")? }},
+            doc! { "$set": { "reputation": bson::to_bson(config).wrap_err("invalid bson for reputation config")? } },
             None,
         )
         .await
-        .wrap_err("push court room")?;
+        .wrap_err("update reputation config")?;
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn add_lawsuit(&self, guild_id: SnowflakeId, lawsuit: &Lawsuit) -> Result<()> {
+    pub async fn set_lawyer_role(
+        &self,
+        guild_id: SnowflakeId,
+        lawyer_role: SnowflakeId,
+    ) -> Result<()> {
         let _ = self.find_or_insert_state(guild_id).await?;
         let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "lawyer_role": lawyer_role } },
+            None,
+        )
+        .await
+        .wrap_err("update lawyer role")?;
+        Ok(())
+    }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn set_bar_exam(&self, guild_id: SnowflakeId, bar_exam: bool) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
         coll.update_one(
             doc! { "guild_id": &guild_id  },
-            doc! { "$push": { "lawsuits": bson::to_bson(lawsuit).wrap_err("invalid bson for lawsuit")? } },
+            doc! { "$set": { "bar_exam": bar_exam } },
             None,
         )
         .await
-        .wrap_err("push lawsuit")?;
+        .wrap_err("update bar exam")?;
+        Ok(())
+    }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn set_voice_rooms(&self, guild_id: SnowflakeId, voice_rooms: bool) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "voice_rooms": voice_rooms } },
+            None,
+        )
+        .await
+        .wrap_err("update voice rooms")?;
         Ok(())
     }
 
-    #[tracing::instrument(skip(self, value))]
-    pub async fn set_court_room(
+    #[tracing::instrument(skip(self))]
+    pub async fn set_spectator_role(
         &self,
         guild_id: SnowflakeId,
-        channel_id: SnowflakeId,
-        value: impl Into<Bson>,
+        spectator_role: Option<SnowflakeId>,
     ) -> Result<()> {
         let _ = self.find_or_insert_state(guild_id).await?;
         let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "spectator_role": spectator_role } },
+            None,
+        )
+        .await
+        .wrap_err("update spectator role")?;
+        Ok(())
+    }
 
+    /// Sets the docket channel, the overview is posted anew in the new channel.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_docket_channel(
+        &self,
+        guild_id: SnowflakeId,
+        docket_channel: Option<SnowflakeId>,
+    ) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
         coll.update_one(
-            doc! { "guild_id": &guild_id, "court_rooms.channel_id": channel_id  },
-            doc! { "$set": value.into() },
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "docket_channel": docket_channel, "docket_overview": Bson::Null } },
             None,
         )
         .await
-        .wrap_err("set courtroom")?;
+        .wrap_err("update docket channel")?;
         Ok(())
     }
 
-    #[tracing::instrument(skip(self, value))]
-    pub async fn set_lawsuit(
+    /// Stores the newly posted overview, unless the overview was replaced since `previous` was
+    /// read or the docket channel changed. Returns whether the overview was stored.
+    #[tracing::instrument(skip(self))]
+    pub async fn claim_docket_overview(
         &self,
         guild_id: SnowflakeId,
-        lawsuit_id: Uuid,
-        value: impl Into<Bson>,
+        docket_channel: SnowflakeId,
+        previous: Option<SnowflakeId>,
+        message_id: SnowflakeId,
+    ) -> Result<bool> {
+        let coll = self.state_coll();
+        let result = coll
+            .update_one(
+                doc! {
+                    "guild_id": &guild_id,
+                    "docket_channel": docket_channel,
+                    "docket_overview": previous,
+                },
+                doc! { "$set": { "docket_overview": message_id } },
+                None,
+            )
+            .await
+            .wrap_err("update docket overview")?;
+        Ok(result.modified_count == 1)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_court_mode(
+        &self,
+        guild_id: SnowflakeId,
+        court_mode: &CourtMode,
     ) -> Result<()> {
         let _ = self.find_or_insert_state(guild_id).await?;
         let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "court_mode": bson::to_bson(court_mode).wrap_err("invalid bson for court mode")? } },
+            None,
+        )
+        .await
+        .wrap_err("update court mode")?;
+        Ok(())
+    }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn add_lawyer(&self, guild_id: SnowflakeId, user_id: SnowflakeId) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
         coll.update_one(
-            doc! { "guild_id": &guild_id, "lawsuit.id": lawsuit_id  },
-            doc! { "$set": value.into() },
+            doc! { "guild_id": &guild_id  },
+            doc! { "$addToSet": { "lawyers": user_id } },
             None,
         )
         .await
-        .wrap_err("set courtroom")?;
+        .wrap_err("add lawyer")?;
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn delete_guild(&self, guild_id: SnowflakeId) -> Result<()> {
+    pub async fn remove_lawyer(&self, guild_id: SnowflakeId, user_id: SnowflakeId) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
         let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$pull": { "lawyers": user_id } },
+            None,
+        )
+        .await
+        .wrap_err("remove lawyer")?;
+        Ok(())
+    }
 
-        coll.delete_one(doc! { "guild_id": &guild_id }, None)
-            .await
-            .wrap_err("delete guild")?;
+    #[tracing::instrument(skip(self))]
+    pub async fn add_court_room(&self, guild_id: SnowflakeId, room: &CourtRoom) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$push": { "court_rooms": bson::to_bson(room).wrap_err("invalid bson for room")? }},
+            None,
+        )
+        .await
+        .wrap_err("push court room")?;
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn add_to_prison(&self, guild_id: SnowflakeId, user_id: SnowflakeId) -> Result<()> {
-        let coll = self.prison_coll();
+    pub async fn set_room_voice_channel(
+        &self,
+        guild_id: SnowflakeId,
+        channel_id: SnowflakeId,
+        voice_channel_id: SnowflakeId,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "court_rooms.channel_id": channel_id },
+            doc! { "$set": { "court_rooms.$.voice_channel_id": voice_channel_id } },
+            None,
+        )
+        .await
+        .wrap_err("set voice channel of court room")?;
+        Ok(())
+    }
+
+    /// Adds the lawsuit to the guild. Its court room has to be claimed through the
+    /// [`RoomAllocator`](crate::room::RoomAllocator) before.
+    #[tracing::instrument(skip(self))]
+    pub async fn open_lawsuit(&self, guild_id: SnowflakeId, lawsuit: &Lawsuit) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
 
         coll.update_one(
-            doc! { "guild_id": guild_id, "user_id": user_id },
-            doc! {
-                "$setOnInsert": {
-                    "guild_id": guild_id, "user_id": user_id,
-                }
-            },
-            UpdateOptions::builder().upsert(true).build(),
+            doc! { "guild_id": &guild_id },
+            doc! { "$push": { "lawsuits": bson::to_bson(lawsuit).wrap_err("invalid bson for lawsuit")? } },
+            None,
         )
         .await
-        .wrap_err("add to prison collection")?;
+        .wrap_err("open lawsuit")?;
 
         Ok(())
     }
 
+    /// Sets the fields of the lawsuit and frees its court room in one atomic update, but only if
+    /// the lawsuit is still open. Returns `false` if it has already been closed.
+    #[tracing::instrument(skip(self, fields))]
+    pub async fn close_lawsuit(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        channel_id: SnowflakeId,
+        fields: Document,
+    ) -> Result<bool> {
+        let coll = self.state_coll();
+
+        let mut update = doc! { "court_rooms.$[room].ongoing_lawsuit": false };
+        for (key, value) in fields {
+            update.insert(format!("lawsuits.$[lawsuit].{key}"), value);
+        }
+
+        let result = coll
+            .update_one(
+                doc! {
+                    "guild_id": &guild_id,
+                    "lawsuits": { "$elemMatch": { "id": lawsuit_id, "outcome": Bson::Null, "verdict": Bson::Null } },
+                },
+                doc! { "$set": update },
+                UpdateOptions::builder()
+                    .array_filters(vec![
+                        doc! { "lawsuit.id": lawsuit_id },
+                        doc! { "room.channel_id": channel_id },
+                    ])
+                    .build(),
+            )
+            .await
+            .wrap_err("close lawsuit")?;
+
+        Ok(result.modified_count == 1)
+    }
+
+    /// Removes a lawsuit that could not be set up completely and frees its court room again. Fails
+    /// if the lawsuit is not stored.
     #[tracing::instrument(skip(self))]
-    pub async fn remove_from_prison(
+    pub async fn cancel_lawsuit(
         &self,
         guild_id: SnowflakeId,
-        user_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        venue: &Venue,
     ) -> Result<()> {
-        let coll = self.prison_coll();
+        let coll = self.state_coll();
+        let (update, options) = cancel_lawsuit_update(lawsuit_id, venue);
 
-        coll.delete_one(doc! { "guild_id": guild_id, "user_id": user_id }, None)
+        let result = coll
+            .update_one(
+                doc! { "guild_id": &guild_id, "lawsuits.id": lawsuit_id },
+                update,
+                options,
+            )
             .await
-            .wrap_err("remove from prison")?;
+            .wrap_err("cancel lawsuit")?;
+
+        if result.matched_count == 0 {
+            return Err(eyre!("lawsuit {lawsuit_id} to cancel not found"));
+        }
 
         Ok(())
     }
 
-    #[tracing::instrument(skip(self))]
-    pub async fn find_prison_entry(
+    #[tracing::instrument(skip(self, value))]
+    pub async fn set_lawsuit(
         &self,
         guild_id: SnowflakeId,
-        user_id: SnowflakeId,
-    ) -> Result<Option<PrisonEntry>> {
-        let coll = self.prison_coll();
+        lawsuit_id: Uuid,
+        value: impl Into<Bson>,
+    ) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "lawsuits.id": lawsuit_id  },
+            doc! { "$set": value.into() },
+            None,
+        )
+        .await
+        .wrap_err("set lawsuit")?;
+        Ok(())
+    }
+
+    /// Like `set_lawsuit`, but only while the lawsuit is still open in the court room. Returns
+    /// `false` if it isn't anymore.
+    #[tracing::instrument(skip(self, value))]
+    pub async fn set_open_lawsuit(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        court_room: SnowflakeId,
+        value: impl Into<Bson>,
+    ) -> Result<bool> {
+        let coll = self.state_coll();
 
-        coll.find_one(doc! { "guild_id": guild_id, "user_id": user_id }, None)
+        let result = coll
+            .update_one(
+                doc! {
+                    "guild_id": &guild_id,
+                    "lawsuits": { "$elemMatch": {
+                        "id": lawsuit_id,
+                        "court_room": court_room,
+                        "outcome": Bson::Null,
+                        "verdict": Bson::Null,
+                    } },
+                },
+                doc! { "$set": value.into() },
+                None,
+            )
             .await
-            .wrap_err("remove from prison")
+            .wrap_err("set open lawsuit")?;
+        Ok(result.matched_count > 0)
     }
 
-    fn state_coll(&self) -> Collection<State> {
-        self.db.collection("state")
+    #[tracing::instrument(skip(self))]
+    pub async fn log_case_event(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        entry: &CaseLogEntry,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "lawsuits.id": lawsuit_id },
+            doc! { "$push": { "lawsuits.$.log": bson::to_bson(entry).wrap_err("invalid bson for case log")? } },
+            None,
+        )
+        .await
+        .wrap_err("log case event")?;
+        Ok(())
     }
 
-    fn prison_coll(&self) -> Collection<PrisonEntry> {
-        self.db.collection("prison")
+    #[tracing::instrument(skip(self))]
+    pub async fn add_contempt(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        contempt: &Contempt,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "lawsuits.id": lawsuit_id },
+            doc! { "$push": { "lawsuits.$.contempts": bson::to_bson(contempt).wrap_err("invalid bson for contempt")? } },
+            None,
+        )
+        .await
+        .wrap_err("add contempt")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn remove_expired_contempts(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        now: DateTime,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "lawsuits.id": lawsuit_id },
+            doc! { "$pull": { "lawsuits.$.contempts": { "until": { "$lte": now } } } },
+            None,
+        )
+        .await
+        .wrap_err("remove expired contempts")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn add_witness(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        witness: &Witness,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "lawsuits.id": lawsuit_id },
+            doc! { "$push": { "lawsuits.$.witnesses": bson::to_bson(witness).wrap_err("invalid bson for witness")? } },
+            None,
+        )
+        .await
+        .wrap_err("add witness")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_witness_status(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        user_id: SnowflakeId,
+        status: WitnessStatus,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id },
+            doc! { "$set": {
+                "lawsuits.$[lawsuit].witnesses.$[witness].status":
+                    bson::to_bson(&status).wrap_err("invalid bson for witness status")?
+            } },
+            UpdateOptions::builder()
+                .array_filters(vec![
+                    doc! { "lawsuit.id": lawsuit_id },
+                    doc! { "witness.user": user_id },
+                ])
+                .build(),
+        )
+        .await
+        .wrap_err("set witness status")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_states_with_expired_contempts(&self, now: DateTime) -> Result<Vec<State>> {
+        let coll = self.state_coll();
+
+        coll.find(doc! { "lawsuits.contempts.until": { "$lte": now } }, None)
+            .await
+            .wrap_err("find expired contempts")?
+            .try_collect()
+            .await
+            .wrap_err("collect expired contempts")
+    }
+
+    /// Sets the plea if the accused hasn't entered one yet and the lawsuit is still ongoing.
+    /// Returns `false` otherwise.
+    #[tracing::instrument(skip(self))]
+    pub async fn enter_plea(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        plea: Plea,
+    ) -> Result<bool> {
+        let coll = self.state_coll();
+
+        let result = coll
+            .update_one(
+                doc! {
+                    "guild_id": &guild_id,
+                    "lawsuits": { "$elemMatch": { "id": lawsuit_id, "plea": Bson::Null, "outcome": Bson::Null, "verdict": Bson::Null } },
+                },
+                doc! { "$set": { "lawsuits.$.plea": bson::to_bson(&plea).wrap_err("invalid bson for plea")? } },
+                None,
+            )
+            .await
+            .wrap_err("enter plea")?;
+
+        Ok(result.modified_count == 1)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_states_with_expired_plea_deadlines(
+        &self,
+        now: DateTime,
+    ) -> Result<Vec<State>> {
+        let coll = self.state_coll();
+
+        coll.find(
+            doc! {
+                "lawsuits": { "$elemMatch": {
+                    "plea": Bson::Null,
+                    "outcome": Bson::Null,
+                    "verdict": Bson::Null,
+                    "plea_deadline": { "$lte": now },
+                } },
+            },
+            None,
+        )
+        .await
+        .wrap_err("find expired plea deadlines")?
+        .try_collect()
+        .await
+        .wrap_err("collect expired plea deadlines")
+    }
+
+    /// Marks the accused of the active lawsuit in the channel as appeared. Returns `false` if
+    /// the user isn't the accused there or has appeared before.
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_appeared(
+        &self,
+        guild_id: SnowflakeId,
+        channel_id: SnowflakeId,
+        user_id: SnowflakeId,
+    ) -> Result<bool> {
+        let coll = self.state_coll();
+
+        let result = coll
+            .update_one(
+                doc! {
+                    "guild_id": &guild_id,
+                    "lawsuits": { "$elemMatch": {
+                        "court_room": channel_id,
+                        "accused": user_id,
+                        "appeared": { "$ne": true },
+                        "outcome": Bson::Null,
+                        "verdict": Bson::Null,
+                    } },
+                },
+                doc! { "$set": { "lawsuits.$.appeared": true } },
+                None,
+            )
+            .await
+            .wrap_err("mark accused as appeared")?;
+
+        Ok(result.modified_count == 1)
+    }
+
+    /// Marks the default judgment of the lawsuit as offered. Returns `false` if it was offered
+    /// before, so it is only offered once.
+    #[tracing::instrument(skip(self))]
+    pub async fn claim_default_judgment(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+    ) -> Result<bool> {
+        let coll = self.state_coll();
+
+        let result = coll
+            .update_one(
+                doc! {
+                    "guild_id": &guild_id,
+                    "lawsuits": { "$elemMatch": { "id": lawsuit_id, "default_judgment_offered": { "$ne": true } } },
+                },
+                doc! { "$set": { "lawsuits.$.default_judgment_offered": true } },
+                None,
+            )
+            .await
+            .wrap_err("claim default judgment")?;
+
+        Ok(result.modified_count == 1)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_states_with_missed_appearances(&self, now: DateTime) -> Result<Vec<State>> {
+        let coll = self.state_coll();
+
+        coll.find(
+            doc! {
+                "lawsuits": { "$elemMatch": {
+                    "appeared": { "$ne": true },
+                    "default_judgment_offered": { "$ne": true },
+                    "outcome": Bson::Null,
+                    "verdict": Bson::Null,
+                    "appearance_deadline": { "$lte": now },
+                } },
+            },
+            None,
+        )
+        .await
+        .wrap_err("find missed appearances")?
+        .try_collect()
+        .await
+        .wrap_err("collect missed appearances")
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_appearance_config(
+        &self,
+        guild_id: SnowflakeId,
+        config: &AppearanceConfig,
+    ) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "appearance": bson::to_bson(config).wrap_err("invalid bson for appearance config")? } },
+            None,
+        )
+        .await
+        .wrap_err("update appearance config")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_plea_config(&self, guild_id: SnowflakeId, config: &PleaConfig) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "plea": bson::to_bson(config).wrap_err("invalid bson for plea config")? } },
+            None,
+        )
+        .await
+        .wrap_err("update plea config")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_retention_config(
+        &self,
+        guild_id: SnowflakeId,
+        config: &RetentionConfig,
+    ) -> Result<()> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id  },
+            doc! { "$set": { "retention": bson::to_bson(config).wrap_err("invalid bson for retention config")? } },
+            None,
+        )
+        .await
+        .wrap_err("update retention config")?;
+        Ok(())
+    }
+
+    /// Marks the guild as left by the bot, or as joined again with `None`.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_guild_departed(
+        &self,
+        guild_id: SnowflakeId,
+        departed_at: Option<DateTime>,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+        coll.update_one(
+            doc! { "guild_id": &guild_id },
+            doc! { "$set": { "departed_at": departed_at } },
+            None,
+        )
+        .await
+        .wrap_err("update departed at")?;
+        Ok(())
+    }
+
+    /// The retention policies of all guilds, without loading their lawsuits.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_retention_configs(&self) -> Result<Vec<(SnowflakeId, RetentionConfig)>> {
+        #[derive(Deserialize)]
+        struct Retention {
+            guild_id: SnowflakeId,
+            #[serde(default)]
+            retention: RetentionConfig,
+        }
+
+        let coll = self.db.collection::<Retention>("state");
+        let configs: Vec<Retention> = coll
+            .find(
+                None,
+                FindOptions::builder()
+                    .projection(doc! { "guild_id": 1, "retention": 1 })
+                    .build(),
+            )
+            .await
+            .wrap_err("find retention configs")?
+            .try_collect()
+            .await
+            .wrap_err("collect retention configs")?;
+
+        Ok(configs
+            .into_iter()
+            .map(|config| (config.guild_id, config.retention))
+            .collect())
+    }
+
+    /// Clears the logs of lawsuits closed before `transcripts_before`, soft-deletes lawsuits closed
+    /// before `cases_before` and purges lawsuits soft-deleted before `purge_before`.
+    #[tracing::instrument(skip(self))]
+    pub async fn apply_retention(
+        &self,
+        guild_id: SnowflakeId,
+        transcripts_before: Option<DateTime>,
+        cases_before: Option<DateTime>,
+        purge_before: DateTime,
+        now: DateTime,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        if let Some(before) = transcripts_before {
+            coll.update_one(
+                doc! { "guild_id": &guild_id },
+                doc! { "$set": { "lawsuits.$[old].log": [] } },
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! { "old.closed_at": { "$lte": before } }])
+                    .build(),
+            )
+            .await
+            .wrap_err("delete old transcripts")?;
+        }
+
+        if let Some(before) = cases_before {
+            coll.update_one(
+                doc! { "guild_id": &guild_id },
+                doc! { "$set": { "lawsuits.$[old].deleted_at": now } },
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! {
+                        "old.closed_at": { "$lte": before },
+                        "old.deleted_at": Bson::Null,
+                    }])
+                    .build(),
+            )
+            .await
+            .wrap_err("soft delete old lawsuits")?;
+        }
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id },
+            doc! { "$pull": { "lawsuits": { "deleted_at": { "$lte": purge_before } } } },
+            None,
+        )
+        .await
+        .wrap_err("purge deleted lawsuits")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_departed_guilds(&self, before: DateTime) -> Result<Vec<SnowflakeId>> {
+        let coll = self.state_coll();
+
+        let states: Vec<State> = coll
+            .find(doc! { "departed_at": { "$lte": before } }, None)
+            .await
+            .wrap_err("find departed guilds")?
+            .try_collect()
+            .await
+            .wrap_err("collect departed guilds")?;
+
+        Ok(states.into_iter().map(|state| state.guild_id).collect())
+    }
+
+    /// Deletes everything about the guild for good, without a snapshot to undo it.
+    #[tracing::instrument(skip(self))]
+    pub async fn purge_guild(&self, guild_id: SnowflakeId) -> Result<()> {
+        self.state_coll()
+            .delete_one(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("purge guild")?;
+        self.prison_coll()
+            .delete_many(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("purge guild prison entries")?;
+        self.achievement_coll()
+            .delete_many(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("purge guild achievements")?;
+        self.trash_coll()
+            .delete_many(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("purge guild snapshots")?;
+        Ok(())
+    }
+
+    /// Finds the states of all guilds with lawsuits or lawyer admissions of the member.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_states_referencing(&self, user_id: SnowflakeId) -> Result<Vec<State>> {
+        let coll = self.state_coll();
+        let mention = doc! { "$regex": format!("<@!?{user_id}>") };
+
+        let mut filters = [
+            "lawsuits.plaintiff",
+            "lawsuits.accused",
+            "lawsuits.judge",
+            "lawsuits.plaintiff_lawyer",
+            "lawsuits.accused_lawyer",
+            "lawsuits.floor",
+            "lawsuits.contempts.user",
+            "lawsuits.witnesses.user",
+            "lawsuits.log.actor",
+            "lawsuits.log.event.to",
+            "lawsuits.log.event.user",
+            "lawyers",
+        ]
+        .into_iter()
+        .map(|field| doc! { field: user_id })
+        .collect::<Vec<_>>();
+        filters.push(doc! { "lawsuits.reason": mention.clone() });
+        filters.push(doc! { "lawsuits.verdict": mention });
+
+        coll.find(doc! { "$or": filters }, None)
+            .await
+            .wrap_err("find states referencing user")?
+            .try_collect()
+            .await
+            .wrap_err("collect states referencing user")
+    }
+
+    /// Replaces the member with the tombstone wherever a lawsuit of the guild names them. Only
+    /// these fields are written, so concurrent changes to the lawsuits are kept.
+    #[tracing::instrument(skip(self))]
+    pub async fn anonymize_lawsuits(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+        tombstone: SnowflakeId,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        let mut fields = [
+            "plaintiff",
+            "accused",
+            "judge",
+            "plaintiff_lawyer",
+            "accused_lawyer",
+            "floor",
+        ]
+        .into_iter()
+        .map(|field| {
+            (
+                format!("lawsuits.$[lawsuit].{field}"),
+                doc! { format!("lawsuit.{field}"): user_id },
+            )
+        })
+        .collect::<Vec<_>>();
+        fields.extend([
+            (
+                "lawsuits.$[].contempts.$[contempt].user".to_string(),
+                doc! { "contempt.user": user_id },
+            ),
+            (
+                "lawsuits.$[].witnesses.$[witness].user".to_string(),
+                doc! { "witness.user": user_id },
+            ),
+            (
+                "lawsuits.$[].log.$[entry].actor".to_string(),
+                doc! { "entry.actor": user_id },
+            ),
+            (
+                "lawsuits.$[].log.$[entry].event.to".to_string(),
+                doc! { "entry.event.to": user_id },
+            ),
+            (
+                "lawsuits.$[].log.$[entry].event.user".to_string(),
+                doc! { "entry.event.user": user_id },
+            ),
+        ]);
+
+        for (field, filter) in fields {
+            coll.update_one(
+                doc! { "guild_id": &guild_id },
+                doc! { "$set": { field: tombstone } },
+                UpdateOptions::builder().array_filters(vec![filter]).build(),
+            )
+            .await
+            .wrap_err("anonymize lawsuits")?;
+        }
+        Ok(())
+    }
+
+    /// Sets the text field of the lawsuit, e.g. the reason, unless it changed from `previous`.
+    #[tracing::instrument(skip(self))]
+    pub async fn replace_lawsuit_text(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        field: &str,
+        previous: &str,
+        text: &str,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id },
+            doc! { "$set": { format!("lawsuits.$[lawsuit].{field}"): text } },
+            UpdateOptions::builder()
+                .array_filters(vec![
+                    doc! { "lawsuit.id": lawsuit_id, format!("lawsuit.{field}"): previous },
+                ])
+                .build(),
+        )
+        .await
+        .wrap_err("replace lawsuit text")?;
+        Ok(())
+    }
+
+    /// Sets the reason of the objections of the lawsuit that were `previous`.
+    #[tracing::instrument(skip(self))]
+    pub async fn replace_objection_reason(
+        &self,
+        guild_id: SnowflakeId,
+        lawsuit_id: Uuid,
+        previous: &str,
+        reason: &str,
+    ) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id },
+            doc! { "$set": { "lawsuits.$[lawsuit].log.$[entry].event.reason": reason } },
+            UpdateOptions::builder()
+                .array_filters(vec![
+                    doc! { "lawsuit.id": lawsuit_id },
+                    doc! { "entry.event.type": "objection", "entry.event.reason": previous },
+                ])
+                .build(),
+        )
+        .await
+        .wrap_err("replace objection reason")?;
+        Ok(())
+    }
+
+    /// The prison entries of the member in every guild.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_user_prison_entries(&self, user_id: SnowflakeId) -> Result<Vec<PrisonEntry>> {
+        let coll = self.prison_coll();
+
+        coll.find(doc! { "user_id": user_id }, None)
+            .await
+            .wrap_err("find user prison entries")?
+            .try_collect()
+            .await
+            .wrap_err("collect user prison entries")
+    }
+
+    /// The achievements of the member in every guild.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_user_achievements(
+        &self,
+        user_id: SnowflakeId,
+    ) -> Result<Vec<UnlockedAchievement>> {
+        let coll = self.achievement_coll();
+
+        coll.find(doc! { "user_id": user_id }, None)
+            .await
+            .wrap_err("find user achievements")?
+            .try_collect()
+            .await
+            .wrap_err("collect user achievements")
+    }
+
+    /// Replaces the member in their prison entries of every guild. Returns how many there were.
+    #[tracing::instrument(skip(self))]
+    pub async fn anonymize_prison_entries(
+        &self,
+        user_id: SnowflakeId,
+        tombstone: SnowflakeId,
+    ) -> Result<u64> {
+        let coll = self.prison_coll();
+
+        let result = coll
+            .update_many(
+                doc! { "user_id": user_id },
+                doc! { "$set": { "user_id": tombstone } },
+                None,
+            )
+            .await
+            .wrap_err("anonymize prison entries")?;
+        Ok(result.modified_count)
+    }
+
+    /// Deletes the achievements of the member in every guild. Returns how many there were.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_user_achievements(&self, user_id: SnowflakeId) -> Result<u64> {
+        let coll = self.achievement_coll();
+
+        let result = coll
+            .delete_many(doc! { "user_id": user_id }, None)
+            .await
+            .wrap_err("delete user achievements")?;
+        Ok(result.deleted_count)
+    }
+
+    /// Deletes the snapshots of the guilds and every other snapshot with data of the member.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_trash_referencing(
+        &self,
+        user_id: SnowflakeId,
+        guild_ids: &[SnowflakeId],
+    ) -> Result<()> {
+        self.trash_coll()
+            .delete_many(
+                doc! { "$or": [
+                    { "guild_id": { "$in": guild_ids } },
+                    { "data.prison.user_id": user_id },
+                    { "data.achievements.user_id": user_id },
+                ] },
+                None,
+            )
+            .await
+            .wrap_err("delete trash referencing user")?;
+        Ok(())
+    }
+
+    /// Deletes the snapshots of cleared guilds taken before `before`.
+    #[tracing::instrument(skip(self))]
+    pub async fn purge_trash(&self, before: DateTime) -> Result<()> {
+        self.trash_coll()
+            .delete_many(doc! { "deleted_at": { "$lte": before } }, None)
+            .await
+            .wrap_err("purge trash")?;
+        Ok(())
+    }
+
+    /// Deletes everything of the guild, keeping a snapshot for the undo. Returns when the snapshot
+    /// was taken.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_guild(&self, guild_id: SnowflakeId) -> Result<DateTime> {
+        let deleted_at = DateTime::now();
+        let snapshot = GuildExport::load(self, guild_id).await?;
+
+        self.trash_coll()
+            .insert_one(
+                DeletedGuild {
+                    guild_id,
+                    deleted_at,
+                    data: snapshot,
+                },
+                None,
+            )
+            .await
+            .wrap_err("keep deleted guild")?;
+
+        self.state_coll()
+            .delete_one(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("delete guild")?;
+        self.prison_coll()
+            .delete_many(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("delete guild prison entries")?;
+        self.achievement_coll()
+            .delete_many(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("delete guild achievements")?;
+        Ok(deleted_at)
+    }
+
+    /// Whether anything was stored for the guild, e.g. since it was deleted. A state without any
+    /// changes does not count, every command creates one.
+    #[tracing::instrument(skip(self))]
+    pub async fn has_guild_data(&self, guild_id: SnowflakeId) -> Result<bool> {
+        let state = self
+            .state_coll()
+            .find_one(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("find state")?;
+        if let Some(state) = state {
+            let state = bson::to_document(&state).wrap_err("serialize state")?;
+            let empty = bson::to_document(&empty_state(guild_id)).wrap_err("serialize state")?;
+            if state != empty {
+                return Ok(true);
+            }
+        }
+
+        let prison = self
+            .prison_coll()
+            .count_documents(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("count guild prison entries")?;
+        let achievements = self
+            .achievement_coll()
+            .count_documents(doc! { "guild_id": &guild_id }, None)
+            .await
+            .wrap_err("count guild achievements")?;
+        Ok(prison > 0 || achievements > 0)
+    }
+
+    /// Restores the guild from its latest snapshot deleted after `deleted_after`. Returns `false`
+    /// if there is none.
+    #[tracing::instrument(skip(self))]
+    pub async fn restore_guild(
+        &self,
+        guild_id: SnowflakeId,
+        deleted_after: DateTime,
+    ) -> Result<bool> {
+        let deleted = self
+            .trash_coll()
+            .find_one(
+                doc! { "guild_id": &guild_id, "deleted_at": { "$gte": deleted_after } },
+                FindOneOptions::builder()
+                    .sort(doc! { "deleted_at": -1 })
+                    .build(),
+            )
+            .await
+            .wrap_err("find deleted guild")?;
+
+        let deleted = match deleted {
+            Some(deleted) => deleted,
+            None => return Ok(false),
+        };
+
+        // the snapshot is only dropped once it is back, a failed restore can be retried
+        let data = &deleted.data;
+        self.import_guild(&data.state, &data.prison, &data.achievements)
+            .await?;
+        self.trash_coll()
+            .delete_one(
+                doc! { "guild_id": &guild_id, "deleted_at": deleted.deleted_at },
+                None,
+            )
+            .await
+            .wrap_err("delete restored guild")?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn add_to_prison(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+        release_at: Option<DateTime>,
+    ) -> Result<()> {
+        let coll = self.prison_coll();
+
+        coll.update_one(
+            doc! { "guild_id": guild_id, "user_id": user_id, "released_at": Bson::Null },
+            doc! {
+                "$setOnInsert": {
+                    "guild_id": guild_id, "user_id": user_id, "arrested_at": DateTime::now(),
+                    "release_at": release_at, "schema_version": SCHEMA_VERSION,
+                }
+            },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await
+        .wrap_err("add to prison collection")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn remove_from_prison(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+    ) -> Result<()> {
+        let coll = self.prison_coll();
+
+        coll.update_many(
+            doc! { "guild_id": guild_id, "user_id": user_id, "released_at": Bson::Null },
+            doc! { "$set": { "released_at": DateTime::now() } },
+            None,
+        )
+        .await
+        .wrap_err("remove from prison")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_prison_entry(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+    ) -> Result<Option<PrisonEntry>> {
+        let coll = self.prison_coll();
+
+        coll.find_one(
+            doc! { "guild_id": guild_id, "user_id": user_id, "released_at": Bson::Null },
+            None,
+        )
+        .await
+        .wrap_err("find prison entry")
+    }
+
+    /// Finds the prisoners whose sentence is over, in every guild.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_due_prison_releases(&self, now: DateTime) -> Result<Vec<PrisonEntry>> {
+        let coll = self.prison_coll();
+
+        coll.find(
+            doc! { "released_at": Bson::Null, "release_at": { "$lte": now } },
+            None,
+        )
+        .await
+        .wrap_err("find due prison releases")?
+        .try_collect()
+        .await
+        .wrap_err("collect due prison releases")
+    }
+
+    /// Increments the counter and returns the new value, the first one is 1.
+    #[tracing::instrument(skip(self))]
+    pub async fn next_counter_value(&self, counter: &str) -> Result<i64> {
+        let counter = self
+            .counter_coll()
+            .find_one_and_update(
+                doc! { "_id": counter },
+                doc! { "$inc": { "value": 1_i64 } },
+                FindOneAndUpdateOptions::builder()
+                    .upsert(true)
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .wrap_err("increment counter")?
+            .ok_or_else(|| eyre!("counter not upserted"))?;
+        counter.get_i64("value").wrap_err("counter value")
+    }
+
+    /// Finds everyone who is in prison since `arrested_before` or longer, in every guild.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_prisoners_since(
+        &self,
+        arrested_before: DateTime,
+    ) -> Result<Vec<PrisonEntry>> {
+        let coll = self.prison_coll();
+
+        coll.find(
+            doc! { "released_at": Bson::Null, "arrested_at": { "$lte": arrested_before } },
+            None,
+        )
+        .await
+        .wrap_err("find long prison stays")?
+        .try_collect()
+        .await
+        .wrap_err("collect long prison stays")
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_prison_history(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+    ) -> Result<Vec<PrisonEntry>> {
+        let coll = self.prison_coll();
+
+        coll.find(doc! { "guild_id": guild_id, "user_id": user_id }, None)
+            .await
+            .wrap_err("find prison history")?
+            .try_collect()
+            .await
+            .wrap_err("collect prison history")
+    }
+
+    /// Aggregates the lawsuit numbers of the guild in the database, without loading the state.
+    #[tracing::instrument(skip(self))]
+    pub async fn aggregate_lawsuit_stats(
+        &self,
+        guild_id: SnowflakeId,
+        filter: StatsFilter,
+    ) -> Result<LawsuitStats> {
+        let coll = self.state_coll();
+
+        let mut lawsuit_match = doc! { "deleted_at": Bson::Null };
+        if let Some(since) = filter.since {
+            lawsuit_match.insert("opened_at", doc! { "$gte": since });
+        }
+        if let Some(user) = filter.user {
+            lawsuit_match.insert(
+                "$or",
+                [
+                    "plaintiff",
+                    "accused",
+                    "judge",
+                    "plaintiff_lawyer",
+                    "accused_lawyer",
+                ]
+                .into_iter()
+                .map(|field| doc! { field: user })
+                .collect::<Vec<_>>(),
+            );
+        }
+
+        let is_closed = doc! { "$or": [
+            { "$ne": [{ "$ifNull": ["$outcome", Bson::Null] }, Bson::Null] },
+            { "$ne": [{ "$ifNull": ["$verdict", Bson::Null] }, Bson::Null] },
+        ] };
+        let has_duration = doc! { "$and": [
+            { "$ne": [{ "$ifNull": ["$opened_at", Bson::Null] }, Bson::Null] },
+            { "$ne": [{ "$ifNull": ["$closed_at", Bson::Null] }, Bson::Null] },
+        ] };
+        let ranking = |field: &str| {
+            vec![
+                doc! { "$group": { "_id": field, "count": { "$sum": 1 } } },
+                doc! { "$sort": { "count": -1, "_id": 1 } },
+                doc! { "$limit": TOP_COUNT },
+            ]
+        };
+        let outcome_count = |outcome: &str| {
+            doc! { "$sum": { "$cond": [{ "$eq": ["$outcome.type", outcome] }, 1, 0] } }
+        };
+        let guilty_plea = bson::to_bson(&Plea::Guilty).wrap_err("invalid bson for plea")?;
+        let week_start = doc! { "$subtract": [
+            "$opened_at",
+            { "$mod": [
+                { "$subtract": ["$opened_at", DateTime::from_millis(FIRST_MONDAY_MILLIS)] },
+                MILLIS_PER_WEEK,
+            ] },
+        ] };
+        let first_total = |field: &str| {
+            doc! { "$ifNull": [{ "$arrayElemAt": [format!("$totals.{field}"), 0] }, 0] }
+        };
+
+        let lawyer_ranking = [
+            vec![
+                doc! { "$project": { "lawyer": ["$plaintiff_lawyer", "$accused_lawyer"] } },
+                doc! { "$unwind": "$lawyer" },
+                doc! { "$match": { "lawyer": { "$ne": Bson::Null } } },
+            ],
+            ranking("$lawyer"),
+        ]
+        .concat();
+
+        let pipeline = vec![
+            doc! { "$match": { "guild_id": guild_id } },
+            doc! { "$unwind": "$lawsuits" },
+            doc! { "$replaceRoot": { "newRoot": "$lawsuits" } },
+            doc! { "$match": lawsuit_match },
+            doc! { "$facet": {
+                "totals": [{ "$group": {
+                    "_id": Bson::Null,
+                    "total": { "$sum": 1 },
+                    "active": { "$sum": { "$cond": [is_closed, 0, 1] } },
+                    "convictions": { "$sum": { "$cond": [{ "$eq": ["$guilty", true] }, 1, 0] } },
+                    "acquittals": { "$sum": { "$cond": [{ "$eq": ["$guilty", false] }, 1, 0] } },
+                    "withdrawn": outcome_count("withdrawn"),
+                    "dismissed": outcome_count("dismissed"),
+                    "settled": outcome_count("settled"),
+                    "guilty_pleas": { "$sum": { "$cond": [{ "$eq": ["$plea", guilty_plea] }, 1, 0] } },
+                    "average_duration_millis": { "$avg": { "$cond": [
+                        has_duration,
+                        { "$subtract": ["$closed_at", "$opened_at"] },
+                        Bson::Null,
+                    ] } },
+                } }],
+                "judges": ranking("$judge"),
+                "lawyers": lawyer_ranking,
+                "accused": ranking("$accused"),
+                "weeks": [
+                    { "$match": { "opened_at": { "$ne": Bson::Null } } },
+                    { "$group": { "_id": week_start, "count": { "$sum": 1 } } },
+                    { "$sort": { "_id": 1 } },
+                ],
+            } },
+            doc! { "$project": {
+                "total": first_total("total"),
+                "active": first_total("active"),
+                "convictions": first_total("convictions"),
+                "acquittals": first_total("acquittals"),
+                "withdrawn": first_total("withdrawn"),
+                "dismissed": first_total("dismissed"),
+                "settled": first_total("settled"),
+                "guilty_pleas": first_total("guilty_pleas"),
+                "average_duration_millis": { "$arrayElemAt": ["$totals.average_duration_millis", 0] },
+                "judges": 1,
+                "lawyers": 1,
+                "accused": 1,
+                "weeks": 1,
+            } },
+        ];
+
+        let stats = coll
+            .aggregate(pipeline, None)
+            .await
+            .wrap_err("aggregate lawsuit stats")?
+            .try_next()
+            .await
+            .wrap_err("read lawsuit stats")?;
+
+        match stats {
+            Some(stats) => bson::from_document(stats).wrap_err("invalid lawsuit stats"),
+            None => Ok(LawsuitStats::default()),
+        }
+    }
+
+    /// Sums up the time spent in prison in the guild, counting ongoing stays up to `now`.
+    #[tracing::instrument(skip(self))]
+    pub async fn aggregate_prison_millis(
+        &self,
+        guild_id: SnowflakeId,
+        filter: StatsFilter,
+        now: DateTime,
+    ) -> Result<i64> {
+        let coll = self.prison_coll();
+
+        let mut prison_match = doc! { "guild_id": guild_id };
+        if let Some(since) = filter.since {
+            prison_match.insert("arrested_at", doc! { "$gte": since });
+        }
+        if let Some(user) = filter.user {
+            prison_match.insert("user_id", user);
+        }
+
+        let pipeline = vec![
+            doc! { "$match": prison_match },
+            doc! { "$group": {
+                "_id": Bson::Null,
+                "millis": { "$sum": { "$subtract": [{ "$ifNull": ["$released_at", now] }, "$arrested_at"] } },
+            } },
+        ];
+
+        let total = coll
+            .aggregate(pipeline, None)
+            .await
+            .wrap_err("aggregate prison time")?
+            .try_next()
+            .await
+            .wrap_err("read prison time")?;
+
+        match total.map(|total| total.get("millis").cloned()) {
+            Some(Some(Bson::Int64(millis))) => Ok(millis),
+            Some(Some(Bson::Int32(millis))) => Ok(millis.into()),
+            _ => Ok(0),
+        }
+    }
+
+    /// Returns `true` if the achievement was not unlocked before.
+    #[tracing::instrument(skip(self))]
+    pub async fn unlock_achievement(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+        achievement: &str,
+    ) -> Result<bool> {
+        let coll = self.achievement_coll();
+
+        let result = coll
+            .update_one(
+                doc! { "guild_id": guild_id, "user_id": user_id, "achievement": achievement },
+                doc! {
+                    "$setOnInsert": {
+                        "guild_id": guild_id,
+                        "user_id": user_id,
+                        "achievement": achievement,
+                        "unlocked_at": DateTime::now(),
+                    }
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .wrap_err("unlock achievement")?;
+
+        Ok(result.upserted_id.is_some())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_achievements(
+        &self,
+        guild_id: SnowflakeId,
+        user_id: SnowflakeId,
+    ) -> Result<Vec<UnlockedAchievement>> {
+        let coll = self.achievement_coll();
+
+        coll.find(doc! { "guild_id": guild_id, "user_id": user_id }, None)
+            .await
+            .wrap_err("find achievements")?
+            .try_collect()
+            .await
+            .wrap_err("collect achievements")
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_guild_prison_entries(
+        &self,
+        guild_id: SnowflakeId,
+    ) -> Result<Vec<PrisonEntry>> {
+        let coll = self.prison_coll();
+
+        coll.find(doc! { "guild_id": guild_id }, None)
+            .await
+            .wrap_err("find guild prison entries")?
+            .try_collect()
+            .await
+            .wrap_err("collect guild prison entries")
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn find_guild_achievements(
+        &self,
+        guild_id: SnowflakeId,
+    ) -> Result<Vec<UnlockedAchievement>> {
+        let coll = self.achievement_coll();
+
+        coll.find(doc! { "guild_id": guild_id }, None)
+            .await
+            .wrap_err("find guild achievements")?
+            .try_collect()
+            .await
+            .wrap_err("collect guild achievements")
+    }
+
+    /// Replaces the state, the prison entries and the achievements of the guild. Transactions need
+    /// a replica set, so the current data is kept in the trash until the import went through and
+    /// written back if it fails.
+    #[tracing::instrument(skip_all, fields(guild_id = %state.guild_id))]
+    pub async fn import_guild(
+        &self,
+        state: &State,
+        prison: &[PrisonEntry],
+        achievements: &[UnlockedAchievement],
+    ) -> Result<()> {
+        let guild_id = state.guild_id;
+
+        let backup = GuildExport::load(self, guild_id).await?;
+        let backup_id = self
+            .trash_coll()
+            .insert_one(
+                DeletedGuild {
+                    guild_id,
+                    deleted_at: DateTime::now(),
+                    data: backup.clone(),
+                },
+                None,
+            )
+            .await
+            .wrap_err("keep guild backup")?
+            .inserted_id;
+
+        if let Err(err) = self.replace_guild(state, prison, achievements).await {
+            let rollback = self
+                .replace_guild(&backup.state, &backup.prison, &backup.achievements)
+                .await;
+            if let Err(rollback_err) = rollback {
+                // the backup stays in the trash to restore it by hand
+                error!(?rollback_err, %backup_id, "Failed to roll back import");
+                return Err(err);
+            }
+            self.delete_backup(backup_id).await?;
+            return Err(err);
+        }
+
+        self.delete_backup(backup_id).await?;
+
+        info!(%guild_id, "Imported guild");
+        Ok(())
+    }
+
+    async fn delete_backup(&self, backup_id: Bson) -> Result<()> {
+        self.trash_coll()
+            .delete_one(doc! { "_id": backup_id }, None)
+            .await
+            .wrap_err("delete guild backup")?;
+        Ok(())
+    }
+
+    async fn replace_guild(
+        &self,
+        state: &State,
+        prison: &[PrisonEntry],
+        achievements: &[UnlockedAchievement],
+    ) -> Result<()> {
+        let guild_id = state.guild_id;
+
+        self.state_coll()
+            .replace_one(
+                doc! { "guild_id": &guild_id },
+                state,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .wrap_err("replace state")?;
+
+        let prison_coll = self.prison_coll();
+        prison_coll
+            .delete_many(doc! { "guild_id": guild_id }, None)
+            .await
+            .wrap_err("delete prison entries")?;
+        if !prison.is_empty() {
+            prison_coll
+                .insert_many(prison, None)
+                .await
+                .wrap_err("insert prison entries")?;
+        }
+
+        let achievement_coll = self.achievement_coll();
+        achievement_coll
+            .delete_many(doc! { "guild_id": guild_id }, None)
+            .await
+            .wrap_err("delete achievements")?;
+        if !achievements.is_empty() {
+            achievement_coll
+                .insert_many(achievements, None)
+                .await
+                .wrap_err("insert achievements")?;
+        }
+
+        Ok(())
+    }
+
+    /// Migrates the documents of the collection that are older than the migration. Returns how
+    /// many there were.
+    #[tracing::instrument(skip_all, fields(version = migration.version))]
+    pub async fn apply_migration(&self, migration: &Migration) -> Result<u64> {
+        let coll = self.db.collection::<Document>(migration.collection);
+        // `$lt` would skip the documents from before migrations, they have no version at all
+        let outdated = doc! { "schema_version": { "$not": { "$gte": migration.version } } };
+
+        let mut cursor = coll
+            .find(outdated, None)
+            .await
+            .wrap_err("find outdated documents")?;
+
+        let mut migrated = 0;
+        while let Some(mut document) = cursor
+            .try_next()
+            .await
+            .wrap_err("fetch outdated document")?
+        {
+            let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+            let version = document
+                .get("schema_version")
+                .cloned()
+                .unwrap_or(Bson::Null);
+            migration
+                .apply(&mut document)
+                .wrap_err_with(|| format!("migrate document {id}"))?;
+
+            // skip documents another instance migrated in the meantime
+            let result = coll
+                .replace_one(
+                    doc! { "_id": id, "schema_version": version },
+                    document,
+                    None,
+                )
+                .await
+                .wrap_err("replace migrated document")?;
+            migrated += result.modified_count;
+        }
+
+        Ok(migrated)
+    }
+
+    /// Remembers that the migration ran. Returns `true` if it ran for the first time.
+    #[tracing::instrument(skip_all, fields(version = migration.version))]
+    pub async fn record_migration(&self, migration: &Migration) -> Result<bool> {
+        let result = self
+            .migration_coll()
+            .update_one(
+                doc! { "version": migration.version },
+                doc! {
+                    "$setOnInsert": {
+                        "version": migration.version,
+                        "name": migration.name,
+                        "applied_at": DateTime::now(),
+                    }
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .wrap_err("record migration")?;
+
+        Ok(result.upserted_id.is_some())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn ping(&self) -> Result<()> {
+        self.db
+            .run_command(doc! { "ping": 1 }, None)
+            .await
+            .wrap_err("ping mongodb")?;
+        Ok(())
+    }
+
+    fn counter_coll(&self) -> Collection<Document> {
+        self.db.collection("counters")
+    }
+
+    fn state_coll(&self) -> Collection<State> {
+        self.db.collection("state")
+    }
+
+    fn trash_coll(&self) -> Collection<DeletedGuild> {
+        self.db.collection("trash")
+    }
+
+    fn migration_coll(&self) -> Collection<Document> {
+        self.db.collection("migrations")
+    }
+
+    fn prison_coll(&self) -> Collection<PrisonEntry> {
+        self.db.collection("prison")
+    }
+
+    fn achievement_coll(&self) -> Collection<UnlockedAchievement> {
+        self.db.collection("achievements")
+    }
+}
+
+impl RoomStore for Mongo {
+    #[tracing::instrument(skip(self))]
+    async fn claim_free_room(&self, guild_id: SnowflakeId) -> Result<Option<CourtRoom>> {
+        let _ = self.find_or_insert_state(guild_id).await?;
+        let coll = self.state_coll();
+
+        // the positional operator updates the first free room, which we then find in the
+        // document from before the update
+        let state = coll
+            .find_one_and_update(
+                doc! {
+                    "guild_id": &guild_id,
+                    "court_rooms": { "$elemMatch": { "ongoing_lawsuit": false } },
+                },
+                doc! { "$set": { "court_rooms.$.ongoing_lawsuit": true } },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::Before)
+                    .build(),
+            )
+            .await
+            .wrap_err("claim free court room")?;
+
+        Ok(state.and_then(|state| {
+            state
+                .court_rooms
+                .into_iter()
+                .find(|room| !room.ongoing_lawsuit)
+                .map(|room| CourtRoom {
+                    ongoing_lawsuit: true,
+                    ..room
+                })
+        }))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn room_count(&self, guild_id: SnowflakeId) -> Result<usize> {
+        let state = self.find_or_insert_state(guild_id).await?;
+        Ok(state.court_rooms.len())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn add_claimed_room(&self, guild_id: SnowflakeId, room: &CourtRoom) -> Result<()> {
+        self.add_court_room(guild_id, room).await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn release_room(&self, guild_id: SnowflakeId, channel_id: SnowflakeId) -> Result<()> {
+        let coll = self.state_coll();
+
+        coll.update_one(
+            doc! { "guild_id": &guild_id, "court_rooms.channel_id": channel_id },
+            doc! { "$set": { "court_rooms.$.ongoing_lawsuit": false } },
+            None,
+        )
+        .await
+        .wrap_err("release court room")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelling_a_thread_lawsuit_only_removes_the_lawsuit() {
+        let lawsuit_id = Uuid::new();
+
+        let (update, options) = cancel_lawsuit_update(lawsuit_id, &Venue::Thread(SnowflakeId(1)));
+
+        assert_eq!(
+            update,
+            doc! { "$pull": { "lawsuits": { "id": lawsuit_id } } }
+        );
+        assert!(options.array_filters.is_none());
+    }
+
+    #[test]
+    fn cancelling_a_room_lawsuit_frees_the_room() {
+        let lawsuit_id = Uuid::new();
+        let room = CourtRoom {
+            channel_id: SnowflakeId(1),
+            ongoing_lawsuit: true,
+            role_id: SnowflakeId(2),
+            voice_channel_id: None,
+        };
+
+        let (update, options) = cancel_lawsuit_update(lawsuit_id, &Venue::Room(room));
+
+        assert_eq!(
+            update,
+            doc! {
+                "$pull": { "lawsuits": { "id": lawsuit_id } },
+                "$set": { "court_rooms.$[room].ongoing_lawsuit": false },
+            }
+        );
+        assert_eq!(
+            options.array_filters,
+            Some(vec![doc! { "room.channel_id": SnowflakeId(1) }])
+        );
     }
 }
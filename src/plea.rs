@@ -0,0 +1,332 @@
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+
+use color_eyre::Result;
+use mongodb::bson::{doc, DateTime, Uuid};
+use poise::{
+    serenity::model::{
+        interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+        prelude::*,
+    },
+    serenity_prelude::{CreateComponents, Http},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    handler::Response,
+    lawsuit::LawsuitCtx,
+    prison,
+    procedure::CaseEvent,
+    time::{self, MILLIS_PER_MINUTE},
+    venue::Venue,
+    Mongo, WrapErr,
+};
+
+const PLEA_PREFIX: &str = "plea";
+
+/// How the accused answers the charge at the start of the lawsuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Plea {
+    Guilty,
+    NotGuilty,
+    /// The accused accepts the consequences without admitting guilt.
+    NoContest,
+}
+
+impl Plea {
+    /// What counts if the accused doesn't enter a plea in time.
+    pub const DEFAULT: Self = Self::NotGuilty;
+
+    const ALL: [Self; 3] = [Self::Guilty, Self::NotGuilty, Self::NoContest];
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::Guilty => "guilty",
+            Self::NotGuilty => "not_guilty",
+            Self::NoContest => "no_contest",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|plea| plea.id() == id)
+    }
+}
+
+impl Display for Plea {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Guilty => "Schuldig",
+            Self::NotGuilty => "Nicht schuldig",
+            Self::NoContest => "Keine Anfechtung",
+        })
+    }
+}
+
+/// The longest time the accused can be given to enter a plea.
+pub const MAX_TIMEOUT_MINUTES: u64 = 30 * 24 * 60;
+
+/// The longest prison sentence after a guilty plea.
+pub const MAX_GUILTY_SENTENCE_HOURS: f64 = 365.0 * 24.0;
+
+/// How the plea phase of a lawsuit works.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PleaConfig {
+    /// How long the accused has to enter a plea before `Plea::DEFAULT` counts.
+    pub timeout_minutes: u64,
+    /// The prison sentence after a guilty plea, which closes the lawsuit right away. Without a
+    /// sentence, the judge rules as usual.
+    pub guilty_sentence_hours: Option<f64>,
+}
+
+impl Default for PleaConfig {
+    fn default() -> Self {
+        Self {
+            timeout_minutes: 24 * 60,
+            guilty_sentence_hours: None,
+        }
+    }
+}
+
+impl PleaConfig {
+    pub fn deadline(&self, now: DateTime) -> DateTime {
+        time::saturating_after(now, self.timeout_minutes, MILLIS_PER_MINUTE)
+    }
+}
+
+/// Adds the buttons for the plea of the accused.
+pub fn plea_buttons(components: &mut CreateComponents, lawsuit_id: Uuid) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        for plea in Plea::ALL {
+            let style = match plea {
+                Plea::Guilty => ButtonStyle::Danger,
+                Plea::NotGuilty => ButtonStyle::Success,
+                Plea::NoContest => ButtonStyle::Secondary,
+            };
+            row.create_button(|b| {
+                b.custom_id(format!("{PLEA_PREFIX}:{}:{lawsuit_id}", plea.id()))
+                    .label(plea.to_string())
+                    .style(style)
+            });
+        }
+        row
+    })
+}
+
+fn parse_plea_id(custom_id: &str) -> Option<(Plea, Uuid)> {
+    let mut parts = custom_id.split(':');
+    if parts.next()? != PLEA_PREFIX {
+        return None;
+    }
+    let plea = Plea::from_id(parts.next()?)?;
+    let lawsuit_id = Uuid::parse_str(parts.next()?).ok()?;
+    Some((plea, lawsuit_id))
+}
+
+impl LawsuitCtx {
+    /// Enters the plea of the accused, or the default plea if `by_default` is set because the
+    /// deadline has passed. A guilty plea closes the lawsuit right away if the guild has a
+    /// sentence for it.
+    pub async fn enter_plea(
+        &mut self,
+        plea: Plea,
+        by_default: bool,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        let guild_id = self.guild_id;
+        let accused = self.lawsuit.accused;
+
+        let entered = self
+            .mongo_client
+            .enter_plea(guild_id.into(), self.lawsuit.id, plea)
+            .await?;
+        if !entered {
+            return Ok(Err(Response(
+                "es isch scho plädiert worde oder de prozess isch abgschlosse".to_string(),
+            )));
+        }
+        self.lawsuit.plea = Some(plea);
+
+        // answering the charge counts as showing up
+        if !by_default && !self.lawsuit.appeared {
+            self.mongo_client
+                .set_lawsuit(
+                    guild_id.into(),
+                    self.lawsuit.id,
+                    doc! { "lawsuits.$.appeared": true },
+                )
+                .await?;
+            self.lawsuit.appeared = true;
+        }
+
+        let (actor, event, announcement) = if by_default {
+            (
+                None,
+                CaseEvent::PleaDefaulted { plea },
+                format!("<@{accused}> hät nöd plädiert, es gilt: **{plea}**"),
+            )
+        } else {
+            (
+                Some(accused),
+                CaseEvent::Plea { plea },
+                format!("<@{accused}> plädiert: **{plea}**"),
+            )
+        };
+        self.log(actor, event).await?;
+
+        ChannelId::from(venue.channel_id())
+            .say(&self.http, announcement)
+            .await
+            .wrap_err("announce plea")?;
+
+        info!(lawsuit_id = %self.lawsuit.id, ?plea, by_default, "Entered plea");
+
+        if plea == Plea::Guilty {
+            let state = self
+                .mongo_client
+                .find_or_insert_state(guild_id.into())
+                .await?;
+            if let Some(hours) = state.plea.guilty_sentence_hours {
+                return self.sentence_after_guilty_plea(hours, venue).await;
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Closes the lawsuit with the reduced sentence for a guilty plea.
+    async fn sentence_after_guilty_plea(
+        &mut self,
+        hours: f64,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        let verdict = format!("Schuldig nach Geständnis, {hours} Stunden Gefängnis");
+        let judge = self.lawsuit.judge.into();
+
+        // the sentence counts as soon as the lawsuit is closed, even if the cleanup failed
+        let closed = match self
+            .rule_verdict(true, judge, verdict, true, venue.clone())
+            .await?
+        {
+            Ok(closed) => closed,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        let release_at = time::saturating_after_hours(DateTime::now(), hours);
+        let imprisoned = prison::imprison(
+            &self.mongo_client,
+            &self.http,
+            self.guild_id,
+            self.lawsuit.accused,
+            Some(release_at),
+        )
+        .await?;
+        if let Err(response) = imprisoned {
+            return Ok(Err(response));
+        }
+
+        Ok(closed.into_result())
+    }
+}
+
+/// Handles a click on a plea button in the court room. Returns `false` if the interaction wasn't
+/// a plea.
+pub async fn handle_plea_interaction(
+    mongo: &Mongo,
+    http: &Arc<Http>,
+    interaction: &MessageComponentInteraction,
+) -> Result<bool> {
+    let (plea, lawsuit_id) = match parse_plea_id(&interaction.data.custom_id) {
+        Some(parsed) => parsed,
+        None => return Ok(false),
+    };
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(false),
+    };
+
+    // a guilty plea might close the whole lawsuit, which takes longer than discord waits
+    interaction
+        .create_interaction_response(http, |response| {
+            response
+                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|data| data.ephemeral(true))
+        })
+        .await
+        .wrap_err("defer plea interaction")?;
+
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    let lawsuit = state
+        .lawsuits
+        .iter()
+        .find(|lawsuit| lawsuit.id == lawsuit_id);
+
+    let response = match lawsuit {
+        Some(lawsuit) if lawsuit.accused != interaction.user.id.into() => {
+            Err(Response("nur de aagklagti cha plädiere".to_string()))
+        }
+        Some(lawsuit) => {
+            let venue = state.venue(lawsuit.court_room);
+            let mut lawsuit_ctx = LawsuitCtx {
+                lawsuit: lawsuit.clone(),
+                mongo_client: mongo.clone(),
+                http: http.clone(),
+                guild_id,
+            };
+            lawsuit_ctx.enter_plea(plea, false, &venue).await?
+        }
+        None => Err(Response("de prozess gits nüme".to_string())),
+    };
+
+    let content = match response {
+        Ok(()) => format!("du häsch plädiert: **{plea}**"),
+        Err(response) => response.to_string(),
+    };
+
+    interaction
+        .edit_original_interaction_response(http, |response| response.content(content))
+        .await
+        .wrap_err("respond to plea interaction")?;
+
+    Ok(true)
+}
+
+/// Enters the default plea in every lawsuit whose accused hasn't answered in time.
+#[tracing::instrument(skip_all)]
+pub async fn enter_default_pleas(mongo: &Mongo, http: &Arc<Http>) -> Result<()> {
+    let now = DateTime::now();
+
+    for state in mongo.find_states_with_expired_plea_deadlines(now).await? {
+        for lawsuit in &state.lawsuits {
+            let expired = lawsuit.plea.is_none()
+                && !lawsuit.is_closed()
+                && lawsuit
+                    .plea_deadline
+                    .is_some_and(|deadline| deadline <= now);
+            if !expired {
+                continue;
+            }
+
+            let venue = state.venue(lawsuit.court_room);
+            let mut lawsuit_ctx = LawsuitCtx {
+                lawsuit: lawsuit.clone(),
+                mongo_client: mongo.clone(),
+                http: http.clone(),
+                guild_id: state.guild_id.into(),
+            };
+
+            match lawsuit_ctx.enter_plea(Plea::DEFAULT, true, &venue).await {
+                Ok(Ok(())) => {}
+                Ok(Err(response)) => {
+                    info!(%response, lawsuit_id = %lawsuit.id, "Could not enter default plea")
+                }
+                Err(err) => error!(?err, lawsuit_id = %lawsuit.id, "Failed to enter default plea"),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use color_eyre::Result;
+use mongodb::bson::DateTime;
+use poise::{serenity::model::prelude::*, serenity_prelude::Http};
+use tracing::{error, info};
+
+use crate::{
+    discord::{discord_error_code, UNKNOWN_MEMBER},
+    handler::Response,
+    model::{PrisonEntry, SnowflakeId},
+    Mongo, WrapErr,
+};
+
+fn missing_role() -> Response {
+    Response("du mosch zerst e rolle setze mit /prison set_role".to_string())
+}
+
+/// Puts the user in prison, until `release_at` or until someone releases them.
+pub async fn imprison(
+    mongo: &Mongo,
+    http: &Http,
+    guild_id: GuildId,
+    user_id: SnowflakeId,
+    release_at: Option<DateTime>,
+) -> Result<Result<(), Response>> {
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    let role = match state.prison_role {
+        Some(role) => role,
+        None => return Ok(Err(missing_role())),
+    };
+
+    mongo
+        .add_to_prison(guild_id.into(), user_id, release_at)
+        .await?;
+
+    guild_id
+        .member(http, user_id)
+        .await
+        .wrap_err("fetching guild member")?
+        .add_role(http, role)
+        .await
+        .wrap_err("add guild member role")?;
+
+    Ok(Ok(()))
+}
+
+/// Lets the user out of prison.
+pub async fn release(
+    mongo: &Mongo,
+    http: &Http,
+    guild_id: GuildId,
+    user_id: SnowflakeId,
+) -> Result<Result<(), Response>> {
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    let role = match state.prison_role {
+        Some(role) => role,
+        None => return Ok(Err(missing_role())),
+    };
+
+    mongo.remove_from_prison(guild_id.into(), user_id).await?;
+
+    let mut member = match guild_id.member(http, user_id).await {
+        Ok(member) => member,
+        // the prison entry is closed, they won't get the role back when they rejoin
+        Err(err) if discord_error_code(&err) == Some(UNKNOWN_MEMBER) => return Ok(Ok(())),
+        Err(err) => return Err(err).wrap_err("fetching guild member"),
+    };
+    member
+        .remove_role(http, role)
+        .await
+        .wrap_err("remove guild member role")?;
+
+    Ok(Ok(()))
+}
+
+/// Releases everyone whose sentence is over, in every guild.
+#[tracing::instrument(skip_all)]
+pub async fn release_due_prisoners(mongo: &Mongo, http: &Http) -> Result<()> {
+    let due = mongo.find_due_prison_releases(DateTime::now()).await?;
+
+    for PrisonEntry {
+        guild_id, user_id, ..
+    } in due
+    {
+        match release(mongo, http, guild_id.into(), user_id).await {
+            Ok(Ok(())) => info!(%guild_id, %user_id, "Released prisoner after their sentence"),
+            Ok(Err(response)) => {
+                // without a prison role there is no role to take away, the sentence is over anyway
+                info!(%guild_id, %user_id, %response, "Could not release prisoner");
+                mongo.remove_from_prison(guild_id, user_id).await?;
+            }
+            Err(err) => error!(?err, %guild_id, %user_id, "Failed to release prisoner"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use color_eyre::{eyre::eyre, Result};
+use mongodb::bson::DateTime;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    handler::Response,
+    lawsuit::Lawsuit,
+    model::{PrisonEntry, SnowflakeId, UnlockedAchievement},
+    procedure::CaseEvent,
+    Mongo,
+};
+
+/// Discord ids below this would have been created before discord existed, so tombstones never
+/// collide with real users.
+const TOMBSTONE_LIMIT: u64 = 1 << 22;
+
+/// A new id that stands in for an erased member. Every erasure gets its own from a counter, so
+/// the records of different erased members stay apart.
+async fn tombstone_id(mongo: &Mongo) -> Result<SnowflakeId> {
+    let id = mongo.next_counter_value("tombstone").await?;
+    match u64::try_from(id) {
+        Ok(id) if id < TOMBSTONE_LIMIT => Ok(SnowflakeId(id)),
+        _ => Err(eyre!("tombstone ids are used up")),
+    }
+}
+
+fn mentions(text: &str, user_id: SnowflakeId) -> bool {
+    text.contains(&format!("<@{user_id}>")) || text.contains(&format!("<@!{user_id}>"))
+}
+
+fn replace_mentions(text: &str, user_id: SnowflakeId, tombstone: SnowflakeId) -> String {
+    text.replace(&format!("<@!{user_id}>"), &format!("<@{tombstone}>"))
+        .replace(&format!("<@{user_id}>"), &format!("<@{tombstone}>"))
+}
+
+impl Lawsuit {
+    /// Every member the lawsuit stores anything about.
+    fn referenced_users(&self) -> Vec<SnowflakeId> {
+        let mut users = vec![self.plaintiff, self.accused, self.judge];
+        users.extend(self.plaintiff_lawyer);
+        users.extend(self.accused_lawyer);
+        users.extend(self.floor);
+        users.extend(self.contempts.iter().map(|contempt| contempt.user));
+        users.extend(self.witnesses.iter().map(|witness| witness.user));
+        for entry in &self.log {
+            users.extend(entry.actor);
+            match &entry.event {
+                CaseEvent::FloorGranted { to } => users.push(*to),
+                CaseEvent::Contempt { user, .. }
+                | CaseEvent::ContemptLifted { user }
+                | CaseEvent::WitnessSummoned { user }
+                | CaseEvent::WitnessAccepted { user }
+                | CaseEvent::WitnessDeclined { user }
+                | CaseEvent::WitnessDismissed { user } => users.push(*user),
+                _ => {}
+            }
+        }
+        users
+    }
+
+    pub fn references(&self, user_id: SnowflakeId) -> bool {
+        self.referenced_users().contains(&user_id)
+            || mentions(&self.reason, user_id)
+            || self
+                .verdict
+                .as_deref()
+                .is_some_and(|verdict| mentions(verdict, user_id))
+    }
+}
+
+/// What a single guild stores about a member.
+#[derive(Debug, Serialize)]
+pub struct GuildMemberData {
+    pub guild_id: SnowflakeId,
+    pub lawyer: bool,
+    pub lawsuits: Vec<Lawsuit>,
+}
+
+/// Everything the bot stores about a member, in every guild.
+#[derive(Debug, Serialize)]
+pub struct MemberData {
+    pub user_id: SnowflakeId,
+    pub exported_at: DateTime,
+    pub guilds: Vec<GuildMemberData>,
+    pub prison: Vec<PrisonEntry>,
+    pub achievements: Vec<UnlockedAchievement>,
+}
+
+impl MemberData {
+    pub async fn load(mongo: &Mongo, user_id: SnowflakeId) -> Result<Self> {
+        let guilds = mongo
+            .find_states_referencing(user_id)
+            .await?
+            .into_iter()
+            .map(|state| GuildMemberData {
+                guild_id: state.guild_id,
+                lawyer: state.lawyers.contains(&user_id),
+                lawsuits: state
+                    .lawsuits
+                    .into_iter()
+                    .filter(|lawsuit| lawsuit.references(user_id))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Self {
+            user_id,
+            exported_at: DateTime::now(),
+            guilds,
+            prison: mongo.find_user_prison_entries(user_id).await?,
+            achievements: mongo.find_user_achievements(user_id).await?,
+        })
+    }
+
+    pub fn lawsuit_count(&self) -> usize {
+        self.guilds.iter().map(|guild| guild.lawsuits.len()).sum()
+    }
+}
+
+/// What was erased about a member.
+#[derive(Debug, Default)]
+pub struct Erasure {
+    pub lawsuits: usize,
+    pub prison_entries: u64,
+    pub achievements: u64,
+}
+
+/// Replaces the mentions of the member in the texts of the lawsuit. The ids are replaced for every
+/// lawsuit of the guild at once, see `Mongo::anonymize_lawsuits`.
+async fn anonymize_texts(
+    mongo: &Mongo,
+    guild_id: SnowflakeId,
+    lawsuit: &Lawsuit,
+    user_id: SnowflakeId,
+    tombstone: SnowflakeId,
+) -> Result<()> {
+    if mentions(&lawsuit.reason, user_id) {
+        let reason = replace_mentions(&lawsuit.reason, user_id, tombstone);
+        mongo
+            .replace_lawsuit_text(guild_id, lawsuit.id, "reason", &lawsuit.reason, &reason)
+            .await?;
+    }
+    if let Some(verdict) = lawsuit.verdict.as_deref() {
+        if mentions(verdict, user_id) {
+            let anonymized = replace_mentions(verdict, user_id, tombstone);
+            mongo
+                .replace_lawsuit_text(guild_id, lawsuit.id, "verdict", verdict, &anonymized)
+                .await?;
+        }
+    }
+    for entry in &lawsuit.log {
+        if let CaseEvent::Objection { reason } = &entry.event {
+            if mentions(reason, user_id) {
+                let anonymized = replace_mentions(reason, user_id, tombstone);
+                mongo
+                    .replace_objection_reason(guild_id, lawsuit.id, reason, &anonymized)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the member from every guild. Lawsuits and prison entries are kept for the other
+/// members, with the member replaced by a tombstone, everything else about them is deleted.
+/// Members in an ongoing lawsuit or in prison can't be erased, the bot still needs to know who
+/// they are.
+#[tracing::instrument(skip(mongo))]
+pub async fn erase_member(
+    mongo: &Mongo,
+    user_id: SnowflakeId,
+) -> Result<Result<Erasure, Response>> {
+    let states = mongo.find_states_referencing(user_id).await?;
+
+    let in_open_lawsuit = states
+        .iter()
+        .flat_map(|state| &state.lawsuits)
+        .any(|lawsuit| !lawsuit.is_closed() && lawsuit.references(user_id));
+    if in_open_lawsuit {
+        return Ok(Err(Response(
+            "du bisch no i me laufende prozess, warte bis er abgschlosse isch".to_string(),
+        )));
+    }
+    let prison = mongo.find_user_prison_entries(user_id).await?;
+    if prison.iter().any(|entry| entry.released_at.is_none()) {
+        return Ok(Err(Response(
+            "du bisch no im gfängnis, warte bis du freiglah wirsch".to_string(),
+        )));
+    }
+
+    let tombstone = tombstone_id(mongo).await?;
+    let mut erasure = Erasure::default();
+    let mut guild_ids = Vec::new();
+
+    for state in states {
+        if state.lawyers.contains(&user_id) {
+            mongo.remove_lawyer(state.guild_id, user_id).await?;
+        }
+        for lawsuit in &state.lawsuits {
+            if lawsuit.references(user_id) {
+                anonymize_texts(mongo, state.guild_id, lawsuit, user_id, tombstone).await?;
+                erasure.lawsuits += 1;
+            }
+        }
+        mongo
+            .anonymize_lawsuits(state.guild_id, user_id, tombstone)
+            .await?;
+        guild_ids.push(state.guild_id);
+    }
+
+    erasure.prison_entries = mongo.anonymize_prison_entries(user_id, tombstone).await?;
+    erasure.achievements = mongo.delete_user_achievements(user_id).await?;
+
+    // the snapshots for undoing a clear would bring the member back
+    mongo.delete_trash_referencing(user_id, &guild_ids).await?;
+
+    info!(?erasure, "Erased member");
+    Ok(Ok(erasure))
+}
@@ -0,0 +1,369 @@
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+use color_eyre::{eyre::ContextCompat, Result};
+use mongodb::bson::{doc, DateTime};
+use poise::{serenity::model::prelude::*, serenity_prelude::Http};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    gallery::Visibility,
+    handler::Response,
+    lawsuit::{Lawsuit, LawsuitCtx},
+    model::{CourtRoom, SnowflakeId},
+    plea::Plea,
+    venue::Venue,
+    Mongo, WrapErr,
+};
+
+/// The longest time someone can be held in contempt of court.
+pub const MAX_CONTEMPT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Someone who may not write in the court room until `until`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contempt {
+    pub user: SnowflakeId,
+    pub until: DateTime,
+}
+
+/// Something that happened during the hearing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CaseEvent {
+    FloorGranted { to: SnowflakeId },
+    FloorOpened,
+    Objection { reason: String },
+    ObjectionSustained,
+    ObjectionOverruled,
+    Contempt { user: SnowflakeId, until: DateTime },
+    ContemptLifted { user: SnowflakeId },
+    WitnessSummoned { user: SnowflakeId },
+    WitnessAccepted { user: SnowflakeId },
+    WitnessDeclined { user: SnowflakeId },
+    WitnessDismissed { user: SnowflakeId },
+    VisibilityChanged { visibility: Visibility },
+    Plea { plea: Plea },
+    PleaDefaulted { plea: Plea },
+    AccusedAppeared,
+    AccusedLeft,
+    DefaultJudgmentOffered,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseLogEntry {
+    pub at: DateTime,
+    /// Who caused the event, `None` for things the bot did on its own.
+    pub actor: Option<SnowflakeId>,
+    pub event: CaseEvent,
+}
+
+impl CaseLogEntry {
+    pub fn new(actor: Option<SnowflakeId>, event: CaseEvent) -> Self {
+        Self {
+            at: DateTime::now(),
+            actor,
+            event,
+        }
+    }
+}
+
+impl Display for CaseLogEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<t:{}:T> ", self.at.timestamp_millis() / 1000)?;
+        if let Some(actor) = self.actor {
+            write!(f, "<@{actor}>: ")?;
+        }
+        match &self.event {
+            CaseEvent::FloorGranted { to } => write!(f, "Wort erteilt an <@{to}>"),
+            CaseEvent::FloorOpened => f.write_str("Wort für alle freigegeben"),
+            CaseEvent::Objection { reason } => write!(f, "Einspruch: {reason}"),
+            CaseEvent::ObjectionSustained => f.write_str("Einspruch stattgegeben"),
+            CaseEvent::ObjectionOverruled => f.write_str("Einspruch abgelehnt"),
+            CaseEvent::Contempt { user, until } => write!(
+                f,
+                "<@{user}> wegen Missachtung des Gerichts bis <t:{}:T> stummgeschaltet",
+                until.timestamp_millis() / 1000
+            ),
+            CaseEvent::ContemptLifted { user } => write!(f, "<@{user}> darf wieder sprechen"),
+            CaseEvent::WitnessSummoned { user } => write!(f, "<@{user}> als Zeuge vorgeladen"),
+            CaseEvent::WitnessAccepted { user } => write!(f, "<@{user}> erscheint als Zeuge"),
+            CaseEvent::WitnessDeclined { user } => write!(f, "<@{user}> verweigert die Aussage"),
+            CaseEvent::WitnessDismissed { user } => write!(f, "Zeuge <@{user}> entlassen"),
+            CaseEvent::VisibilityChanged {
+                visibility: Visibility::Public,
+            } => f.write_str("Prozess für Zuschauer geöffnet"),
+            CaseEvent::VisibilityChanged {
+                visibility: Visibility::Private,
+            } => f.write_str("Prozess für Zuschauer geschlossen"),
+            CaseEvent::Plea { plea } => write!(f, "Plädiert auf {plea}"),
+            CaseEvent::PleaDefaulted { plea } => {
+                write!(f, "Keine Antwort innert Frist, es gilt {plea}")
+            }
+            CaseEvent::AccusedAppeared => f.write_str("Angeklagter erschienen"),
+            CaseEvent::AccusedLeft => f.write_str("Angeklagter hat den Server verlassen"),
+            CaseEvent::DefaultJudgmentOffered => f.write_str("Versäumnisurteil angeboten"),
+        }
+    }
+}
+
+impl Lawsuit {
+    /// Everyone who may not write in the court room right now, because someone else has the
+    /// floor or because they are held in contempt.
+    pub fn muted_participants(&self, now: DateTime) -> Vec<SnowflakeId> {
+        self.participants()
+            .into_iter()
+            .filter(|&user| {
+                let without_floor = self
+                    .floor
+                    .is_some_and(|holder| holder != user && user != self.judge);
+                let in_contempt = self
+                    .contempts
+                    .iter()
+                    .any(|contempt| contempt.user == user && contempt.until > now);
+                without_floor || in_contempt
+            })
+            .collect()
+    }
+}
+
+impl LawsuitCtx {
+    /// Gives the floor to a single participant, or to everyone again with `None`.
+    pub async fn grant_floor(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        holder: Option<SnowflakeId>,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        if self.lawsuit.judge != user_id.into() && !permission_override {
+            return Ok(Err(Response("du häsch kei recht für da!".to_string())));
+        }
+        if let Some(holder) = holder {
+            if !self.lawsuit.is_participant(holder) {
+                return Ok(Err(Response(format!(
+                    "<@{holder}> isch nöd am prozess beteiligt"
+                ))));
+            }
+        }
+
+        let room = match ordered_room(venue) {
+            Ok(room) => room,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        let previous = std::mem::replace(&mut self.lawsuit.floor, holder);
+        if let Err(err) = self.apply_order(room).await {
+            self.lawsuit.floor = previous;
+            return Err(err);
+        }
+
+        self.mongo_client
+            .set_lawsuit(
+                self.guild_id.into(),
+                self.lawsuit.id,
+                doc! { "lawsuits.$.floor": holder },
+            )
+            .await?;
+
+        let event = match holder {
+            Some(to) => CaseEvent::FloorGranted { to },
+            None => CaseEvent::FloorOpened,
+        };
+        self.log(Some(user_id.into()), event).await?;
+
+        Ok(Ok(()))
+    }
+
+    /// Mutes the participant in the court room for the given time.
+    pub async fn hold_in_contempt(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        user: SnowflakeId,
+        duration: Duration,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        if self.lawsuit.judge != user_id.into() && !permission_override {
+            return Ok(Err(Response("du häsch kei recht für da!".to_string())));
+        }
+        if !self.lawsuit.is_participant(user) || user == self.lawsuit.judge {
+            return Ok(Err(Response(format!(
+                "<@{user}> cha nöd wegen missachtig vom gricht gstraft werde"
+            ))));
+        }
+
+        let room = match ordered_room(venue) {
+            Ok(room) => room,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        let until =
+            DateTime::from_millis(DateTime::now().timestamp_millis() + duration.as_millis() as i64);
+        let contempt = Contempt { user, until };
+
+        self.lawsuit.contempts.push(contempt.clone());
+        if let Err(err) = self.apply_order(room).await {
+            self.lawsuit.contempts.pop();
+            return Err(err);
+        }
+
+        self.mongo_client
+            .add_contempt(self.guild_id.into(), self.lawsuit.id, &contempt)
+            .await?;
+
+        self.log(Some(user_id.into()), CaseEvent::Contempt { user, until })
+            .await?;
+
+        Ok(Ok(()))
+    }
+
+    /// Lets everyone whose contempt has expired write again.
+    pub async fn lift_expired_contempts(&mut self, venue: &Venue) -> Result<()> {
+        let now = DateTime::now();
+        let (expired, remaining) = std::mem::take(&mut self.lawsuit.contempts)
+            .into_iter()
+            .partition::<Vec<_>, _>(|contempt| contempt.until <= now);
+        self.lawsuit.contempts = remaining;
+
+        self.mongo_client
+            .remove_expired_contempts(self.guild_id.into(), self.lawsuit.id, now)
+            .await?;
+
+        if self.lawsuit.is_closed() {
+            return Ok(());
+        }
+
+        for contempt in &expired {
+            self.log(
+                None,
+                CaseEvent::ContemptLifted {
+                    user: contempt.user,
+                },
+            )
+            .await?;
+        }
+
+        match ordered_room(venue) {
+            Ok(room) => self.apply_order(room).await?,
+            Err(response) => info!(%response, "Could not lift contempt"),
+        }
+
+        for contempt in expired {
+            ChannelId::from(venue.channel_id())
+                .say(&self.http, format!("<@{}> dörf wieder rede", contempt.user))
+                .await
+                .wrap_err("announce lifted contempt")?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn log(&mut self, actor: Option<SnowflakeId>, event: CaseEvent) -> Result<()> {
+        let entry = CaseLogEntry::new(actor, event);
+        self.mongo_client
+            .log_case_event(self.guild_id.into(), self.lawsuit.id, &entry)
+            .await?;
+
+        info!(lawsuit_id = %self.lawsuit.id, %entry, "Logged case event");
+        self.lawsuit.log.push(entry);
+
+        // the first event of the hearing puts the lawsuit in session
+        if self.lawsuit.log.len() == 1 {
+            if let Err(err) = self.update_docket().await {
+                error!(?err, "Failed to update docket");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the permissions of the participants in the court room according to who has the floor
+    /// and who is held in contempt.
+    async fn apply_order(&self, room: &CourtRoom) -> Result<()> {
+        let channel_id = ChannelId::from(room.channel_id);
+        let channel = channel_id
+            .to_channel(&self.http)
+            .await
+            .wrap_err("fetch court room")?
+            .guild()
+            .wrap_err("court room is not in a guild")?;
+
+        let muted = self.lawsuit.muted_participants(DateTime::now());
+
+        for user in self.lawsuit.participants() {
+            let kind = PermissionOverwriteType::Member(user.into());
+            let is_muted = channel
+                .permission_overwrites
+                .iter()
+                .any(|overwrite| overwrite.kind == kind);
+
+            match (muted.contains(&user), is_muted) {
+                (true, false) => channel_id
+                    .create_permission(
+                        &self.http,
+                        &PermissionOverwrite {
+                            allow: Permissions::empty(),
+                            deny: Permissions::SEND_MESSAGES,
+                            kind,
+                        },
+                    )
+                    .await
+                    .wrap_err("mute participant")?,
+                (false, true) => channel_id
+                    .delete_permission(&self.http, kind)
+                    .await
+                    .wrap_err("unmute participant")?,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The court room in which the order of the hearing can be enforced. Threads can't be, as their
+/// permissions come from the parent channel.
+fn ordered_room(venue: &Venue) -> Result<&CourtRoom, Response> {
+    match venue {
+        Venue::Room(room) => Ok(room),
+        Venue::Thread(_) => Err(Response(
+            "i me thread chan i niemertem s schriibe verbüte".to_string(),
+        )),
+    }
+}
+
+/// Lifts all contempts that have expired, in every guild.
+#[tracing::instrument(skip_all)]
+pub async fn lift_expired_contempts(mongo: &Mongo, http: &Arc<Http>) -> Result<()> {
+    let now = DateTime::now();
+
+    for state in mongo.find_states_with_expired_contempts(now).await? {
+        for lawsuit in &state.lawsuits {
+            if !lawsuit
+                .contempts
+                .iter()
+                .any(|contempt| contempt.until <= now)
+            {
+                continue;
+            }
+
+            let venue = state.venue(lawsuit.court_room);
+            let mut lawsuit_ctx = LawsuitCtx {
+                lawsuit: lawsuit.clone(),
+                mongo_client: mongo.clone(),
+                http: http.clone(),
+                guild_id: state.guild_id.into(),
+            };
+
+            if let Err(err) = lawsuit_ctx.lift_expired_contempts(&venue).await {
+                error!(?err, lawsuit_id = %lawsuit.id, "Failed to lift contempt");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use color_eyre::Result;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lawsuit::Lawsuit,
+    model::{PrisonEntry, SnowflakeId, State},
+    plea::Plea,
+    time::{MILLIS_PER_DAY, MILLIS_PER_HOUR},
+    Mongo,
+};
+
+/// How the reputation score of a member is calculated from their criminal record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationConfig {
+    /// The score of a member with a clean record.
+    pub base: f64,
+    /// Subtracted for every conviction.
+    pub conviction_weight: f64,
+    /// Added for every acquittal.
+    pub acquittal_weight: f64,
+    /// Subtracted for every hour spent in prison.
+    pub prison_hour_weight: f64,
+    /// After how many days an entry only counts half as much.
+    pub half_life_days: f64,
+    /// Members below this score may not serve as judge or lawyer.
+    pub min_reputation: Option<f64>,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            base: 100.0,
+            conviction_weight: 20.0,
+            acquittal_weight: 5.0,
+            prison_hour_weight: 0.5,
+            half_life_days: 90.0,
+            min_reputation: None,
+        }
+    }
+}
+
+impl ReputationConfig {
+    fn decay(&self, at: DateTime, now: DateTime) -> f64 {
+        if self.half_life_days <= 0.0 {
+            return 1.0;
+        }
+        let age_days =
+            (now.timestamp_millis() - at.timestamp_millis()).max(0) as f64 / MILLIS_PER_DAY as f64;
+        0.5_f64.powf(age_days / self.half_life_days)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CriminalRecord {
+    pub convictions: Vec<Lawsuit>,
+    pub acquittals: Vec<Lawsuit>,
+    pub prison_entries: Vec<PrisonEntry>,
+}
+
+impl CriminalRecord {
+    pub async fn load(mongo: &Mongo, state: &State, user_id: SnowflakeId) -> Result<Self> {
+        let prison_entries = mongo.find_prison_history(state.guild_id, user_id).await?;
+        Ok(Self::from_parts(state, user_id, prison_entries))
+    }
+
+    pub fn from_parts(
+        state: &State,
+        user_id: SnowflakeId,
+        prison_entries: Vec<PrisonEntry>,
+    ) -> Self {
+        let (convictions, acquittals) = state
+            .lawsuits
+            .iter()
+            .filter(|lawsuit| {
+                lawsuit.accused == user_id && lawsuit.guilty.is_some() && !lawsuit.is_deleted()
+            })
+            .cloned()
+            .partition(|lawsuit| lawsuit.guilty == Some(true));
+
+        Self {
+            convictions,
+            acquittals,
+            prison_entries,
+        }
+    }
+
+    /// How often the member pleaded guilty, whatever the verdict was.
+    pub fn guilty_pleas(&self) -> usize {
+        self.convictions
+            .iter()
+            .chain(&self.acquittals)
+            .filter(|lawsuit| lawsuit.plea == Some(Plea::Guilty))
+            .count()
+    }
+
+    /// The total time spent in prison, counting ongoing stays up to `now`.
+    pub fn prison_hours(&self, now: DateTime) -> f64 {
+        self.prison_entries
+            .iter()
+            .filter_map(|entry| stay_hours(entry, now))
+            .sum()
+    }
+
+    pub fn reputation(&self, config: &ReputationConfig, now: DateTime) -> f64 {
+        let closed_at = |lawsuit: &Lawsuit| lawsuit.closed_at.unwrap_or(now);
+
+        let convictions: f64 = self
+            .convictions
+            .iter()
+            .map(|lawsuit| config.conviction_weight * config.decay(closed_at(lawsuit), now))
+            .sum();
+
+        let acquittals: f64 = self
+            .acquittals
+            .iter()
+            .map(|lawsuit| config.acquittal_weight * config.decay(closed_at(lawsuit), now))
+            .sum();
+
+        let prison: f64 = self
+            .prison_entries
+            .iter()
+            .filter_map(|entry| {
+                let hours = stay_hours(entry, now)?;
+                let released_at = entry.released_at.unwrap_or(now);
+                Some(config.prison_hour_weight * hours * config.decay(released_at, now))
+            })
+            .sum();
+
+        config.base - convictions + acquittals - prison
+    }
+}
+
+/// How long a prison stay lasted, or has lasted so far if it is still ongoing.
+pub fn stay_hours(entry: &PrisonEntry, now: DateTime) -> Option<f64> {
+    let arrested_at = entry.arrested_at?;
+    let released_at = entry.released_at.unwrap_or(now);
+    let millis = (released_at.timestamp_millis() - arrested_at.timestamp_millis()).max(0);
+    Some(millis as f64 / MILLIS_PER_HOUR as f64)
+}
+
+/// Checks whether the user has a good enough reputation to serve as judge or lawyer.
+pub async fn is_eligible(mongo: &Mongo, state: &State, user_id: SnowflakeId) -> Result<bool> {
+    let min_reputation = match state.reputation.min_reputation {
+        Some(min) => min,
+        None => return Ok(true),
+    };
+
+    let record = CriminalRecord::load(mongo, state, user_id).await?;
+    Ok(record.reputation(&state.reputation, DateTime::now()) >= min_reputation)
+}
@@ -0,0 +1,77 @@
+use color_eyre::Result;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    lawsuit::Lawsuit,
+    time::{self, MILLIS_PER_DAY},
+    Mongo,
+};
+
+/// How long soft-deleted lawsuits can still be restored from a backup of the database.
+const SOFT_DELETE_GRACE_DAYS: u64 = 7;
+
+/// How long the data of a guild is kept after the bot was removed from it, in case it is added
+/// back.
+const DEPARTED_GUILD_DAYS: u64 = 30;
+
+/// How long the snapshots for undoing `/lawsuit clear` are kept. The undo button is long gone by
+/// then.
+const TRASH_DAYS: u64 = 1;
+
+/// The longest retention period that can be set, longer ones might as well keep the data forever.
+pub const MAX_RETENTION_DAYS: u64 = 3650;
+
+/// How long the data of a guild is kept. Everything is kept until a guild opts in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// After how many days closed lawsuits are deleted, `None` keeps them forever.
+    pub closed_case_days: Option<u64>,
+    /// After how many days the logs of closed lawsuits are deleted, `None` keeps them forever.
+    pub transcript_days: Option<u64>,
+}
+
+impl Lawsuit {
+    /// Whether the lawsuit was deleted by the retention policy and only waits to be purged.
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+fn days_ago(now: DateTime, days: u64) -> DateTime {
+    time::saturating_before(now, days, MILLIS_PER_DAY)
+}
+
+/// Applies the retention policies of every guild, and purges the guilds the bot has left.
+#[tracing::instrument(skip_all)]
+pub async fn enforce_retention(mongo: &Mongo) -> Result<()> {
+    let now = DateTime::now();
+
+    for (guild_id, config) in mongo.find_retention_configs().await? {
+        let result = mongo
+            .apply_retention(
+                guild_id,
+                config.transcript_days.map(|days| days_ago(now, days)),
+                config.closed_case_days.map(|days| days_ago(now, days)),
+                days_ago(now, SOFT_DELETE_GRACE_DAYS),
+                now,
+            )
+            .await;
+        if let Err(err) = result {
+            error!(?err, %guild_id, "Failed to apply retention policy");
+        }
+    }
+
+    for guild_id in mongo
+        .find_departed_guilds(days_ago(now, DEPARTED_GUILD_DAYS))
+        .await?
+    {
+        match mongo.purge_guild(guild_id).await {
+            Ok(()) => info!(%guild_id, "Purged the data of a guild the bot has left"),
+            Err(err) => error!(?err, %guild_id, "Failed to purge guild"),
+        }
+    }
+
+    mongo.purge_trash(days_ago(now, TRASH_DAYS)).await
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::Result;
+
+use crate::{
+    handler::Response,
+    model::{CourtRoom, SnowflakeId},
+};
+
+/// Where the court rooms of a guild are stored.
+pub trait RoomStore {
+    /// Atomically marks a free court room as busy and returns it.
+    async fn claim_free_room(&self, guild_id: SnowflakeId) -> Result<Option<CourtRoom>>;
+
+    async fn room_count(&self, guild_id: SnowflakeId) -> Result<usize>;
+
+    /// Stores a newly created court room that is already marked as busy.
+    async fn add_claimed_room(&self, guild_id: SnowflakeId, room: &CourtRoom) -> Result<()>;
+
+    async fn release_room(&self, guild_id: SnowflakeId, channel_id: SnowflakeId) -> Result<()>;
+}
+
+/// Hands out court rooms so that no two lawsuits end up in the same one.
+#[derive(Debug, Clone, Default)]
+pub struct RoomAllocator {
+    locks: Arc<Mutex<HashMap<SnowflakeId, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl RoomAllocator {
+    /// Claims a free court room or, if there is none, creates a new one with the next free
+    /// number. Room creation is serialized per guild so no number is used twice.
+    pub async fn allocate<S, F, Fut>(
+        &self,
+        store: &S,
+        guild_id: SnowflakeId,
+        create_room: F,
+    ) -> Result<Result<CourtRoom, Response>>
+    where
+        S: RoomStore,
+        F: FnOnce(usize) -> Fut,
+        Fut: Future<Output = Result<Result<CourtRoom, Response>>>,
+    {
+        if let Some(room) = store.claim_free_room(guild_id).await? {
+            return Ok(Ok(room));
+        }
+
+        let lock = self.guild_lock(guild_id);
+        let _guard = lock.lock().await;
+
+        // another lawsuit might have freed or created a room while we were waiting
+        if let Some(room) = store.claim_free_room(guild_id).await? {
+            return Ok(Ok(room));
+        }
+
+        let room_number = store.room_count(guild_id).await? + 1;
+        let room = match create_room(room_number).await? {
+            Ok(room) => CourtRoom {
+                ongoing_lawsuit: true,
+                ..room
+            },
+            Err(response) => return Ok(Err(response)),
+        };
+
+        store.add_claimed_room(guild_id, &room).await?;
+
+        Ok(Ok(room))
+    }
+
+    fn guild_lock(&self, guild_id: SnowflakeId) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.locks.lock().expect("room allocator lock poisoned");
+        locks.entry(guild_id).or_default().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        rooms: Mutex<Vec<CourtRoom>>,
+    }
+
+    impl RoomStore for MemoryStore {
+        async fn claim_free_room(&self, _: SnowflakeId) -> Result<Option<CourtRoom>> {
+            tokio::task::yield_now().await;
+            let mut rooms = self.rooms.lock().unwrap();
+            let room = rooms.iter_mut().find(|room| !room.ongoing_lawsuit);
+            Ok(room.map(|room| {
+                room.ongoing_lawsuit = true;
+                room.clone()
+            }))
+        }
+
+        async fn room_count(&self, _: SnowflakeId) -> Result<usize> {
+            tokio::task::yield_now().await;
+            Ok(self.rooms.lock().unwrap().len())
+        }
+
+        async fn add_claimed_room(&self, _: SnowflakeId, room: &CourtRoom) -> Result<()> {
+            tokio::task::yield_now().await;
+            self.rooms.lock().unwrap().push(room.clone());
+            Ok(())
+        }
+
+        async fn release_room(&self, _: SnowflakeId, channel_id: SnowflakeId) -> Result<()> {
+            let mut rooms = self.rooms.lock().unwrap();
+            for room in rooms
+                .iter_mut()
+                .filter(|room| room.channel_id == channel_id)
+            {
+                room.ongoing_lawsuit = false;
+            }
+            Ok(())
+        }
+    }
+
+    fn room(number: usize) -> CourtRoom {
+        CourtRoom {
+            channel_id: SnowflakeId(number as u64),
+            ongoing_lawsuit: false,
+            role_id: SnowflakeId(1000 + number as u64),
+            voice_channel_id: None,
+        }
+    }
+
+    async fn allocate_concurrently(
+        allocator: &RoomAllocator,
+        store: &Arc<MemoryStore>,
+        count: usize,
+    ) -> Vec<CourtRoom> {
+        let tasks = (0..count).map(|_| {
+            let allocator = allocator.clone();
+            let store = store.clone();
+            tokio::spawn(async move {
+                allocator
+                    .allocate(&*store, SnowflakeId(1), |number| async move {
+                        // creating channels and roles on discord takes a while
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                        Ok(Ok(room(number)))
+                    })
+                    .await
+                    .unwrap()
+                    .unwrap()
+            })
+        });
+
+        let mut rooms = Vec::new();
+        for task in tasks.collect::<Vec<_>>() {
+            rooms.push(task.await.unwrap());
+        }
+        rooms
+    }
+
+    fn assert_unique(rooms: &[CourtRoom]) {
+        let channels: HashSet<_> = rooms.iter().map(|room| room.channel_id).collect();
+        assert_eq!(channels.len(), rooms.len(), "a room was allocated twice");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn concurrent_creations_get_unique_rooms() {
+        let allocator = RoomAllocator::default();
+        let store = Arc::new(MemoryStore::default());
+
+        let rooms = allocate_concurrently(&allocator, &store, 50).await;
+
+        assert_unique(&rooms);
+        assert_eq!(store.rooms.lock().unwrap().len(), 50);
+        assert!(rooms.iter().all(|room| room.ongoing_lawsuit));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn free_rooms_are_claimed_before_creating_new_ones() {
+        let allocator = RoomAllocator::default();
+        let store = Arc::new(MemoryStore::default());
+        store.rooms.lock().unwrap().extend((1..=3).map(room));
+
+        let rooms = allocate_concurrently(&allocator, &store, 10).await;
+
+        assert_unique(&rooms);
+        assert_eq!(store.rooms.lock().unwrap().len(), 10);
+
+        store
+            .release_room(SnowflakeId(1), SnowflakeId(2))
+            .await
+            .unwrap();
+        let reused = allocate_concurrently(&allocator, &store, 1).await;
+        assert_eq!(reused[0].channel_id, SnowflakeId(2));
+    }
+}
@@ -0,0 +1,63 @@
+use std::{sync::Arc, time::Duration};
+
+use poise::serenity_prelude::Http;
+use tracing::error;
+
+use crate::{
+    achievements, appearance, plea, prison, procedure, retention, shutdown::ShutdownSignal, Mongo,
+};
+
+/// How often the scheduler looks for due jobs. Everything it does is stored in the database,
+/// so nothing is lost when the bot restarts in between.
+const TICK: Duration = Duration::from_secs(30);
+
+/// Data is kept for days, checking the retention policies hourly is plenty.
+const RETENTION_TICK: Duration = Duration::from_secs(60 * 60);
+
+/// Runs the periodic jobs of the bot in the background, until the shutdown. The jobs of the
+/// current tick are finished first.
+pub fn start(mongo: Mongo, http: Arc<Http>, shutdown: ShutdownSignal) {
+    let retention_mongo = mongo.clone();
+    let mut retention_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_TICK);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = retention_shutdown.recv() => break,
+            }
+
+            if let Err(err) = retention::enforce_retention(&retention_mongo).await {
+                error!(?err, "Failed to enforce retention policies");
+            }
+        }
+    });
+
+    let mut shutdown = shutdown;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.recv() => break,
+            }
+
+            if let Err(err) = procedure::lift_expired_contempts(&mongo, &http).await {
+                error!(?err, "Failed to lift expired contempts");
+            }
+            if let Err(err) = plea::enter_default_pleas(&mongo, &http).await {
+                error!(?err, "Failed to enter default pleas");
+            }
+            if let Err(err) = appearance::handle_missed_appearances(&mongo, &http).await {
+                error!(?err, "Failed to handle missed appearances");
+            }
+            // before the releases, the stays that end now still count
+            if let Err(err) = achievements::unlock_prison_achievements(&mongo).await {
+                error!(?err, "Failed to unlock prison achievements");
+            }
+            if let Err(err) = prison::release_due_prisoners(&mongo, &http).await {
+                error!(?err, "Failed to release prisoners");
+            }
+        }
+    });
+}
@@ -0,0 +1,147 @@
+use std::{sync::Arc, time::Duration};
+
+use poise::serenity_prelude::ShardManager;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc, watch, Mutex},
+};
+use tracing::{error, info, warn};
+
+use crate::health::Health;
+
+/// How long commands and the scheduler get to finish. Commands waiting for a button give up
+/// right away, docker kills the bot a bit later, see `stop_grace_period` in the compose file.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// The sender every signal clones, taken away when the shutdown starts.
+type DoneSender = Arc<std::sync::Mutex<Option<mpsc::Sender<()>>>>;
+
+/// Tells the background tasks to stop, and waits for them to finish what they are doing.
+pub struct Shutdown {
+    stop: watch::Sender<bool>,
+    done_tx: DoneSender,
+    done_rx: mpsc::Receiver<()>,
+}
+
+/// Hands out signals to background tasks that are started later. Unlike a signal it doesn't hold
+/// up the shutdown, a task that was never started isn't waited for.
+#[derive(Clone)]
+pub struct Signals {
+    stop: watch::Receiver<bool>,
+    done_tx: DoneSender,
+}
+
+/// Held by a background task until it has stopped.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    stop: watch::Receiver<bool>,
+    _done: mpsc::Sender<()>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (stop, _) = watch::channel(false);
+        let (done_tx, done_rx) = mpsc::channel(1);
+        Self {
+            stop,
+            done_tx: Arc::new(std::sync::Mutex::new(Some(done_tx))),
+            done_rx,
+        }
+    }
+
+    pub fn signals(&self) -> Signals {
+        Signals {
+            stop: self.stop.subscribe(),
+            done_tx: self.done_tx.clone(),
+        }
+    }
+
+    /// Stops the background tasks. Returns `false` if they took longer than `timeout`.
+    async fn stop(self, timeout: Duration) -> bool {
+        let Self {
+            stop,
+            done_tx,
+            mut done_rx,
+        } = self;
+
+        let _ = stop.send(true);
+        done_tx.lock().expect("shutdown lock poisoned").take();
+
+        // every signal holds a sender, the channel closes once the last task has dropped it
+        tokio::time::timeout(timeout, done_rx.recv()).await.is_ok()
+    }
+}
+
+impl Signals {
+    /// The signal for a task that starts now, `None` if the shutdown has already started.
+    pub fn signal(&self) -> Option<ShutdownSignal> {
+        let done = self
+            .done_tx
+            .lock()
+            .expect("shutdown lock poisoned")
+            .clone()?;
+        Some(ShutdownSignal {
+            stop: self.stop.clone(),
+            _done: done,
+        })
+    }
+}
+
+impl ShutdownSignal {
+    /// Completes once the shutdown has started.
+    pub async fn recv(&mut self) {
+        while !*self.stop.borrow_and_update() {
+            if self.stop.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn wait_for_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(err) => {
+            error!(?err, "Failed to listen for SIGTERM");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+    }
+}
+
+/// Shuts the bot down gracefully on SIGTERM or SIGINT. The running commands and the current tick
+/// of the scheduler are finished first, everything else the scheduler handles is stored in the
+/// database and picked up after the restart. Then the shards disconnect, which ends the bot.
+pub async fn shutdown_on_signal(
+    shutdown: Shutdown,
+    health: Arc<Health>,
+    shard_manager: Arc<Mutex<ShardManager>>,
+) {
+    wait_for_signal().await;
+
+    info!("Shutting down...");
+    health.set_shutting_down();
+
+    let (scheduler_stopped, commands_finished) = tokio::join!(
+        shutdown.stop(SHUTDOWN_TIMEOUT),
+        health.wait_idle(SHUTDOWN_TIMEOUT)
+    );
+    if !scheduler_stopped {
+        warn!("The scheduler did not stop in time");
+    }
+    if !commands_finished {
+        warn!(
+            running_commands = health.running_commands(),
+            "Commands did not finish in time"
+        );
+    }
+
+    shard_manager.lock().await.shutdown_all().await;
+
+    info!("Disconnected from discord");
+}
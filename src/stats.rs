@@ -0,0 +1,198 @@
+use color_eyre::Result;
+use mongodb::bson::DateTime;
+use poise::serenity_prelude::CreateEmbed;
+use serde::Deserialize;
+
+use crate::{
+    model::SnowflakeId,
+    time::{self, MILLIS_PER_DAY, MILLIS_PER_HOUR},
+    Mongo,
+};
+
+/// The epoch was a thursday, weeks start on the following monday.
+pub const FIRST_MONDAY_MILLIS: i64 = 4 * MILLIS_PER_DAY;
+
+/// How many members are shown in the rankings.
+pub const TOP_COUNT: i64 = 5;
+
+/// Which part of the court activity the statistics are about.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsFilter {
+    /// Only lawsuits opened and prison stays started after this.
+    pub since: Option<DateTime>,
+    /// Only lawsuits this member took part in and their own prison stays.
+    pub user: Option<SnowflakeId>,
+}
+
+impl StatsFilter {
+    /// Only the activity of the last `days`, every day since the epoch counts if there are more.
+    pub fn last_days(days: Option<u64>, user: Option<SnowflakeId>, now: DateTime) -> Self {
+        Self {
+            since: days.map(|days| time::saturating_before(now, days, MILLIS_PER_DAY)),
+            user,
+        }
+    }
+}
+
+/// How often a member shows up in the lawsuits.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RankEntry {
+    #[serde(rename = "_id")]
+    pub user: SnowflakeId,
+    pub count: u64,
+}
+
+/// How many lawsuits were opened in the week starting at `week`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeekCount {
+    #[serde(rename = "_id")]
+    pub week: DateTime,
+    pub count: u64,
+}
+
+/// The lawsuit numbers, as aggregated by `Mongo::aggregate_lawsuit_stats`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LawsuitStats {
+    pub total: u64,
+    pub active: u64,
+    pub convictions: u64,
+    pub acquittals: u64,
+    pub withdrawn: u64,
+    pub dismissed: u64,
+    pub settled: u64,
+    pub guilty_pleas: u64,
+    pub average_duration_millis: Option<f64>,
+    pub judges: Vec<RankEntry>,
+    pub lawyers: Vec<RankEntry>,
+    pub accused: Vec<RankEntry>,
+    /// Only weeks with lawsuits, oldest first.
+    pub weeks: Vec<WeekCount>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CourtStats {
+    pub lawsuits: LawsuitStats,
+    pub prison_millis: i64,
+}
+
+impl CourtStats {
+    pub async fn load(
+        mongo: &Mongo,
+        guild_id: SnowflakeId,
+        filter: StatsFilter,
+        now: DateTime,
+    ) -> Result<Self> {
+        let lawsuits = mongo.aggregate_lawsuit_stats(guild_id, filter).await?;
+        let prison_millis = mongo.aggregate_prison_millis(guild_id, filter, now).await?;
+        Ok(Self {
+            lawsuits,
+            prison_millis,
+        })
+    }
+
+    /// The share of verdicts that were convictions, `None` without any verdicts.
+    pub fn conviction_rate(&self) -> Option<f64> {
+        let verdicts = self.lawsuits.convictions + self.lawsuits.acquittals;
+        (verdicts > 0).then(|| self.lawsuits.convictions as f64 / verdicts as f64)
+    }
+}
+
+fn ranking(entries: &[RankEntry]) -> String {
+    if entries.is_empty() {
+        return "Niemand".to_string();
+    }
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| format!("{}. <@{}> ({})", i + 1, entry.user, entry.count))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn stats_embed<'a>(embed: &'a mut CreateEmbed, stats: &CourtStats) -> &'a mut CreateEmbed {
+    let lawsuits = &stats.lawsuits;
+
+    let conviction_rate = match stats.conviction_rate() {
+        Some(rate) => format!("{:.0}%", rate * 100.0),
+        None => "-".to_string(),
+    };
+    let average_duration = match lawsuits.average_duration_millis {
+        Some(millis) => format!("{:.1}h", millis / MILLIS_PER_HOUR as f64),
+        None => "-".to_string(),
+    };
+
+    embed
+        .field("Prozesse", lawsuits.total, true)
+        .field("Laufend", lawsuits.active, true)
+        .field("Verurteilungsquote", conviction_rate, true)
+        .field("Geständnisse", lawsuits.guilty_pleas, true)
+        .field("Durchschnittliche Dauer", average_duration, true)
+        .field(
+            "Zeit im Gefängnis",
+            format!(
+                "{:.1}h",
+                stats.prison_millis as f64 / MILLIS_PER_HOUR as f64
+            ),
+            true,
+        )
+        .field("Fleissigste Richter", ranking(&lawsuits.judges), false)
+        .field("Fleissigste Anwälte", ranking(&lawsuits.lawyers), false)
+        .field("Am meisten verklagt", ranking(&lawsuits.accused), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: DateTime = DateTime::from_millis(1_650_000_000_000);
+
+    fn stats(convictions: u64, acquittals: u64) -> CourtStats {
+        CourtStats {
+            lawsuits: LawsuitStats {
+                total: convictions + acquittals + 2,
+                convictions,
+                acquittals,
+                withdrawn: 1,
+                settled: 1,
+                ..LawsuitStats::default()
+            },
+            prison_millis: 0,
+        }
+    }
+
+    #[test]
+    fn conviction_rate_counts_only_verdicts() {
+        assert_eq!(stats(3, 1).conviction_rate(), Some(0.75));
+        assert_eq!(stats(0, 2).conviction_rate(), Some(0.0));
+        assert_eq!(stats(2, 0).conviction_rate(), Some(1.0));
+    }
+
+    #[test]
+    fn conviction_rate_without_verdicts_is_none() {
+        assert_eq!(stats(0, 0).conviction_rate(), None);
+    }
+
+    #[test]
+    fn last_days_starts_that_many_days_ago() {
+        let user = Some(SnowflakeId(1));
+
+        let filter = StatsFilter::last_days(Some(7), user, NOW);
+
+        assert_eq!(
+            filter.since,
+            Some(DateTime::from_millis(
+                NOW.timestamp_millis() - 7 * MILLIS_PER_DAY
+            ))
+        );
+        assert_eq!(filter.user, user);
+        assert_eq!(StatsFilter::last_days(None, None, NOW).since, None);
+    }
+
+    #[test]
+    fn last_days_beyond_the_epoch_counts_everything() {
+        for days in [i64::MAX as u64 / 1000, u64::MAX] {
+            let filter = StatsFilter::last_days(Some(days), None, NOW);
+            assert_eq!(filter.since, Some(DateTime::MIN));
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use mongodb::bson::DateTime;
+
+pub const MILLIS_PER_MINUTE: i64 = 60 * 1000;
+pub const MILLIS_PER_HOUR: i64 = 60 * MILLIS_PER_MINUTE;
+pub const MILLIS_PER_DAY: i64 = 24 * MILLIS_PER_HOUR;
+pub const MILLIS_PER_WEEK: i64 = 7 * MILLIS_PER_DAY;
+
+/// `count` times `unit` in milliseconds, `None` if that doesn't fit into a timestamp.
+pub fn checked_millis(count: u64, unit: i64) -> Option<i64> {
+    i64::try_from(count).ok()?.checked_mul(unit)
+}
+
+/// The time `count` units after `now`, `None` if it is out of range.
+pub fn checked_after(now: DateTime, count: u64, unit: i64) -> Option<DateTime> {
+    let millis = checked_millis(count, unit)?;
+    Some(DateTime::from_millis(
+        now.timestamp_millis().checked_add(millis)?,
+    ))
+}
+
+/// The time `count` units before `now`, `None` if it is out of range.
+pub fn checked_before(now: DateTime, count: u64, unit: i64) -> Option<DateTime> {
+    let millis = checked_millis(count, unit)?;
+    Some(DateTime::from_millis(
+        now.timestamp_millis().checked_sub(millis)?,
+    ))
+}
+
+/// Like `checked_after`, but the latest representable time if it is out of range.
+pub fn saturating_after(now: DateTime, count: u64, unit: i64) -> DateTime {
+    checked_after(now, count, unit).unwrap_or(DateTime::MAX)
+}
+
+/// Like `checked_before`, but the earliest representable time if it is out of range.
+pub fn saturating_before(now: DateTime, count: u64, unit: i64) -> DateTime {
+    checked_before(now, count, unit).unwrap_or(DateTime::MIN)
+}
+
+/// The time `hours` after `now`, for fractional durations like prison sentences. Saturates like
+/// `saturating_after`.
+pub fn saturating_after_hours(now: DateTime, hours: f64) -> DateTime {
+    // float to int casts saturate, NaN becomes 0
+    let millis = (hours * MILLIS_PER_HOUR as f64) as i64;
+    DateTime::from_millis(now.timestamp_millis().saturating_add(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: DateTime = DateTime::from_millis(1_650_000_000_000);
+
+    #[test]
+    fn durations_in_range_are_exact() {
+        assert_eq!(checked_millis(3, MILLIS_PER_HOUR), Some(3 * 60 * 60 * 1000));
+        assert_eq!(
+            saturating_before(NOW, 90, MILLIS_PER_MINUTE),
+            DateTime::from_millis(1_650_000_000_000 - 90 * 60 * 1000)
+        );
+        assert_eq!(
+            saturating_after(NOW, 2, MILLIS_PER_DAY),
+            DateTime::from_millis(1_650_000_000_000 + 2 * 24 * 60 * 60 * 1000)
+        );
+        assert_eq!(
+            saturating_after_hours(NOW, 1.5),
+            DateTime::from_millis(1_650_000_000_000 + 90 * 60 * 1000)
+        );
+    }
+
+    #[test]
+    fn durations_out_of_range_saturate() {
+        assert_eq!(checked_millis(u64::MAX, 1), None);
+        assert_eq!(checked_millis(i64::MAX as u64, MILLIS_PER_DAY), None);
+        assert_eq!(checked_before(DateTime::MIN, 1, 1), None);
+        assert_eq!(checked_after(DateTime::MAX, 1, 1), None);
+        assert_eq!(
+            saturating_after(NOW, u64::MAX, MILLIS_PER_MINUTE),
+            DateTime::MAX
+        );
+        assert_eq!(saturating_after_hours(NOW, f64::INFINITY), DateTime::MAX);
+        assert_eq!(
+            saturating_before(NOW, u64::MAX, MILLIS_PER_DAY),
+            DateTime::MIN
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use color_eyre::Result;
+use poise::{serenity::model::prelude::*, serenity_prelude::Http};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    discord::{discord_error_code, retry, SetupStep, UNKNOWN_MEMBER},
+    model::{CourtRoom, SnowflakeId},
+    WrapErr,
+};
+
+/// Where the lawsuits of a guild take place.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CourtMode {
+    /// Every lawsuit gets a `gerichtsraum-N` channel from the pool, participants get its role.
+    #[default]
+    Rooms,
+    /// Every lawsuit gets a private thread in the court channel, participants are added to it.
+    Threads { channel_id: SnowflakeId },
+}
+
+/// The place of a single lawsuit.
+#[derive(Debug, Clone)]
+pub enum Venue {
+    Room(CourtRoom),
+    Thread(SnowflakeId),
+}
+
+impl Venue {
+    /// The channel where the messages of the lawsuit are posted.
+    pub fn channel_id(&self) -> SnowflakeId {
+        match self {
+            Self::Room(room) => room.channel_id,
+            Self::Thread(thread_id) => *thread_id,
+        }
+    }
+
+    /// Gives the user access to the venue.
+    pub async fn admit(&self, http: &Http, guild_id: GuildId, user: SnowflakeId) -> Result<()> {
+        match self {
+            Self::Room(room) => assign_role(user, http, guild_id, room.role_id)
+                .await
+                .wrap_err(SetupStep::AssignRole(user)),
+            Self::Thread(thread_id) => ChannelId::from(*thread_id)
+                .add_thread_member(http, user.into())
+                .await
+                .wrap_err(SetupStep::AddToThread(user)),
+        }
+    }
+
+    /// Takes the access to the venue away from the user, doing nothing if they have left the
+    /// guild.
+    pub async fn expel(&self, http: &Http, guild_id: GuildId, user: SnowflakeId) -> Result<()> {
+        match self {
+            Self::Room(room) => remove_role(user, http, guild_id, room.role_id).await,
+            Self::Thread(thread_id) => {
+                match ChannelId::from(*thread_id)
+                    .remove_thread_member(http, user.into())
+                    .await
+                {
+                    Err(err) if discord_error_code(&err) == Some(UNKNOWN_MEMBER) => {
+                        info!(%user, "Member has left the guild, not removing from thread");
+                        Ok(())
+                    }
+                    result => result.wrap_err("remove member from thread"),
+                }
+            }
+        }
+    }
+
+    /// Makes the venue read only after the lawsuit is closed. The roles of a court room are
+    /// taken away from the participants, returning the ones where that failed. Threads are
+    /// archived and locked, so the participants can still read them.
+    pub async fn close(
+        &self,
+        http: &Http,
+        guild_id: GuildId,
+        participants: &[SnowflakeId],
+    ) -> Result<Vec<SnowflakeId>> {
+        match self {
+            Self::Room(_) => {
+                let mut failed = Vec::new();
+                for &user in participants {
+                    if let Err(err) = retry(|| self.expel(http, guild_id, user)).await {
+                        error!(?err, %user, "Failed to remove court room role");
+                        failed.push(user);
+                    }
+                }
+                Ok(failed)
+            }
+            Self::Thread(thread_id) => {
+                ChannelId::from(*thread_id)
+                    .edit_thread(http, |thread| thread.archived(true).locked(true))
+                    .await
+                    .wrap_err("archive thread")?;
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Removes a venue that was created for a lawsuit which could not be opened. Court rooms are
+    /// kept for the next lawsuit.
+    pub async fn discard(&self, http: &Http) -> Result<()> {
+        match self {
+            Self::Room(_) => Ok(()),
+            Self::Thread(thread_id) => {
+                ChannelId::from(*thread_id)
+                    .delete(http)
+                    .await
+                    .wrap_err("delete thread")?;
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn assign_role(
+    user: SnowflakeId,
+    http: &Http,
+    guild_id: GuildId,
+    role_id: SnowflakeId,
+) -> Result<()> {
+    let mut member = guild_id.member(http, user).await.wrap_err("fetch member")?;
+    member
+        .add_role(http, role_id)
+        .await
+        .wrap_err("add role to member")?;
+
+    Ok(())
+}
+
+async fn remove_role(
+    user: SnowflakeId,
+    http: &Http,
+    guild_id: GuildId,
+    role_id: SnowflakeId,
+) -> Result<()> {
+    let mut member = match guild_id.member(http, user).await {
+        Ok(member) => member,
+        Err(err) if discord_error_code(&err) == Some(UNKNOWN_MEMBER) => {
+            info!(%user, "Member has left the guild, not removing role");
+            return Ok(());
+        }
+        Err(err) => return Err(err).wrap_err("fetch member"),
+    };
+    member
+        .remove_role(http, role_id)
+        .await
+        .wrap_err("remove role from member")?;
+
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use std::sync::Arc;
+
+use color_eyre::Result;
+use mongodb::bson::Uuid;
+use poise::{
+    serenity::model::{
+        interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+        prelude::*,
+    },
+    serenity_prelude::Http,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    handler::Response,
+    lawsuit::{Lawsuit, LawsuitCtx},
+    model::SnowflakeId,
+    procedure::CaseEvent,
+    venue::Venue,
+    Mongo, WrapErr,
+};
+
+const SUBPOENA_PREFIX: &str = "subpoena";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WitnessStatus {
+    /// The subpoena was sent, but not answered yet.
+    Summoned,
+    /// The witness is testifying and has access to the court room.
+    Accepted,
+    Declined,
+    /// The testimony is over.
+    Dismissed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Witness {
+    pub user: SnowflakeId,
+    pub status: WitnessStatus,
+}
+
+impl Witness {
+    pub fn mention(&self) -> String {
+        let icon = match self.status {
+            WitnessStatus::Summoned => "⏳",
+            WitnessStatus::Accepted => "✅",
+            WitnessStatus::Declined => "❌",
+            WitnessStatus::Dismissed => "🏁",
+        };
+        format!("{icon} <@{}>", self.user)
+    }
+}
+
+impl Lawsuit {
+    pub fn witness(&self, user: SnowflakeId) -> Option<&Witness> {
+        self.witnesses.iter().find(|witness| witness.user == user)
+    }
+
+    /// The witnesses that are currently testifying.
+    pub fn testifying_witnesses(&self) -> impl Iterator<Item = SnowflakeId> + '_ {
+        self.witnesses
+            .iter()
+            .filter(|witness| witness.status == WitnessStatus::Accepted)
+            .map(|witness| witness.user)
+    }
+}
+
+impl LawsuitCtx {
+    /// Sends a subpoena to the user, which they can accept or decline in their DMs.
+    pub async fn summon_witness(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        witness: &User,
+    ) -> Result<Result<(), Response>> {
+        let lawsuit = &self.lawsuit;
+        if !lawsuit.is_participant(user_id.into()) && !permission_override {
+            return Ok(Err(Response(
+                "nur lüt wo am prozess beteiligt sind chönd zeuge vorlade".to_string(),
+            )));
+        }
+        if witness.bot {
+            return Ok(Err(Response("bots chönd kei zeuge si".to_string())));
+        }
+
+        let witness_id = witness.id.into();
+        if lawsuit.is_participant(witness_id) {
+            return Ok(Err(Response(format!(
+                "<@{witness_id}> isch scho am prozess beteiligt"
+            ))));
+        }
+        if let Some(existing) = lawsuit.witness(witness_id) {
+            if matches!(
+                existing.status,
+                WitnessStatus::Summoned | WitnessStatus::Accepted
+            ) {
+                return Ok(Err(Response(format!("<@{witness_id}> isch scho vorglade"))));
+            }
+        }
+
+        let witness_entry = Witness {
+            user: witness_id,
+            status: WitnessStatus::Summoned,
+        };
+        if lawsuit.witness(witness_id).is_some() {
+            self.mongo_client
+                .set_witness_status(
+                    self.guild_id.into(),
+                    lawsuit.id,
+                    witness_id,
+                    WitnessStatus::Summoned,
+                )
+                .await?;
+        } else {
+            self.mongo_client
+                .add_witness(self.guild_id.into(), lawsuit.id, &witness_entry)
+                .await?;
+        }
+        self.set_local_status(witness_id, WitnessStatus::Summoned);
+
+        let sent = self.send_subpoena(witness).await;
+        if let Err(err) = sent {
+            // DMs might be closed, the witness can't be reached then
+            info!(?err, %witness_id, "Could not send subpoena");
+            self.mongo_client
+                .set_witness_status(
+                    self.guild_id.into(),
+                    self.lawsuit.id,
+                    witness_id,
+                    WitnessStatus::Declined,
+                )
+                .await?;
+            return Ok(Err(Response(format!(
+                "ich ha <@{witness_id}> kei vorladig chöne schicke, vilicht sind d DMs zue"
+            ))));
+        }
+
+        self.log(
+            Some(user_id.into()),
+            CaseEvent::WitnessSummoned { user: witness_id },
+        )
+        .await?;
+
+        Ok(Ok(()))
+    }
+
+    async fn send_subpoena(&self, witness: &User) -> Result<()> {
+        let guild_name = self
+            .guild_id
+            .to_partial_guild(&self.http)
+            .await
+            .wrap_err("fetch partial guild")?
+            .name;
+        let lawsuit = &self.lawsuit;
+
+        witness
+            .direct_message(&self.http, |msg| {
+                msg.embed(|embed| {
+                    embed
+                        .title("Vorladung")
+                        .description(format!(
+                            "Du wirst auf **{guild_name}** als Zeuge im Prozess gegen <@{}> vorgeladen.",
+                            lawsuit.accused
+                        ))
+                        .field("Grund", &lawsuit.reason, false)
+                        .field("Richter", format!("<@{}>", lawsuit.judge), true)
+                })
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_button(|b| {
+                            b.custom_id(subpoena_id(true, self.guild_id, lawsuit.id))
+                                .label("Aussagen")
+                                .style(ButtonStyle::Success)
+                        })
+                        .create_button(|b| {
+                            b.custom_id(subpoena_id(false, self.guild_id, lawsuit.id))
+                                .label("Ablehnen")
+                                .style(ButtonStyle::Danger)
+                        })
+                    })
+                })
+            })
+            .await
+            .wrap_err("send subpoena")?;
+
+        Ok(())
+    }
+
+    /// Answers the subpoena of the witness. Accepted witnesses are admitted to the court room.
+    pub async fn answer_subpoena(
+        &mut self,
+        witness: SnowflakeId,
+        accept: bool,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        if self.lawsuit.is_closed() {
+            return Ok(Err(Response(
+                "de prozess isch scho abgschlosse".to_string(),
+            )));
+        }
+        match self.lawsuit.witness(witness) {
+            Some(Witness {
+                status: WitnessStatus::Summoned,
+                ..
+            }) => {}
+            _ => {
+                return Ok(Err(Response(
+                    "du bisch für de prozess nöd (meh) vorglade".to_string(),
+                )))
+            }
+        }
+
+        let status = if accept {
+            WitnessStatus::Accepted
+        } else {
+            WitnessStatus::Declined
+        };
+
+        if accept {
+            venue.admit(&self.http, self.guild_id, witness).await?;
+        }
+
+        self.mongo_client
+            .set_witness_status(self.guild_id.into(), self.lawsuit.id, witness, status)
+            .await?;
+        self.set_local_status(witness, status);
+
+        let (event, announcement) = if accept {
+            (
+                CaseEvent::WitnessAccepted { user: witness },
+                format!("<@{witness}> isch als zeuge erschiene"),
+            )
+        } else {
+            (
+                CaseEvent::WitnessDeclined { user: witness },
+                format!("<@{witness}> wott nöd als zeuge ussäge"),
+            )
+        };
+        self.log(Some(witness), event).await?;
+
+        ChannelId::from(venue.channel_id())
+            .say(&self.http, announcement)
+            .await
+            .wrap_err("announce witness")?;
+
+        Ok(Ok(()))
+    }
+
+    /// Ends the testimony of the witness and takes away their access to the court room.
+    pub async fn dismiss_witness(
+        &mut self,
+        permission_override: bool,
+        user_id: UserId,
+        witness: SnowflakeId,
+        venue: &Venue,
+    ) -> Result<Result<(), Response>> {
+        if self.lawsuit.judge != user_id.into() && !permission_override {
+            return Ok(Err(Response("du häsch kei recht für da!".to_string())));
+        }
+        if self.lawsuit.witness(witness).is_none() {
+            return Ok(Err(Response(format!("<@{witness}> isch kei zeuge"))));
+        }
+
+        let was_testifying = self.lawsuit.testifying_witnesses().any(|w| w == witness);
+
+        self.mongo_client
+            .set_witness_status(
+                self.guild_id.into(),
+                self.lawsuit.id,
+                witness,
+                WitnessStatus::Dismissed,
+            )
+            .await?;
+        self.set_local_status(witness, WitnessStatus::Dismissed);
+
+        if was_testifying && !self.lawsuit.is_participant(witness) {
+            venue.expel(&self.http, self.guild_id, witness).await?;
+        }
+
+        self.log(
+            Some(user_id.into()),
+            CaseEvent::WitnessDismissed { user: witness },
+        )
+        .await?;
+
+        Ok(Ok(()))
+    }
+
+    fn set_local_status(&mut self, user: SnowflakeId, status: WitnessStatus) {
+        match self
+            .lawsuit
+            .witnesses
+            .iter_mut()
+            .find(|witness| witness.user == user)
+        {
+            Some(witness) => witness.status = status,
+            None => self.lawsuit.witnesses.push(Witness { user, status }),
+        }
+    }
+}
+
+fn subpoena_id(accept: bool, guild_id: GuildId, lawsuit_id: Uuid) -> String {
+    let answer = if accept { "accept" } else { "decline" };
+    format!("{SUBPOENA_PREFIX}:{answer}:{guild_id}:{lawsuit_id}")
+}
+
+/// Parses the custom id of a subpoena button into the answer, the guild and the lawsuit.
+fn parse_subpoena_id(custom_id: &str) -> Option<(bool, GuildId, Uuid)> {
+    let mut parts = custom_id.split(':');
+    if parts.next()? != SUBPOENA_PREFIX {
+        return None;
+    }
+    let accept = match parts.next()? {
+        "accept" => true,
+        "decline" => false,
+        _ => return None,
+    };
+    let guild_id = GuildId(parts.next()?.parse().ok()?);
+    let lawsuit_id = Uuid::parse_str(parts.next()?).ok()?;
+    Some((accept, guild_id, lawsuit_id))
+}
+
+/// Handles a click on a subpoena button in the DMs of a witness. Returns `false` if the
+/// interaction wasn't a subpoena answer.
+pub async fn handle_subpoena_interaction(
+    mongo: &Mongo,
+    http: &Arc<Http>,
+    interaction: &MessageComponentInteraction,
+) -> Result<bool> {
+    let (accept, guild_id, lawsuit_id) = match parse_subpoena_id(&interaction.data.custom_id) {
+        Some(parsed) => parsed,
+        None => return Ok(false),
+    };
+
+    let state = mongo.find_or_insert_state(guild_id.into()).await?;
+    let lawsuit = state
+        .lawsuits
+        .iter()
+        .find(|lawsuit| lawsuit.id == lawsuit_id);
+
+    let response = match lawsuit {
+        Some(lawsuit) => {
+            let venue = state.venue(lawsuit.court_room);
+            let mut lawsuit_ctx = LawsuitCtx {
+                lawsuit: lawsuit.clone(),
+                mongo_client: mongo.clone(),
+                http: http.clone(),
+                guild_id,
+            };
+            lawsuit_ctx
+                .answer_subpoena(interaction.user.id.into(), accept, &venue)
+                .await?
+        }
+        None => Err(Response("de prozess gits nüme".to_string())),
+    };
+
+    let content = match response {
+        Ok(()) if accept => {
+            "Du hesch d vorladig aagno, de gerichtsruum isch jetzt offe für di.".to_string()
+        }
+        Ok(()) => "Du hesch d vorladig abglehnt.".to_string(),
+        Err(response) => response.to_string(),
+    };
+
+    interaction
+        .create_interaction_response(http, |response| {
+            response
+                .kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|data| data.content(content).components(|c| c))
+        })
+        .await
+        .wrap_err("respond to subpoena interaction")?;
+
+    Ok(true)
+}